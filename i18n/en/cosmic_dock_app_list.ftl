@@ -1 +1,86 @@
 cosmic-dock-app-list = Cosmic Dock App List
+
+# window popover
+new-window = New Window
+add-favorite = Add to Favorites
+remove-favorite = Remove from Favorites
+quit = Quit
+quit-all-windows = Quit All Windows
+workspace = Workspace { $number }
+other-workspace = Other
+minimize = Minimize
+maximize = Maximize
+move-to-workspace = Move to Workspace
+
+# context menu
+launch-new-window = Launch New Window
+pin = Pin
+unpin = Unpin
+quit-all = Quit All
+empty-trash = Empty Trash
+eject = Eject
+clear-recent = Clear History
+clear-badge = Clear Badge
+hidden-app = This app is hidden from the app library and can't be pinned
+pin-anyway = Pin Anyway
+unpin-delete = Unpin and Delete Entry
+
+# keep in dock prompt
+keep-in-dock = Keep { $app } in the dock?
+
+# sections
+running-apps = Running
+
+# system items
+trash = Trash
+
+# tooltip
+window-count = { $count ->
+    [one] 1 window
+   *[other] { $count } windows
+}
+
+# screen reader
+pinned-state = pinned
+launching-state = launching
+attention-state = needs attention
+close-window = Close { $window }
+window-actions = Actions for { $window }
+saved-apps = Pinned apps
+running-apps-group = Running apps
+recent-apps = Recent apps
+system-items = Drives and trash
+
+# settings
+preferences = Preferences
+icon-size = Icon size
+icon-size-automatic = Same as the dock
+icon-size-pixels = { $size } pixels
+click-action = Clicking a running app
+click-show-popover = Shows its windows
+click-focus-last = Focuses its last used window
+click-cycle-windows = Cycles through its windows
+click-minimize-toggle = Minimizes or focuses it
+show-saved = Show pinned apps
+show-active = Show running apps
+sort-order = Order of running apps
+sort-launch-order = Launch order
+sort-most-recently-used = Most recently used
+sort-alphabetical = Alphabetical
+grouping-mode = Running windows
+group-by-app = One icon per app
+one-per-window = One icon per window
+animations = Animations
+
+# toasts
+dismiss = Dismiss
+launch-failed = Couldn't launch { $app }: { $error }
+focus-failed = Couldn't focus the window: { $error }
+close-failed = Couldn't close the window: { $error }
+minimize-failed = Couldn't minimize the window: { $error }
+maximize-failed = Couldn't maximize the window: { $error }
+move-failed = Couldn't move the window: { $error }
+pin-failed = Couldn't pin { $app }: { $error }
+delete-entry-failed = Couldn't delete the desktop entry of { $app }: { $error }
+save-failed = Couldn't save the pinned apps: { $error }
+daemon-unavailable = Window list unavailable, only saved apps can be launched
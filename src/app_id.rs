@@ -0,0 +1,150 @@
+// SPDX-License-Identifier: MPL-2.0-only
+
+//! Normalized app ids, so the windows, desktop entries and pinned ids of one app compare equal.
+//! Flatpak and Snap packages and games started by Steam report other ids than their desktop
+//! entries have.
+
+use gio::DesktopAppInfo;
+use gtk4::prelude::*;
+
+// ids that differ between packagings of the same app, both sides are normalized to the second
+const ALIASES: &[(&str, &str)] = &[
+    ("org.mozilla.firefox", "firefox"),
+    ("firefox-esr", "firefox"),
+    ("org.mozilla.thunderbird", "thunderbird"),
+    ("com.valvesoftware.steam", "steam"),
+    ("org.libreoffice.libreoffice", "libreoffice-startcenter"),
+    ("org.libreoffice.libreoffice.base", "libreoffice-base"),
+    ("org.libreoffice.libreoffice.calc", "libreoffice-calc"),
+    ("org.libreoffice.libreoffice.draw", "libreoffice-draw"),
+    ("org.libreoffice.libreoffice.impress", "libreoffice-impress"),
+    ("org.libreoffice.libreoffice.math", "libreoffice-math"),
+    ("org.libreoffice.libreoffice.writer", "libreoffice-writer"),
+];
+
+// the launch URI of Steam games, whose windows report `steam_app_<id>`
+const STEAM_GAME_URI: &str = "steam://rungameid/";
+
+/// `id` in lowercase without ".desktop", with known aliases resolved.
+pub fn normalize(id: &str) -> String {
+    let id = id.trim();
+    let id = id.strip_suffix(".desktop").unwrap_or(id).to_lowercase();
+    ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == id)
+        .map(|(_, id)| id.to_string())
+        .unwrap_or(id)
+}
+
+/// Whether two desktop ids or window app ids belong to the same app.
+pub fn same_app(a: &str, b: &str) -> bool {
+    !a.is_empty() && !b.is_empty() && normalize(a) == normalize(b)
+}
+
+/// The normalized ids the windows of the app with desktop id `id` may report.
+pub fn app_info_ids(app_info: &DesktopAppInfo, id: &str) -> Vec<String> {
+    let mut ids = Vec::new();
+    if !id.is_empty() {
+        ids.push(normalize(id));
+    }
+    if let Some(class) = app_info.startup_wm_class() {
+        ids.push(normalize(&class));
+    }
+    // the id inside the sandbox, exported entries may be renamed
+    if let Some(flatpak) = app_info.string("X-Flatpak") {
+        ids.push(normalize(&flatpak));
+    }
+    // snap desktop ids are "<snap>_<app>", the windows report the snap or app name
+    if let Some(snap) = app_info.string("X-SnapInstanceName") {
+        let prefix = format!("{}_", snap);
+        if let Some(app) = id.strip_prefix(&prefix) {
+            ids.push(normalize(app));
+        }
+        ids.push(normalize(&snap));
+    }
+    if let Some(game) = app_info
+        .commandline()
+        .and_then(|commandline| steam_game_id(&commandline.to_string_lossy()))
+    {
+        ids.push(format!("steam_app_{}", game));
+    }
+    ids.sort();
+    ids.dedup();
+    ids
+}
+
+fn steam_game_id(commandline: &str) -> Option<String> {
+    let start = commandline.find(STEAM_GAME_URI)? + STEAM_GAME_URI.len();
+    let game: String = commandline[start..]
+        .chars()
+        .take_while(|c| c.is_ascii_digit())
+        .collect();
+    if game.is_empty() {
+        None
+    } else {
+        Some(game)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_strips_the_suffix_and_case() {
+        assert_eq!(normalize("Firefox.desktop"), "firefox");
+        assert_eq!(
+            normalize(" org.gnome.Terminal.desktop\n"),
+            "org.gnome.terminal"
+        );
+        assert_eq!(normalize("steam_app_570"), "steam_app_570");
+    }
+
+    #[test]
+    fn aliases_resolve_in_any_spelling() {
+        for (alias, id) in ALIASES {
+            assert_eq!(normalize(alias), *id, "{}", alias);
+            assert_eq!(normalize(&format!("{}.desktop", alias)), *id, "{}", alias);
+            assert_eq!(normalize(&alias.to_uppercase()), *id, "{}", alias);
+            assert!(same_app(alias, &format!("{}.desktop", id)), "{}", alias);
+        }
+    }
+
+    #[test]
+    fn same_app_across_packagings() {
+        assert!(same_app("firefox.desktop", "org.mozilla.firefox"));
+        assert!(same_app(
+            "firefox-esr.desktop",
+            "org.mozilla.firefox.desktop"
+        ));
+        assert!(same_app("com.valvesoftware.Steam.desktop", "steam"));
+        assert!(same_app(
+            "org.libreoffice.LibreOffice.writer.desktop",
+            "libreoffice-writer"
+        ));
+        // the suites share a prefix, not an app
+        assert!(!same_app("libreoffice-writer", "libreoffice-calc"));
+        assert!(!same_app(
+            "org.libreoffice.LibreOffice",
+            "org.libreoffice.LibreOffice.calc"
+        ));
+        assert!(!same_app("firefox", "thunderbird"));
+        assert!(!same_app("", ""));
+        assert!(!same_app("firefox", ""));
+    }
+
+    #[test]
+    fn steam_game_ids_from_the_launch_command() {
+        assert_eq!(
+            steam_game_id("steam steam://rungameid/570"),
+            Some("570".to_string())
+        );
+        assert_eq!(
+            steam_game_id("/usr/bin/steam -silent steam://rungameid/1091500 %U"),
+            Some("1091500".to_string())
+        );
+        assert_eq!(steam_game_id("steam steam://rungameid/"), None);
+        assert_eq!(steam_game_id("steam steam://open/games"), None);
+        assert_eq!(steam_game_id("firefox %u"), None);
+    }
+}
@@ -1,14 +1,66 @@
 // SPDX-License-Identifier: MPL-2.0-only
+use cosmic_panel_config::config::CosmicPanelConfig;
+use glib::subclass::Signal;
+use glib::{ParamFlags, ParamSpec, ParamSpecInt, Value};
 use gtk4::glib;
+use gtk4::prelude::*;
 use gtk4::subclass::prelude::*;
-use once_cell::sync::OnceCell;
+use once_cell::sync::{Lazy, OnceCell};
+use std::cell::{Cell, RefCell};
+use std::collections::HashSet;
+use tokio::sync::mpsc::Sender;
 
+use crate::config::{DisplayMode, GroupingMode, SortOrder};
+use crate::dock_item::DockItem;
 use crate::dock_list::DockList;
+use crate::system_items::SystemItems;
+use crate::toast::Toast;
+use crate::utils::Event;
 
 #[derive(Default)]
 pub struct AppsContainer {
     pub saved_list: OnceCell<DockList>,
     pub active_list: OnceCell<DockList>,
+    pub separator: OnceCell<gtk4::Separator>,
+    pub separator_box: OnceCell<gtk4::Box>,
+    pub separator_revealer: OnceCell<gtk4::Revealer>,
+    pub active_heading: OnceCell<gtk4::Label>,
+    pub active_scroller: OnceCell<gtk4::ScrolledWindow>,
+    pub recent_list: OnceCell<DockList>,
+    pub recent_separator: OnceCell<gtk4::Separator>,
+    pub recent_count: Cell<usize>,
+    pub system_list: OnceCell<DockList>,
+    pub system_separator: OnceCell<gtk4::Separator>,
+    pub system_items: RefCell<Option<SystemItems>>,
+    pub toast: OnceCell<Toast>,
+    pub window_model: OnceCell<gtk4::gio::ListStore>,
+    pub overflow_button: OnceCell<gtk4::MenuButton>,
+    pub overflow_menu: OnceCell<gtk4::Box>,
+    pub overflow_mode: Cell<super::OverflowMode>,
+    pub overflow_update_queued: Cell<bool>,
+    pub hide_saved: Cell<bool>,
+    pub hide_active: Cell<bool>,
+    pub daemon_unavailable: Cell<bool>,
+    pub keep_visible: RefCell<Vec<String>>,
+    pub output_filter: RefCell<Option<String>>,
+    pub all_outputs: Cell<bool>,
+    pub sort_order: Cell<SortOrder>,
+    pub grouping_mode: Cell<GroupingMode>,
+    pub display_mode: Cell<DisplayMode>,
+    pub preferred_length: Cell<i32>,
+    pub overlapped: Cell<bool>,
+    pub icon_size_override: Cell<u32>,
+    pub animations_enabled: Cell<bool>,
+    // the one popover of the dock that is open and the item it belongs to
+    pub open_popover: RefCell<Option<(gtk4::Popover, glib::WeakRef<DockItem>)>>,
+    pub drag_motion: OnceCell<gtk4::DropControllerMotion>,
+    // apps that offered to be kept in the dock this session, whether or not it was answered
+    pub keep_prompted: RefCell<HashSet<String>>,
+    // windows closed from the dock, their apps were quit on purpose
+    pub dock_quits: RefCell<HashSet<(u32, u32)>>,
+    pub pinned: RefCell<Vec<String>>,
+    pub tx: OnceCell<Sender<Event>>,
+    pub config: RefCell<CosmicPanelConfig>,
 }
 
 #[glib::object_subclass]
@@ -19,8 +71,52 @@ impl ObjectSubclass for AppsContainer {
     type ParentType = gtk4::Box;
 }
 
-impl ObjectImpl for AppsContainer {}
+impl ObjectImpl for AppsContainer {
+    fn signals() -> &'static [Signal] {
+        static SIGNALS: Lazy<Vec<Signal>> = Lazy::new(|| {
+            vec![
+                Signal::builder(
+                    // emitted when windows start or stop covering the dock, true if they do
+                    "overlap-changed",
+                    &[bool::static_type().into()],
+                    <()>::static_type().into(),
+                )
+                .build(),
+                // emitted after the pinned apps changed, however they were changed
+                Signal::builder("pinned-changed", &[], <()>::static_type().into()).build(),
+            ]
+        });
+        SIGNALS.as_ref()
+    }
 
-impl WidgetImpl for AppsContainer {}
+    fn properties() -> &'static [ParamSpec] {
+        static PROPERTIES: Lazy<Vec<ParamSpec>> = Lazy::new(|| {
+            vec![ParamSpecInt::new(
+                "preferred-length",
+                "preferred-length",
+                "The length the dock needs to show every app without collapsing",
+                0,
+                i32::MAX,
+                0,
+                ParamFlags::READABLE,
+            )]
+        });
+        PROPERTIES.as_ref()
+    }
+
+    fn property(&self, _obj: &Self::Type, _id: usize, pspec: &ParamSpec) -> Value {
+        match pspec.name() {
+            "preferred-length" => self.preferred_length.get().to_value(),
+            _ => unimplemented!(),
+        }
+    }
+}
+
+impl WidgetImpl for AppsContainer {
+    fn size_allocate(&self, widget: &Self::Type, width: i32, height: i32, baseline: i32) {
+        self.parent_size_allocate(widget, width, height, baseline);
+        widget.queue_overflow_update();
+    }
+}
 
 impl BoxImpl for AppsContainer {}
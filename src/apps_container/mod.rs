@@ -1,21 +1,106 @@
 use std::env;
 
 // SPDX-License-Identifier: MPL-2.0-only
+use crate::app_id;
+use crate::config::{ClickAction, DisplayMode, GroupingMode, SortOrder};
+use crate::custom_launcher::{self, CustomLauncher, LaunchTarget};
+use crate::dock_item::{DockItem, REVEAL_DURATION_MS};
 use crate::dock_list::DockList;
 use crate::dock_list::DockListType;
-use crate::utils::Event;
+use crate::dock_object::DockObject;
+use crate::fl;
+use crate::generated_entry;
+use crate::model_sync;
+use crate::recent::RecentApps;
+use crate::settings_page;
+use crate::system_items::SystemItems;
+use crate::toast::{Severity, Toast};
+use crate::utils::{current_workspace, send_event, BoxedWindowList, Event, Item};
+use crate::window_object::{update_window_model, WindowObject};
 use cascade::cascade;
 use cosmic_panel_config::config::Anchor;
 use cosmic_panel_config::config::CosmicPanelConfig;
+use cosmic_panel_config::config::PanelSize;
+use gio::DesktopAppInfo;
+use gtk4::gdk;
 use gtk4::prelude::*;
 use gtk4::subclass::prelude::*;
+use gtk4::{DropControllerMotion, DropTarget};
+use gtk4::Inhibit;
+use gtk4::{EventControllerKey, EventControllerScroll, EventControllerScrollFlags};
 use gtk4::Orientation;
+use gtk4::Separator;
+use gtk4::{Box, Button, Label, MenuButton, PolicyType, Popover, ScrolledWindow};
+use gtk4::{Revealer, RevealerTransitionType};
 use gtk4::{gio, glib};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::time::Duration;
 use tokio::sync::mpsc::Sender;
 
 mod imp;
 
+/// How active apps that don't fit in the dock are displayed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowMode {
+    /// The active apps can be scrolled.
+    Scroll,
+    /// Trailing active apps are collapsed into a "…" button.
+    Collapse,
+}
+
+impl Default for OverflowMode {
+    fn default() -> Self {
+        OverflowMode::Scroll
+    }
+}
+
+/// How long an app has to have been focused before closing it offers to keep it in the dock.
+const KEEP_PROMPT_MIN_USE: Duration = Duration::from_secs(5 * 60);
+
+// the size tiers themes can style, by icon size in pixels
+const SIZE_CLASSES: [(u32, &str); 5] = [
+    (0, "size-xs"),
+    (24, "size-s"),
+    (32, "size-m"),
+    (48, "size-l"),
+    (64, "size-xl"),
+];
+
+// the size tier of `icon_size`
+fn size_class(icon_size: u32) -> &'static str {
+    SIZE_CLASSES
+        .iter()
+        .rev()
+        .find(|(min_size, _)| icon_size >= *min_size)
+        .map(|(_, class)| *class)
+        .unwrap_or("size-m")
+}
+
 glib::wrapper! {
+    /// The app list of the dock. Themes can restyle it with a provider of a higher priority
+    /// than the application's, the nodes are:
+    ///
+    /// ```text
+    /// box.dock-apps.size-{xs,s,m,l,xl}.{horizontal,vertical}
+    /// ├── box (saved, recent, running and system lists)
+    /// │   ╰── listview > row
+    /// │       ╰── dockitem[.active][.attention][.launching][.missing][.all-minimized]
+    /// │           ├── revealer > overlay
+    /// │           │   ├── box.dock-item-box
+    /// │           │   │   ├── box > image, label.dock_label
+    /// │           │   │   ╰── box.dock-indicators
+    /// │           │   │       ╰── box.dock-indicator[.focused][.minimized]
+    /// │           │   ├── label.dock_badge
+    /// │           │   ╰── progressbar.dock_progress
+    /// │           ├── popover.window-popover > contents > box.popover_menu
+    /// │           ╰── popover > contents > box.dock_flyout > button.dock_flyout_item
+    /// ├── separator.dock-separator
+    /// ╰── menubutton (overflow)
+    /// ```
+    ///
+    /// The size tier follows the icon size, it sets the padding, margins and corner radius
+    /// of the items and the margins of the separators.
     pub struct AppsContainer(ObjectSubclass<imp::AppsContainer>)
         @extends gtk4::Widget, gtk4::Box,
     @implements gtk4::Accessible, gtk4::Buildable, gtk4::ConstraintTarget, gtk4::Orientable;
@@ -25,12 +110,13 @@ impl AppsContainer {
     pub fn new(tx: Sender<Event>) -> Self {
         let self_: Self = glib::Object::new(&[]).expect("Failed to create AppsContainer");
         let imp = imp::AppsContainer::from_instance(&self_);
+        imp.animations_enabled.set(true);
 
         cascade! {
             &self_;
             ..set_orientation(Orientation::Horizontal);
             ..add_css_class("transparent");
-            // ..add_css_class("dock_container");
+            ..add_css_class("dock-apps");
         };
 
         let config = CosmicPanelConfig::load_from_env().unwrap_or_default();
@@ -38,35 +124,138 @@ impl AppsContainer {
         let saved_app_list_view = DockList::new(DockListType::Saved, tx.clone(), config.clone());
         self_.append(&saved_app_list_view);
 
-        // let separator_container = cascade! {
-        //     gtk4::Box::new(Orientation::Vertical, 0);
-        //     ..set_margin_top(8);
-        //     ..set_margin_bottom(8);
-        //     ..set_vexpand(true);
-        // };
-        // self_.append(&separator_container);
-        // let separator = cascade! {
-        //     Separator::new(Orientation::Vertical);
-        //     ..set_margin_start(8);
-        //     ..set_margin_end(8);
-        //     ..set_vexpand(true);
-        //     ..add_css_class("dock_separator");
-        // };
-        // separator_container.append(&separator);
-        let active_app_list_view = DockList::new(DockListType::Active, tx, config.clone());
-        self_.append(&active_app_list_view);
-        // self_.connect_orientation_notify(glib::clone!(@weak separator => move |c| {
-        //     dbg!(c.orientation());
-        //     separator.set_orientation(match c.orientation() {
-        //         Orientation::Horizontal => Orientation::Vertical,
-        //         _ => Orientation::Horizontal,
-        //     });
-        // }));
+        let separator = cascade! {
+            Separator::new(Orientation::Vertical);
+            ..add_css_class("dock-separator");
+        };
+        // names the running apps when the dock shows labels
+        let active_heading = cascade! {
+            Label::new(Some(&fl!("running-apps")));
+            ..set_visible(false);
+            ..add_css_class("dock_section_heading");
+        };
+        let separator_box = cascade! {
+            Box::new(Orientation::Horizontal, 0);
+            ..append(&separator);
+            ..append(&active_heading);
+        };
+        // only shown between two lists with apps in them, it slides in and out as they change
+        let separator_revealer = cascade! {
+            Revealer::new();
+            ..set_transition_type(RevealerTransitionType::SlideRight);
+            ..set_transition_duration(REVEAL_DURATION_MS);
+            ..set_child(Some(&separator_box));
+        };
+        self_.append(&separator_revealer);
 
+        let recent_app_list_view = cascade! {
+            DockList::new(DockListType::Recent, tx.clone(), config.clone());
+            ..set_visible(false);
+        };
+        self_.append(&recent_app_list_view);
+        let recent_separator = cascade! {
+            Separator::new(Orientation::Vertical);
+            ..set_visible(false);
+            ..add_css_class("dock-separator");
+        };
+        self_.append(&recent_separator);
+        let active_app_list_view = DockList::new(DockListType::Active, tx.clone(), config.clone());
+        // saved apps always stay visible, only the active apps scroll or collapse
+        let active_scroller = cascade! {
+            ScrolledWindow::new();
+            ..set_child(Some(&active_app_list_view));
+            ..set_propagate_natural_width(true);
+            ..set_propagate_natural_height(true);
+            ..set_policy(PolicyType::Never, PolicyType::Never);
+        };
+        self_.append(&active_scroller);
+
+        let overflow_menu = cascade! {
+            Box::new(Orientation::Vertical, 4);
+            ..add_css_class("popover_menu");
+        };
+        let overflow_button = cascade! {
+            MenuButton::new();
+            ..set_label("…");
+            ..set_visible(false);
+            ..add_css_class("dock_item");
+            ..set_popover(Some(&cascade! {
+                Popover::new();
+                ..set_child(Some(&overflow_menu));
+                ..add_css_class("dock");
+                ..set_has_arrow(false);
+            }));
+        };
+        self_.append(&overflow_button);
+
+        let system_separator = cascade! {
+            Separator::new(Orientation::Vertical);
+            ..set_visible(false);
+            ..add_css_class("dock-separator");
+        };
+        self_.append(&system_separator);
+        let system_list_view = cascade! {
+            DockList::new(DockListType::System, tx.clone(), config.clone());
+            ..set_visible(false);
+        };
+        self_.append(&system_list_view);
+        let toast = Toast::new();
+        self_.append(&toast);
+        imp.config.replace(config.clone());
         imp.saved_list.set(saved_app_list_view).unwrap();
+        imp.separator.set(separator).unwrap();
+        imp.separator_box.set(separator_box).unwrap();
+        imp.separator_revealer.set(separator_revealer).unwrap();
+        imp.active_heading.set(active_heading).unwrap();
+        imp.tx.set(tx).unwrap();
+        self_.update_size_class(config.get_applet_icon_size());
         imp.active_list.set(active_app_list_view).unwrap();
+        imp.active_scroller.set(active_scroller).unwrap();
+        imp.overflow_button.set(overflow_button).unwrap();
+        imp.overflow_menu.set(overflow_menu).unwrap();
+        imp.recent_list.set(recent_app_list_view).unwrap();
+        imp.recent_separator.set(recent_separator).unwrap();
+        imp.system_separator.set(system_separator).unwrap();
+        imp.system_list.set(system_list_view).unwrap();
+        imp.toast.set(toast).unwrap();
+        imp.window_model
+            .set(gio::ListStore::new(WindowObject::static_type()))
+            .unwrap();
+        // any list changing the length of the dock may change the overflow and preferred length
+        for type_ in [
+            DockListType::Saved,
+            DockListType::Recent,
+            DockListType::Active,
+            DockListType::System,
+        ] {
+            self_
+                .model(type_)
+                .connect_items_changed(glib::clone!(@weak self_ => move |_, _, _, _| {
+                    self_.queue_overflow_update();
+                }));
+        }
+        for type_ in [DockListType::Saved, DockListType::Active] {
+            self_
+                .model(type_)
+                .connect_items_changed(glib::clone!(@weak self_ => move |_, _, _, _| {
+                    self_.update_visibility();
+                }));
+        }
+        self_.connect_realize(|self_| self_.queue_overflow_update());
+        imp.pinned.replace(self_.pinned_apps());
+        self_
+            .model(DockListType::Saved)
+            .connect_items_changed(glib::clone!(@weak self_ => move |_, _, _, _| {
+                self_.update_pinned();
+            }));
+        self_.setup_keyboard_navigation();
+        self_.setup_scroll_gesture();
         // Setup
         self_.setup_callbacks();
+        self_.setup_file_drop_target();
+        let drag_motion = DropControllerMotion::new();
+        self_.add_controller(&drag_motion);
+        imp.drag_motion.set(drag_motion).unwrap();
         self_.set_position(config.anchor);
         
 
@@ -81,14 +270,1036 @@ impl AppsContainer {
         match type_ {
             DockListType::Active => imp.active_list.get().unwrap().model(),
             DockListType::Saved => imp.saved_list.get().unwrap().model(),
+            DockListType::System => imp.system_list.get().unwrap().model(),
+            DockListType::Recent => imp.recent_list.get().unwrap().model(),
+        }
+    }
+
+    /// Every window listed by the daemon as a [`WindowObject`], in the order the daemon lists them.
+    pub fn window_model(&self) -> &gio::ListStore {
+        let imp = imp::AppsContainer::from_instance(self);
+        imp.window_model.get().unwrap()
+    }
+
+    pub fn update_window_model(&self, windows: &[Item]) {
+        let imp = imp::AppsContainer::from_instance(self);
+        update_window_model(self.window_model(), windows, imp.tx.get().unwrap());
+        imp.dock_quits
+            .borrow_mut()
+            .retain(|entity| windows.iter().any(|w| w.entity == *entity));
+    }
+
+    /// Records that the dock closed the windows, so their app doesn't offer to be kept.
+    pub fn note_quit_from_dock(&self, entities: &[(u32, u32)]) {
+        let imp = imp::AppsContainer::from_instance(self);
+        imp.dock_quits.borrow_mut().extend(entities.iter().copied());
+    }
+
+    /// Whether the item of the unpinned app, whose last window was just closed, should offer to
+    /// keep the app in the dock. Each app offers it once per session and only if it was used
+    /// for a while and not quit from the dock.
+    pub fn offer_keep_in_dock(&self, dock_object: &DockObject) -> bool {
+        let imp = imp::AppsContainer::from_instance(self);
+        let id = match dock_object.get_id() {
+            Some(id) => id,
+            None => return false,
+        };
+        let quit_from_dock = {
+            let dock_quits = imp.dock_quits.borrow();
+            dock_object
+                .property::<BoxedWindowList>("active")
+                .0
+                .iter()
+                .any(|w| dock_quits.contains(&w.entity))
+        };
+        // an open popover means the user is busy with the dock
+        if quit_from_dock
+            || !self.shows_saved()
+            || dock_object.property::<bool>("saved")
+            || !dock_object.can_pin()
+            || dock_object.focused_time() < KEEP_PROMPT_MIN_USE
+            || self.has_open_popover()
+            || !self.is_mapped()
+        {
+            return false;
+        }
+        imp.keep_prompted.borrow_mut().insert(id)
+    }
+
+    /// Answers the keep in dock prompt of the closed app's item, pinning the app if `keep`.
+    pub fn answer_keep_prompt(&self, dock_object: &DockObject, keep: bool) {
+        let imp = imp::AppsContainer::from_instance(self);
+        if !dock_object.property::<bool>("keep-prompt") {
+            return;
+        }
+        // the pinned item takes the place of the closed one instead of shrinking in after it
+        let animate = self.animations_enabled() && !keep;
+        model_sync::end_keep_prompt(self.model(DockListType::Active), dock_object, animate);
+        if keep {
+            if let Some(id) = dock_object.get_id() {
+                // the end of the pinned apps is next to where the item was
+                send_event(
+                    imp.tx.get().unwrap(),
+                    Event::Favorite {
+                        id,
+                        favorite: true,
+                        index: None,
+                    },
+                );
+            }
         }
     }
 
+    // arrow keys continue from one list into the other and wrap around at the ends
+    // scrolling on the dock outside of the items, which handle scrolling themselves, minimizes
+    // the windows on the dock's output, scrolling back restores them
+    fn setup_scroll_gesture(&self) {
+        let controller = EventControllerScroll::new(
+            EventControllerScrollFlags::VERTICAL | EventControllerScrollFlags::DISCRETE,
+        );
+        controller.connect_scroll(
+            glib::clone!(@weak self as self_ => @default-return Inhibit(false), move |_, _, dy| {
+                let imp = imp::AppsContainer::from_instance(&self_);
+                let event = if dy > 0.0 {
+                    // the output showing the dock, even if the dock lists every output
+                    Event::MinimizeAll(imp.output_filter.borrow().clone())
+                } else if dy < 0.0 {
+                    Event::RestoreAll
+                } else {
+                    return Inhibit(false);
+                };
+                send_event(imp.tx.get().unwrap(), event);
+                Inhibit(true)
+            }),
+        );
+        self.add_controller(&controller);
+    }
+
+    fn setup_keyboard_navigation(&self) {
+        let imp = imp::AppsContainer::from_instance(self);
+        let saved_list = imp.saved_list.get().unwrap();
+        let active_list = imp.active_list.get().unwrap();
+        saved_list.connect_local(
+            "focus-boundary",
+            false,
+            glib::clone!(@weak saved_list, @weak active_list => @default-return None, move |args| {
+                let forward = args[1].get::<bool>().unwrap();
+                if !active_list.focus_edge(forward) {
+                    saved_list.focus_edge(forward);
+                }
+                None
+            }),
+        );
+        active_list.connect_local(
+            "focus-boundary",
+            false,
+            glib::clone!(@weak saved_list, @weak active_list => @default-return None, move |args| {
+                let forward = args[1].get::<bool>().unwrap();
+                if !saved_list.focus_edge(forward) {
+                    active_list.focus_edge(forward);
+                }
+                None
+            }),
+        );
+        // menus handle escape themselves, this closes the thumbnails, which don't take the focus
+        let escape_controller = EventControllerKey::new();
+        escape_controller.connect_key_pressed(
+            glib::clone!(@weak self as self_ => @default-return Inhibit(false), move |_, key, _, _| {
+                if key != gdk::Key::Escape {
+                    return Inhibit(false);
+                }
+                let imp = imp::AppsContainer::from_instance(&self_);
+                let item = imp
+                    .open_popover
+                    .borrow()
+                    .as_ref()
+                    .and_then(|(_, item)| item.upgrade());
+                if !self_.close_popover() {
+                    return Inhibit(false);
+                }
+                if let Some(item) = item {
+                    item.grab_focus();
+                }
+                Inhibit(true)
+            }),
+        );
+        self.add_controller(&escape_controller);
+    }
+
+    /// Writes pending changes of the saved apps, see `DockList::flush_saved`.
+    pub fn flush_saved(&self) {
+        let imp = imp::AppsContainer::from_instance(self);
+        imp.saved_list.get().unwrap().flush_saved();
+    }
+
+    /// Desktop ids of the saved apps, in dock order.
+    pub fn saved_ids(&self) -> Vec<String> {
+        let imp = imp::AppsContainer::from_instance(self);
+        imp.saved_list.get().unwrap().saved_ids()
+    }
+
+    /// Desktop ids of the pinned apps in dock order, including apps that are not installed
+    /// right now.
+    pub fn pinned_apps(&self) -> Vec<String> {
+        let imp = imp::AppsContainer::from_instance(self);
+        imp.saved_list.get().unwrap().pinned_ids()
+    }
+
+    /// Pins exactly the apps in `ids`, in that order. Apps that are not installed are kept in
+    /// their place and show up once they are installed.
+    pub fn set_pinned_apps(&self, ids: Vec<String>) {
+        let imp = imp::AppsContainer::from_instance(self);
+        let tx = imp.tx.get().unwrap();
+        // pinned-changed is emitted by the saved model as the events land
+        let mut queued = false;
+        let mut seen = HashSet::new();
+        let ids: Vec<String> = ids.into_iter().filter(|id| seen.insert(id.clone())).collect();
+        let (installed, unresolved): (Vec<_>, Vec<_>) = ids
+            .iter()
+            .enumerate()
+            .partition(|(_, id)| {
+                generated_entry::app_info(id).is_some() || custom_launcher::get(id).is_some()
+            });
+
+        // the same events as pinning from the dock, applied in order by the event loop
+        let mut saved = self.saved_ids();
+        saved.retain(|id| {
+            let keep = ids.contains(id);
+            if !keep {
+                queued = true;
+                send_event(
+                    tx,
+                    Event::Favorite {
+                        id: id.clone(),
+                        favorite: false,
+                        index: None,
+                    },
+                );
+            }
+            keep
+        });
+        for (index, (_, id)) in installed.into_iter().enumerate() {
+            if saved.get(index) == Some(id) {
+                continue;
+            }
+            if let Some(old_index) = saved.iter().position(|saved_id| saved_id == id) {
+                saved.remove(old_index);
+                saved.insert(index, id.clone());
+                queued = true;
+                send_event(
+                    tx,
+                    Event::Reorder {
+                        id: id.clone(),
+                        new_index: index as u32,
+                    },
+                );
+                continue;
+            }
+            saved.insert(index, id.clone());
+            queued = true;
+            send_event(
+                tx,
+                Event::Favorite {
+                    id: id.clone(),
+                    favorite: true,
+                    index: Some(index as u32),
+                },
+            );
+        }
+        imp.saved_list.get().unwrap().set_unresolved(
+            unresolved
+                .into_iter()
+                .map(|(index, id)| (index as u32, id.clone()))
+                .collect(),
+        );
+        // only the apps that aren't installed changed, the model won't tell
+        if !queued {
+            self.update_pinned();
+        }
+    }
+
+    /// Moves the saved app `id` to `new_index`, clamped to the saved apps. Returns false if
+    /// the app isn't saved.
+    pub fn reorder_saved(&self, id: &str, new_index: u32) -> bool {
+        let imp = imp::AppsContainer::from_instance(self);
+        let saved_list = imp.saved_list.get().unwrap();
+        let model = saved_list.model();
+        let position = (0..model.n_items()).find(|&i| {
+            model
+                .item(i)
+                .and_then(|item| item.downcast::<DockObject>().ok())
+                .and_then(|dock_object| dock_object.get_id())
+                .as_deref()
+                == Some(id)
+        });
+        match position {
+            Some(position) => {
+                saved_list.move_item(position, new_index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Saves a launcher that opens `target`, e.g. a web app, and returns its id. Windows whose
+    /// app id is `match_string` or whose title contains it are shown on the launcher.
+    pub fn add_custom_launcher(
+        &self,
+        name: &str,
+        icon: &str,
+        target: LaunchTarget,
+        match_string: &str,
+    ) -> String {
+        let imp = imp::AppsContainer::from_instance(self);
+        let launcher = CustomLauncher::new(name, icon, target, match_string);
+        let id = launcher.id.clone();
+        send_event(imp.tx.get().unwrap(), Event::AddCustomLauncher(launcher));
+        id
+    }
+
+    fn update_pinned(&self) {
+        let imp = imp::AppsContainer::from_instance(self);
+        let pinned = self.pinned_apps();
+        if *imp.pinned.borrow() != pinned {
+            imp.pinned.replace(pinned);
+            self.emit_by_name::<()>("pinned-changed", &[]);
+        }
+    }
+
+    /// Activates the saved app at `index`, out of range indices are ignored.
+    pub fn activate_saved(&self, index: u32) {
+        let imp = imp::AppsContainer::from_instance(self);
+        imp.saved_list.get().unwrap().activate_index(index);
+    }
+
+    /// Chooses how active apps that don't fit in the dock are shown.
+    pub fn set_overflow_mode(&self, mode: OverflowMode) {
+        let imp = imp::AppsContainer::from_instance(self);
+        imp.overflow_mode.set(mode);
+        self.update_scroll_policy();
+        self.queue_overflow_update();
+    }
+
+    fn update_scroll_policy(&self) {
+        let imp = imp::AppsContainer::from_instance(self);
+        let scroller = imp.active_scroller.get().unwrap();
+        match (imp.overflow_mode.get(), self.orientation()) {
+            (OverflowMode::Scroll, Orientation::Horizontal) => {
+                scroller.set_policy(PolicyType::External, PolicyType::Never)
+            }
+            (OverflowMode::Scroll, _) => scroller.set_policy(PolicyType::Never, PolicyType::External),
+            (OverflowMode::Collapse, _) => scroller.set_policy(PolicyType::Never, PolicyType::Never),
+        }
+    }
+
+    /// Recomputes the overflow once the current layout pass is done.
+    pub(crate) fn queue_overflow_update(&self) {
+        let imp = imp::AppsContainer::from_instance(self);
+        if imp.overflow_update_queued.replace(true) {
+            return;
+        }
+        glib::idle_add_local_once(glib::clone!(@weak self as self_ => move || {
+            let imp = imp::AppsContainer::from_instance(&self_);
+            imp.overflow_update_queued.set(false);
+            self_.update_overflow();
+            self_.update_preferred_length();
+        }));
+    }
+
+    /// The length along the dock needed to show every item, including the collapsed ones.
+    pub fn preferred_length(&self) -> i32 {
+        let imp = imp::AppsContainer::from_instance(self);
+        imp.preferred_length.get()
+    }
+
+    // notifies the host when the length it should reserve for the app list changed
+    fn update_preferred_length(&self) {
+        let imp = imp::AppsContainer::from_instance(self);
+        let orientation = self.orientation();
+        let natural = |w: &gtk4::Widget| {
+            if w.is_visible() {
+                w.measure(orientation, -1).1
+            } else {
+                0
+            }
+        };
+        let active_list = imp.active_list.get().unwrap();
+        let n_active = self.model(DockListType::Active).n_items() as i32;
+        // collapsed apps aren't measured, so they are assumed to be as long as the shown ones
+        let active_length = if imp.active_scroller.get().unwrap().is_visible() && n_active > 0 {
+            let n_visible = active_list.visible_n_items().max(1) as i32;
+            natural(active_list.upcast_ref()) / n_visible * n_active
+        } else {
+            0
+        };
+        let length = natural(imp.saved_list.get().unwrap().upcast_ref())
+            + natural(imp.separator_revealer.get().unwrap().upcast_ref())
+            + natural(imp.recent_list.get().unwrap().upcast_ref())
+            + natural(imp.recent_separator.get().unwrap().upcast_ref())
+            + active_length
+            + natural(imp.system_separator.get().unwrap().upcast_ref())
+            + natural(imp.system_list.get().unwrap().upcast_ref());
+        if imp.preferred_length.replace(length) != length {
+            self.notify("preferred-length");
+        }
+    }
+
+    // the area of the output covered by the dock, assuming it is centered on its edge
+    fn dock_area(&self) -> Option<(i32, i32, i32, i32)> {
+        let imp = imp::AppsContainer::from_instance(self);
+        let root = self.root()?;
+        let surface = root.surface()?;
+        let monitor = surface.display().monitor_at_surface(&surface)?;
+        let output = monitor.geometry();
+        let (width, height) = (root.width(), root.height());
+        let centered_x = (output.width() - width) / 2;
+        let centered_y = (output.height() - height) / 2;
+        let area = match imp.config.borrow().anchor {
+            Anchor::Top => (centered_x, 0, width, height),
+            Anchor::Bottom => (centered_x, output.height() - height, width, height),
+            Anchor::Left => (0, centered_y, width, height),
+            Anchor::Right => (output.width() - width, centered_y, width, height),
+        };
+        Some(area)
+    }
+
+    /// Checks whether any window on the dock's output and the current workspace covers the dock,
+    /// emitting `overlap-changed` when that changes.
+    pub fn update_overlap(&self, windows: &[Item]) {
+        let imp = imp::AppsContainer::from_instance(self);
+        let (x, y, width, height) = match self.dock_area() {
+            Some(area) => area,
+            None => return,
+        };
+        let output = imp.output_filter.borrow().clone();
+        let workspace = current_workspace();
+        let overlapped = windows.iter().any(|window| {
+            let on_output = match (&output, window.output()) {
+                (Some(output), Some(window_output)) => output == window_output,
+                _ => true,
+            };
+            let on_workspace = match (workspace, window.workspace()) {
+                (Some(workspace), Some(window_workspace)) => workspace == window_workspace,
+                _ => true,
+            };
+            on_output && on_workspace && window.intersects(x, y, width, height)
+        });
+        if imp.overlapped.replace(overlapped) != overlapped {
+            self.emit_by_name::<()>("overlap-changed", &[&overlapped]);
+        }
+    }
+
+    // collapses the trailing active apps into the overflow button if they don't fit
+    fn update_overflow(&self) {
+        let imp = imp::AppsContainer::from_instance(self);
+        let active_list = imp.active_list.get().unwrap();
+        let overflow_button = imp.overflow_button.get().unwrap();
+        let n_active = self.model(DockListType::Active).n_items();
+        if imp.overflow_mode.get() != OverflowMode::Collapse || n_active == 0 || !self.is_realized() {
+            active_list.set_visible_limit(None);
+            overflow_button.set_visible(false);
+            return;
+        }
+
+        let orientation = self.orientation();
+        let available = match orientation {
+            Orientation::Horizontal => self.width(),
+            _ => self.height(),
+        };
+        let natural = |w: &gtk4::Widget| {
+            if w.is_visible() {
+                w.measure(orientation, -1).1
+            } else {
+                0
+            }
+        };
+        let used = natural(imp.saved_list.get().unwrap().upcast_ref())
+            + natural(imp.separator_revealer.get().unwrap().upcast_ref())
+            + natural(imp.recent_list.get().unwrap().upcast_ref())
+            + natural(imp.recent_separator.get().unwrap().upcast_ref())
+            + natural(imp.system_list.get().unwrap().upcast_ref())
+            + natural(imp.system_separator.get().unwrap().upcast_ref());
+        let n_visible = active_list.visible_n_items().max(1);
+        let item_length = (natural(active_list.upcast_ref()) / n_visible as i32).max(1);
+        let button_length = overflow_button.measure(orientation, -1).1;
+
+        let fits = ((available - used - button_length).max(0) / item_length) as u32;
+        if fits >= n_active {
+            active_list.set_visible_limit(None);
+            overflow_button.set_visible(false);
+        } else {
+            active_list.set_visible_limit(Some(fits));
+            self.fill_overflow_menu(fits);
+            overflow_button.set_visible(true);
+        }
+    }
+
+    // lists the collapsed apps, clicking one focuses or launches it
+    fn fill_overflow_menu(&self, first_hidden: u32) {
+        let imp = imp::AppsContainer::from_instance(self);
+        let overflow_menu = imp.overflow_menu.get().unwrap();
+        while let Some(c) = overflow_menu.first_child() {
+            overflow_menu.remove(&c);
+        }
+        let model = self.model(DockListType::Active);
+        let mut i = first_hidden;
+        while let Some(item) = model.item(i) {
+            if let Ok(dock_object) = item.downcast::<DockObject>() {
+                let label = dock_object.display_name().unwrap_or_default();
+                let button = cascade! {
+                    Button::with_label(&label);
+                    ..add_css_class("popover_menu");
+                };
+                let tx = imp.tx.get().unwrap().clone();
+                let overflow_button = imp.overflow_button.get().unwrap();
+                button.connect_clicked(glib::clone!(@weak dock_object, @weak overflow_button => move |_| {
+                    let event = match dock_object.property::<BoxedWindowList>("active").0.get(0) {
+                        Some(first) => Event::Activate(first.entity),
+                        None => match dock_object.get_id() {
+                            Some(id) => Event::Launch(id),
+                            None => return,
+                        },
+                    };
+                    send_event(&tx, event);
+                    overflow_button.popdown();
+                }));
+                overflow_menu.append(&button);
+            }
+            i += 1;
+        }
+    }
+
+    pub fn daemon_available(&self) -> bool {
+        let imp = imp::AppsContainer::from_instance(self);
+        !imp.daemon_unavailable.get()
+    }
+
+    /// Switches between the normal dock and the launch-only mode used while the window daemon is unavailable.
+    pub fn set_daemon_available(&self, available: bool) {
+        let imp = imp::AppsContainer::from_instance(self);
+        imp.daemon_unavailable.set(!available);
+        self.update_visibility();
+        if available {
+            self.remove_css_class("offline");
+        } else {
+            self.add_css_class("offline");
+        }
+    }
+
+    /// Shows or hides the saved apps. Without them the dock only lists running apps.
+    pub fn set_show_saved(&self, show_saved: bool) {
+        let imp = imp::AppsContainer::from_instance(self);
+        imp.hide_saved.set(!show_saved);
+        self.update_visibility();
+    }
+
+    pub fn shows_saved(&self) -> bool {
+        let imp = imp::AppsContainer::from_instance(self);
+        !imp.hide_saved.get()
+    }
+
+    /// Shows or hides running apps that aren't saved. Without them the dock is a pure launcher.
+    pub fn set_show_active(&self, show_active: bool) {
+        let imp = imp::AppsContainer::from_instance(self);
+        imp.hide_active.set(!show_active);
+        self.update_visibility();
+    }
+
+    pub fn shows_active(&self) -> bool {
+        let imp = imp::AppsContainer::from_instance(self);
+        !imp.hide_active.get()
+    }
+
+    /// Shows or hides the removable drives and the trash at the end of the dock.
+    pub fn set_show_system(&self, show_system: bool) {
+        let imp = imp::AppsContainer::from_instance(self);
+        if show_system == self.shows_system() {
+            return;
+        }
+        let model = self.model(DockListType::System);
+        if show_system {
+            imp.system_items.replace(Some(SystemItems::watch(model)));
+        } else {
+            if let Some(system_items) = imp.system_items.take() {
+                system_items.stop();
+            }
+            model.remove_all();
+        }
+        imp.system_list.get().unwrap().set_visible(show_system);
+        imp.system_separator.get().unwrap().set_visible(show_system);
+        self.queue_overflow_update();
+    }
+
+    pub fn shows_system(&self) -> bool {
+        let imp = imp::AppsContainer::from_instance(self);
+        imp.system_items.borrow().is_some()
+    }
+
+    /// Sets what a primary click on a running app does in both lists.
+    pub fn set_click_action(&self, click_action: ClickAction) {
+        let imp = imp::AppsContainer::from_instance(self);
+        imp.saved_list.get().unwrap().set_click_action(click_action);
+        imp.active_list.get().unwrap().set_click_action(click_action);
+    }
+
+    /// Shows window thumbnails when the pointer rests on a running app in either list.
+    pub fn set_hover_previews(&self, hover_previews: bool) {
+        let imp = imp::AppsContainer::from_instance(self);
+        imp.saved_list.get().unwrap().set_hover_previews(hover_previews);
+        imp.active_list.get().unwrap().set_hover_previews(hover_previews);
+    }
+
+    /// Turns the animations of the dock on or off, e.g. for users that prefer reduced motion.
+    /// With animations off, items appear, disappear and change state instantly.
+    pub fn set_animations_enabled(&self, enabled: bool) {
+        let imp = imp::AppsContainer::from_instance(self);
+        if imp.animations_enabled.replace(enabled) == enabled {
+            return;
+        }
+        // the style sheet stops the CSS transitions and keyframe animations
+        if enabled {
+            self.remove_css_class("no-animations");
+        } else {
+            self.add_css_class("no-animations");
+        }
+        imp.separator_revealer
+            .get()
+            .unwrap()
+            .set_transition_duration(if enabled { REVEAL_DURATION_MS } else { 0 });
+        imp.saved_list.get().unwrap().set_animations_enabled(enabled);
+        imp.active_list.get().unwrap().set_animations_enabled(enabled);
+        imp.recent_list.get().unwrap().set_animations_enabled(enabled);
+        imp.system_list.get().unwrap().set_animations_enabled(enabled);
+    }
+
+    pub fn animations_enabled(&self) -> bool {
+        let imp = imp::AppsContainer::from_instance(self);
+        imp.animations_enabled.get()
+    }
+
+    /// A preferences page for the app list, for the settings app to embed. Changes apply to the
+    /// running app list once they are saved.
+    pub fn settings_widget(&self) -> gtk4::Widget {
+        settings_page::new()
+    }
+
+    /// Rebinds the items of every list, e.g. to pick up icons after a theme change.
+    pub fn refresh_items(&self) {
+        let imp = imp::AppsContainer::from_instance(self);
+        imp.saved_list.get().unwrap().refresh_items();
+        imp.active_list.get().unwrap().refresh_items();
+        imp.recent_list.get().unwrap().refresh_items();
+        imp.system_list.get().unwrap().refresh_items();
+    }
+
+    /// Shows the app names next to the icons in every list.
+    pub fn set_display_mode(&self, display_mode: DisplayMode) {
+        let imp = imp::AppsContainer::from_instance(self);
+        if imp.display_mode.replace(display_mode) == display_mode {
+            return;
+        }
+        let show_labels = display_mode == DisplayMode::IconsAndLabels;
+        imp.saved_list.get().unwrap().set_show_labels(show_labels);
+        imp.active_list.get().unwrap().set_show_labels(show_labels);
+        imp.recent_list.get().unwrap().set_show_labels(show_labels);
+        imp.system_list.get().unwrap().set_show_labels(show_labels);
+        imp.active_heading.get().unwrap().set_visible(show_labels);
+        // the items got longer or shorter, so fewer or more of them fit
+        self.queue_overflow_update();
+    }
+
+    pub fn set_sort_order(&self, sort_order: SortOrder) {
+        let imp = imp::AppsContainer::from_instance(self);
+        imp.sort_order.set(sort_order);
+    }
+
+    /// How running apps that aren't saved are ordered.
+    pub fn sort_order(&self) -> SortOrder {
+        let imp = imp::AppsContainer::from_instance(self);
+        imp.sort_order.get()
+    }
+
+    /// Empties the active list when the mode changes, the next sync fills it with items of the
+    /// new kind. The saved apps keep their items.
+    pub fn set_grouping_mode(&self, grouping_mode: GroupingMode) {
+        let imp = imp::AppsContainer::from_instance(self);
+        if imp.grouping_mode.replace(grouping_mode) != grouping_mode {
+            self.model(DockListType::Active).remove_all();
+        }
+    }
+
+    /// Whether running apps that aren't saved get an item per app or per window.
+    pub fn grouping_mode(&self) -> GroupingMode {
+        let imp = imp::AppsContainer::from_instance(self);
+        imp.grouping_mode.get()
+    }
+
+    /// Limits the active list to windows on the output with connector name `output`.
+    pub fn set_output_filter(&self, output: Option<String>) {
+        let imp = imp::AppsContainer::from_instance(self);
+        imp.output_filter.replace(output);
+    }
+
+    /// Lists windows from every output, ignoring the output filter.
+    pub fn set_all_outputs(&self, all_outputs: bool) {
+        let imp = imp::AppsContainer::from_instance(self);
+        imp.all_outputs.set(all_outputs);
+    }
+
+    pub fn shows_all_outputs(&self) -> bool {
+        let imp = imp::AppsContainer::from_instance(self);
+        imp.all_outputs.get()
+    }
+
+    /// The output whose windows are listed, `None` if windows from every output are.
+    pub fn output_filter(&self) -> Option<String> {
+        let imp = imp::AppsContainer::from_instance(self);
+        if imp.all_outputs.get() {
+            None
+        } else {
+            imp.output_filter.borrow().clone()
+        }
+    }
+
+    /// Sets how many recently launched apps are shown, 0 hides the section.
+    pub fn set_recent_count(&self, recent_count: usize) {
+        let imp = imp::AppsContainer::from_instance(self);
+        imp.recent_count.set(recent_count);
+    }
+
+    pub fn recent_count(&self) -> usize {
+        let imp = imp::AppsContainer::from_instance(self);
+        imp.recent_count.get()
+    }
+
+    /// Shows the latest launched apps that are neither saved nor running.
+    pub fn update_recent(&self, recent: &RecentApps) {
+        let imp = imp::AppsContainer::from_instance(self);
+        let mut exclude: HashSet<String> = self.saved_ids().into_iter().collect();
+        exclude.extend(self.keep_visible_ids());
+        let active_model = self.model(DockListType::Active);
+        let mut i = 0;
+        while let Some(item) = active_model.item(i) {
+            if let Some(id) = item.downcast::<DockObject>().ok().and_then(|o| o.get_id()) {
+                exclude.insert(id);
+            }
+            i += 1;
+        }
+        // apps that were uninstalled since are skipped
+        let apps: Vec<DesktopAppInfo> = recent
+            .latest(imp.recent_count.get(), &exclude)
+            .iter()
+            .filter_map(|id| DesktopAppInfo::new(id))
+            .filter(|app_info| app_info.should_show())
+            .collect();
+
+        let model = self.model(DockListType::Recent);
+        let mut current = Vec::new();
+        let mut i = 0;
+        while let Some(item) = model.item(i) {
+            current.push(item.downcast::<DockObject>().ok().and_then(|o| o.get_id()));
+            i += 1;
+        }
+        let ids: Vec<Option<String>> = apps.iter().map(|a| a.id().map(|id| id.to_string())).collect();
+        if current != ids {
+            let dock_objects: Vec<glib::Object> = apps
+                .into_iter()
+                .map(|app_info| DockObject::from_recent(app_info).upcast())
+                .collect();
+            model.splice(0, model.n_items(), &dock_objects);
+        }
+        self.update_visibility();
+    }
+
+    // separators are only needed between lists that are shown
+    fn update_visibility(&self) {
+        let imp = imp::AppsContainer::from_instance(self);
+        let show_saved = !imp.hide_saved.get();
+        let show_active = !imp.hide_active.get() && !imp.daemon_unavailable.get();
+        // pinning a recent app needs the saved list
+        let show_recent = show_saved
+            && imp.recent_count.get() > 0
+            && self.model(DockListType::Recent).n_items() > 0;
+        imp.saved_list.get().unwrap().set_visible(show_saved);
+        imp.recent_list.get().unwrap().set_visible(show_recent);
+        imp.active_scroller.get().unwrap().set_visible(show_active);
+        // the saved and running apps are only separated when both have apps
+        let has_saved = self.model(DockListType::Saved).n_items() > 0;
+        let has_active = self.model(DockListType::Active).n_items() > 0;
+        imp.separator_revealer
+            .get()
+            .unwrap()
+            .set_reveal_child(show_saved && has_saved && (show_recent || (show_active && has_active)));
+        imp.recent_separator
+            .get()
+            .unwrap()
+            .set_visible(show_recent && show_active);
+        self.queue_overflow_update();
+    }
+
+    /// Toggles whether the app stays in the active list without windows, used in tasks-only mode.
+    pub fn toggle_keep_visible(&self, id: &str) {
+        let imp = imp::AppsContainer::from_instance(self);
+        let mut keep_visible = imp.keep_visible.borrow_mut();
+        if let Some(i) = keep_visible.iter().position(|kept| kept == id) {
+            keep_visible.remove(i);
+        } else {
+            keep_visible.push(id.to_string());
+        }
+    }
+
+    /// Desktop ids of the apps that stay in the active list without windows.
+    pub fn keep_visible_ids(&self) -> Vec<String> {
+        let imp = imp::AppsContainer::from_instance(self);
+        imp.keep_visible.borrow().clone()
+    }
+
+    /// Forgets the windows last focused from the dock, whose entities belong to a daemon that
+    /// is gone.
+    pub fn forget_windows(&self) {
+        for type_ in [DockListType::Saved, DockListType::Recent, DockListType::Active] {
+            let model = self.model(type_);
+            let mut i = 0;
+            while let Some(item) = model.item(i) {
+                if let Ok(dock_object) = item.downcast::<DockObject>() {
+                    dock_object.clear_last_activated();
+                }
+                i += 1;
+            }
+        }
+    }
+
+    /// Shows the window `entity` again where the dock hid it before the daemon confirmed that it
+    /// closed, e.g. a row of the window list.
+    pub fn restore_window(&self, entity: (u32, u32)) {
+        for type_ in [DockListType::Saved, DockListType::Active] {
+            let model = self.model(type_);
+            let mut i = 0;
+            while let Some(item) = model.item(i) {
+                if let Ok(dock_object) = item.downcast::<DockObject>() {
+                    let active = dock_object.property::<BoxedWindowList>("active");
+                    if active.0.iter().any(|w| w.entity == entity) {
+                        // the window list rebuilds its rows from the windows of the app
+                        dock_object.notify("active");
+                    }
+                }
+                i += 1;
+            }
+        }
+    }
+
+    /// Follows drags over the dock, in the coordinates of the container.
+    pub fn drag_motion(&self) -> &DropControllerMotion {
+        let imp = imp::AppsContainer::from_instance(self);
+        imp.drag_motion.get().unwrap()
+    }
+
+    /// Keeps one popover of the dock open at a time, the window list, context menu or hover
+    /// thumbnails of another item are closed when `popover` is shown.
+    pub fn popover_shown(&self, popover: &impl IsA<Popover>, item: &DockItem) {
+        let imp = imp::AppsContainer::from_instance(self);
+        let popover = popover.upcast_ref::<Popover>();
+        let previous = imp
+            .open_popover
+            .replace(Some((popover.clone(), item.downgrade())));
+        // closing the previous popover calls `popover_hidden`, which must not see it as open
+        if let Some((previous, _)) = previous {
+            if &previous != popover {
+                previous.popdown();
+            }
+        }
+    }
+
+    /// Forgets `popover` if it is the open one, returns whether it was.
+    pub fn popover_hidden(&self, popover: &impl IsA<Popover>) -> bool {
+        let imp = imp::AppsContainer::from_instance(self);
+        let is_open = matches!(
+            imp.open_popover.borrow().as_ref(),
+            Some((open, _)) if open == popover.upcast_ref::<Popover>()
+        );
+        if is_open {
+            imp.open_popover.replace(None);
+        }
+        is_open
+    }
+
+    pub fn has_open_popover(&self) -> bool {
+        let imp = imp::AppsContainer::from_instance(self);
+        imp.open_popover.borrow().is_some()
+    }
+
+    /// Closes the open popover, returns whether there was one.
+    pub fn close_popover(&self) -> bool {
+        let imp = imp::AppsContainer::from_instance(self);
+        let open = imp.open_popover.borrow_mut().take();
+        match open {
+            Some((popover, _)) => {
+                popover.popdown();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Closes the open popover if the window list removed the object of its item.
+    pub fn close_stale_popover(&self) {
+        let imp = imp::AppsContainer::from_instance(self);
+        let dock_object = match imp.open_popover.borrow().as_ref() {
+            Some((_, item)) => item.upgrade().and_then(|item| item.dock_object()),
+            None => return,
+        };
+        let listed = dock_object
+            .filter(|dock_object| !dock_object.property::<bool>("removing"))
+            .map(|dock_object| {
+                [
+                    DockListType::Saved,
+                    DockListType::Recent,
+                    DockListType::Active,
+                    DockListType::System,
+                ]
+                .into_iter()
+                .any(|type_| {
+                    let model = self.model(type_);
+                    (0..model.n_items())
+                        .any(|i| model.item(i).as_ref() == Some(dock_object.upcast_ref()))
+                })
+            })
+            .unwrap_or_default();
+        if !listed {
+            self.close_popover();
+        }
+    }
+
+    /// Finds the dock objects for the desktop id `id` in both models.
+    pub fn dock_objects_by_id(&self, id: &str) -> Vec<DockObject> {
+        let mut dock_objects = Vec::new();
+        for type_ in [DockListType::Saved, DockListType::Recent, DockListType::Active] {
+            let model = self.model(type_);
+            let mut i = 0;
+            while let Some(item) = model.item(i) {
+                if let Ok(dock_object) = item.downcast::<DockObject>() {
+                    if dock_object.get_id().as_deref() == Some(id) {
+                        dock_objects.push(dock_object);
+                    }
+                }
+                i += 1;
+            }
+        }
+        dock_objects
+    }
+
+    /// The dock object of the app with desktop id or window app id `app_id`, preferring
+    /// objects with windows.
+    pub fn dock_object_for_app(&self, app_id: &str) -> Option<DockObject> {
+        let desktop_id = if app_id.ends_with(".desktop") {
+            app_id.to_string()
+        } else {
+            format!("{}.desktop", app_id)
+        };
+        let mut found = self.dock_objects_by_id(&desktop_id);
+        if found.is_empty() {
+            // e.g. the Flatpak id of an app saved from the distribution's package
+            for type_ in [DockListType::Saved, DockListType::Active] {
+                let model = self.model(type_);
+                let mut i = 0;
+                while let Some(item) = model.item(i) {
+                    if let Ok(dock_object) = item.downcast::<DockObject>() {
+                        let windows = dock_object.property::<BoxedWindowList>("active").0;
+                        let same_id = dock_object
+                            .get_id()
+                            .map(|id| app_id::same_app(&id, app_id))
+                            .unwrap_or_default();
+                        if same_id || windows.iter().any(|w| app_id::same_app(&w.app_id, app_id)) {
+                            found.push(dock_object);
+                        }
+                    }
+                    i += 1;
+                }
+            }
+        }
+        let has_windows =
+            |o: &DockObject| !o.property::<BoxedWindowList>("active").0.is_empty();
+        found
+            .iter()
+            .find(|o| has_windows(o))
+            .or_else(|| found.first())
+            .cloned()
+    }
+
     pub fn set_position(&self, position: Anchor) {
-        self.set_orientation(position.into());
+        // the open popover would point the wrong way
+        self.close_popover();
+        let orientation: Orientation = position.into();
+        self.set_orientation(orientation);
         let imp = imp::AppsContainer::from_instance(self);
+        imp.config.borrow_mut().anchor = position;
+        // the separators run across the dock
+        let separator_orientation = match orientation {
+            Orientation::Horizontal => Orientation::Vertical,
+            _ => Orientation::Horizontal,
+        };
+        imp.separator.get().unwrap().set_orientation(separator_orientation);
+        imp.separator_box.get().unwrap().set_orientation(orientation);
+        imp.separator_revealer
+            .get()
+            .unwrap()
+            .set_transition_type(match orientation {
+                Orientation::Horizontal => RevealerTransitionType::SlideRight,
+                _ => RevealerTransitionType::SlideDown,
+            });
+        imp.recent_separator.get().unwrap().set_orientation(separator_orientation);
+        imp.system_separator.get().unwrap().set_orientation(separator_orientation);
         imp.saved_list.get().unwrap().set_position(position);
         imp.active_list.get().unwrap().set_position(position);
+        imp.recent_list.get().unwrap().set_position(position);
+        imp.system_list.get().unwrap().set_position(position);
+        imp.toast.get().unwrap().set_anchor(position);
+        self.update_scroll_policy();
+        self.queue_overflow_update();
+    }
+
+    /// Shows a short message next to the dock, messages arriving while one is shown queue up.
+    pub fn show_toast(&self, severity: Severity, message: String) {
+        let imp = imp::AppsContainer::from_instance(self);
+        imp.toast.get().unwrap().show_message(severity, message);
+    }
+
+    /// Applies the dock size, rescaling the icons of both lists.
+    pub fn set_size(&self, size: PanelSize) {
+        let imp = imp::AppsContainer::from_instance(self);
+        imp.config.borrow_mut().size = size;
+        self.update_icon_size();
+    }
+
+    /// Icon size in pixels used instead of the size of the dock, 0 follows the dock again.
+    pub fn set_icon_size_override(&self, icon_size: u32) {
+        let imp = imp::AppsContainer::from_instance(self);
+        if imp.icon_size_override.replace(icon_size) != icon_size {
+            self.update_icon_size();
+        }
+    }
+
+    fn update_icon_size(&self) {
+        let imp = imp::AppsContainer::from_instance(self);
+        let icon_size = match imp.icon_size_override.get() {
+            0 => imp.config.borrow().get_applet_icon_size(),
+            icon_size => icon_size,
+        };
+        imp.saved_list.get().unwrap().set_icon_size(icon_size);
+        imp.active_list.get().unwrap().set_icon_size(icon_size);
+        imp.recent_list.get().unwrap().set_icon_size(icon_size);
+        imp.system_list.get().unwrap().set_icon_size(icon_size);
+        self.update_size_class(icon_size);
+    }
+
+    fn update_size_class(&self, icon_size: u32) {
+        let tier = size_class(icon_size);
+        for (_, class) in SIZE_CLASSES {
+            if class != tier {
+                self.remove_css_class(class);
+            }
+        }
+        self.add_css_class(tier);
     }
 
     fn setup_callbacks(&self) {
@@ -99,4 +1310,126 @@ impl AppsContainer {
         // hack to prevent hiding window when dnd from other apps
         drop_controller.connect_enter(move |_self, _x, _y| gtk4::gdk::DragAction::COPY);
     }
+
+    fn setup_file_drop_target(&self) {
+        let imp = imp::AppsContainer::from_instance(self);
+        // file managers and app grids offer text/uri-list, which gtk provides as a FileList
+        let drop_controller = DropTarget::builder()
+            .preload(true)
+            .actions(gdk::DragAction::COPY)
+            .formats(&gdk::ContentFormats::for_type(gdk::FileList::static_type()))
+            .build();
+        self.add_controller(&drop_controller);
+
+        let tx = imp.tx.get().unwrap().clone();
+        drop_controller.connect_drop(
+            glib::clone!(@weak self as self_ => @default-return false, move |_self, drop_value, _x, _y| {
+                let files = match drop_value.get::<gdk::FileList>() {
+                    Ok(files) => files.files(),
+                    Err(_) => return false,
+                };
+                let mut accepted = false;
+                for file in files {
+                    let app_info = file
+                        .path()
+                        .filter(|path| path.extension().map(|e| e == "desktop").unwrap_or_default())
+                        .and_then(DesktopAppInfo::from_filename);
+                    let app_info = match app_info {
+                        Some(app_info) if !self_.is_saved(&app_info) => app_info,
+                        _ => {
+                            self_.flash_rejected();
+                            continue;
+                        }
+                    };
+                    // saved apps are stored by desktop id, so the file has to be installed
+                    let id = app_info
+                        .filename()
+                        .and_then(|path| path.file_name().map(|name| name.to_string_lossy().to_string()))
+                        .filter(|id| DesktopAppInfo::new(id).is_some());
+                    match id {
+                        Some(id) => {
+                            send_event(
+                                &tx,
+                                Event::Favorite {
+                                    id,
+                                    favorite: true,
+                                    index: None,
+                                },
+                            );
+                            accepted = true;
+                        }
+                        None => self_.flash_rejected(),
+                    }
+                }
+                accepted
+            }),
+        );
+    }
+
+    fn is_saved(&self, app_info: &DesktopAppInfo) -> bool {
+        let saved_model = self.model(DockListType::Saved);
+        let mut i = 0;
+        while let Some(item) = saved_model.item(i) {
+            if let Ok(dock_object) = item.downcast::<DockObject>() {
+                if dock_object.get_path().map(PathBuf::from) == app_info.filename() {
+                    return true;
+                }
+            }
+            i += 1;
+        }
+        false
+    }
+
+    // briefly shake the dock to show that a drop was rejected
+    fn flash_rejected(&self) {
+        if !self.animations_enabled() {
+            return;
+        }
+        self.add_css_class("drop_rejected");
+        glib::timeout_add_local_once(
+            Duration::from_millis(400),
+            glib::clone!(@weak self as self_ => move || {
+                self_.remove_css_class("drop_rejected");
+            }),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn size_tiers_follow_the_icon_size() {
+        let cases = [
+            (16, "size-xs"),
+            (23, "size-xs"),
+            (24, "size-s"),
+            (32, "size-m"),
+            (47, "size-m"),
+            (48, "size-l"),
+            (64, "size-xl"),
+            (256, "size-xl"),
+        ];
+        for (icon_size, class) in cases {
+            assert_eq!(size_class(icon_size), class, "{} px", icon_size);
+        }
+    }
+
+    #[test]
+    fn every_size_tier_is_styled() {
+        let style = include_str!("../style.css");
+        for (_, class) in SIZE_CLASSES {
+            let selectors = [
+                format!(".{} dockitem", class),
+                format!(".{}.horizontal dockitem", class),
+                format!(".{}.vertical dockitem", class),
+                format!(".{} dockitem box.dock-item-box", class),
+                format!(".{} separator.dock-separator", class),
+            ];
+            for selector in selectors {
+                assert!(style.contains(&selector), "no rule for {}", selector);
+            }
+        }
+    }
 }
@@ -1,6 +1,6 @@
 // SPDX-License-Identifier: MPL-2.0-only
 
-use crate::{apps_container::AppsContainer, fl, Event};
+use crate::{apps_container::AppsContainer, fl, settings_page};
 use cascade::cascade;
 use gtk4::{
     gio,
@@ -8,7 +8,6 @@ use gtk4::{
     prelude::*,
     subclass::prelude::*,
 };
-use tokio::sync::mpsc;
 
 mod imp;
 
@@ -20,7 +19,8 @@ glib::wrapper! {
 }
 
 impl CosmicDockAppListWindow {
-    pub fn new(app: &gtk4::Application, tx: mpsc::Sender<Event>) -> Self {
+    /// A window showing `app_list`, which is owned and kept up to date by the backend.
+    pub fn new(app: &gtk4::Application, app_list: &AppsContainer) -> Self {
         let self_: Self = Object::new(&[("application", app)])
             .expect("Failed to create `CosmicDockAppListWindow`.");
         let imp = imp::CosmicDockAppListWindow::from_instance(&self_);
@@ -34,11 +34,32 @@ impl CosmicDockAppListWindow {
             ..set_title(Some(&fl!("cosmic-dock-app-list")));
             ..add_css_class("transparent");
         };
-        let app_list = AppsContainer::new(tx);
-        self_.set_child(Some(&app_list));
-        imp.inner.set(app_list).unwrap();
+        // the standalone window has no host to open the preferences from
+        let menu = gio::Menu::new();
+        menu.append(Some(&fl!("preferences")), Some("win.preferences"));
+        menu.append(Some(&fl!("quit")), Some("win.quit"));
+        let menu_button = cascade! {
+            gtk4::MenuButton::new();
+            ..set_icon_name("open-menu-symbolic");
+            ..set_menu_model(Some(&menu));
+            ..set_valign(gtk4::Align::Center);
+            ..add_css_class("flat");
+        };
+        let content = cascade! {
+            gtk4::Box::new(gtk4::Orientation::Horizontal, 0);
+            ..append(app_list);
+            ..append(&menu_button);
+        };
+        self_.set_child(Some(&content));
+        imp.inner.set(app_list.clone()).unwrap();
 
         self_.setup_shortcuts();
+        // the app list belongs to the backend, so it must not be destroyed with the window
+        self_.connect_close_request(glib::clone!(@weak app_list => @default-return gtk4::Inhibit(false), move |window| {
+            content.remove(&app_list);
+            window.set_child(None::<&gtk4::Widget>);
+            gtk4::Inhibit(false)
+        }));
 
         self_
     }
@@ -47,10 +68,46 @@ impl CosmicDockAppListWindow {
         let window = self.clone().upcast::<gtk4::Window>();
         let action_quit = gio::SimpleAction::new("quit", None);
         action_quit.connect_activate(glib::clone!(@weak window => move |_, _| {
+            // the application returns from `run` and tears the backend down on shutdown
             window.close();
             if let Some(a) = window.application() { a.quit() }
-            std::process::exit(0);
         }));
         self.add_action(&action_quit);
+
+        let action_preferences = gio::SimpleAction::new("preferences", None);
+        action_preferences.connect_activate(glib::clone!(@weak window => move |_, _| {
+            cascade! {
+                gtk4::Window::new();
+                ..set_title(Some(&fl!("preferences")));
+                ..set_transient_for(Some(&window));
+                ..set_child(Some(&settings_page::new()));
+                ..present();
+            };
+        }));
+        self.add_action(&action_preferences);
+
+        // Super+1..9 activate the saved apps in dock order
+        let action_activate_saved =
+            gio::SimpleAction::new("activate-saved", Some(glib::VariantTy::UINT32));
+        action_activate_saved.connect_activate(glib::clone!(@weak self as self_ => move |_, index| {
+            if let Some(index) = index.and_then(|index| index.get::<u32>()) {
+                self_.activate_saved(index);
+            }
+        }));
+        self.add_action(&action_activate_saved);
+        if let Some(app) = self.application() {
+            for i in 0..9u32 {
+                let action = gio::Action::print_detailed_name(
+                    "win.activate-saved",
+                    Some(&i.to_variant()),
+                );
+                app.set_accels_for_action(&action, &[&format!("<Super>{}", i + 1)]);
+            }
+        }
+    }
+
+    pub fn activate_saved(&self, index: u32) {
+        let imp = imp::CosmicDockAppListWindow::from_instance(self);
+        imp.inner.get().unwrap().activate_saved(index);
     }
 }
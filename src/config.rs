@@ -0,0 +1,245 @@
+// SPDX-License-Identifier: MPL-2.0-only
+
+use crate::style;
+use crate::utils::config_path;
+use gio::glib;
+use gio::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::time::Duration;
+
+/// What a primary click on a running app does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClickAction {
+    /// Focuses the window that was focused most recently.
+    FocusLast,
+    /// Focuses the next window of the app on every click.
+    CycleWindows,
+    /// Minimizes the app if its window has focus, focuses it otherwise.
+    MinimizeToggle,
+    /// Shows the window list, apps with a single window are focused.
+    ShowPopover,
+}
+
+impl Default for ClickAction {
+    fn default() -> Self {
+        Self::ShowPopover
+    }
+}
+
+impl ClickAction {
+    /// Apps with a single window have nothing to cycle through or pick from.
+    pub fn for_window_count(self, n_windows: usize) -> Self {
+        match self {
+            Self::CycleWindows | Self::ShowPopover if n_windows == 1 => Self::MinimizeToggle,
+            action => action,
+        }
+    }
+}
+
+/// How running apps that aren't saved are ordered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    /// The app whose window was focused last comes first.
+    MostRecentlyUsed,
+    /// Apps are added at the end when their first window opens.
+    LaunchOrder,
+    /// By app id, or window title for windows without one.
+    Alphabetical,
+}
+
+impl Default for SortOrder {
+    fn default() -> Self {
+        Self::LaunchOrder
+    }
+}
+
+/// How the windows of running apps that aren't saved are shown, saved apps always group their
+/// windows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupingMode {
+    /// One item per app, listing its windows in the popover.
+    GroupByApp,
+    /// One item per window, like a classic taskbar.
+    OnePerWindow,
+}
+
+impl Default for GroupingMode {
+    fn default() -> Self {
+        Self::GroupByApp
+    }
+}
+
+/// Whether the dock items show the app names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DisplayMode {
+    IconsOnly,
+    /// The name goes beside the icon on a horizontal dock and below it on a vertical one.
+    IconsAndLabels,
+}
+
+impl Default for DisplayMode {
+    fn default() -> Self {
+        Self::IconsOnly
+    }
+}
+
+/// User configuration of the app list.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AppsConfig {
+    /// How often the window list is polled when the daemon doesn't send change signals.
+    pub poll_interval_ms: u64,
+    /// Shows the saved apps, a tasks-only dock is shown otherwise.
+    pub show_saved: bool,
+    /// Shows running apps that aren't saved, a launcher-only dock is shown otherwise.
+    pub show_active: bool,
+    /// Lists windows from every output instead of only those on the dock's output.
+    pub all_outputs: bool,
+    /// Shows removable drives and the trash after the apps.
+    pub show_system: bool,
+    /// How many recently launched apps are shown between the saved and running apps, 0 hides them.
+    pub recent_count: usize,
+    pub click_action: ClickAction,
+    pub sort_order: SortOrder,
+    pub grouping_mode: GroupingMode,
+    /// Icon size in pixels, 0 uses the size of the dock.
+    pub icon_size: u32,
+    pub display_mode: DisplayMode,
+    /// Shows thumbnails of the windows when the pointer rests on a running app.
+    pub hover_previews: bool,
+    /// Animates launching, adding and removing apps and apps that need attention, see
+    /// [`AppsConfig::animations_enabled`].
+    pub animations: bool,
+}
+
+impl Default for AppsConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval_ms: 200,
+            show_saved: true,
+            show_active: true,
+            all_outputs: false,
+            show_system: false,
+            recent_count: 4,
+            click_action: ClickAction::default(),
+            sort_order: SortOrder::default(),
+            grouping_mode: GroupingMode::default(),
+            icon_size: 0,
+            display_mode: DisplayMode::default(),
+            hover_previews: false,
+            animations: true,
+        }
+    }
+}
+
+/// Id of the GSettings schema in `data/`.
+pub const SETTINGS_SCHEMA: &str = "com.system76.dock.apps";
+
+/// The settings of the app list, `None` if the schema isn't installed.
+pub fn settings() -> Option<gio::Settings> {
+    gio::SettingsSchemaSource::default()?.lookup(SETTINGS_SCHEMA, true)?;
+    Some(gio::Settings::new(SETTINGS_SCHEMA))
+}
+
+// the choices of the string keys match the serde names of the enums
+fn enum_key<T: DeserializeOwned + Default>(settings: &gio::Settings, key: &str) -> T {
+    let value = settings.string(key).to_string();
+    serde_json::from_value(serde_json::Value::String(value.clone())).unwrap_or_else(|_| {
+        log::warn!("Invalid value {} for {}", value, key);
+        T::default()
+    })
+}
+
+fn set_enum_key<T: Serialize>(settings: &gio::Settings, key: &str, value: T) -> Result<(), String> {
+    match serde_json::to_value(value) {
+        Ok(serde_json::Value::String(value)) => {
+            settings.set_string(key, &value).map_err(|err| err.to_string())
+        }
+        _ => Err(format!("{} is not a string key", key)),
+    }
+}
+
+impl AppsConfig {
+    /// Reads the GSettings schema if it is installed, then the config file, then the defaults.
+    pub fn load() -> Self {
+        if let Some(settings) = settings() {
+            return Self::from_settings(&settings);
+        }
+        File::open(config_path())
+            .ok()
+            .and_then(|file| serde_json::from_reader(file).ok())
+            .unwrap_or_default()
+    }
+
+    /// Writes the config where [`AppsConfig::load`] reads it from. The running app list picks
+    /// up the change from there.
+    pub fn save(&self) -> Result<(), String> {
+        match settings() {
+            Some(settings) => self.write_settings(&settings),
+            None => {
+                let file = File::create(config_path()).map_err(|err| err.to_string())?;
+                serde_json::to_writer_pretty(file, self).map_err(|err| err.to_string())
+            }
+        }
+    }
+
+    fn write_settings(&self, settings: &gio::Settings) -> Result<(), String> {
+        // applied at once, so the app list sees a single change
+        settings.delay();
+        let result = (|| {
+            let set = |result: Result<(), glib::BoolError>| result.map_err(|err| err.to_string());
+            set(settings.set_uint64("poll-interval-ms", self.poll_interval_ms))?;
+            set(settings.set_boolean("show-saved", self.show_saved))?;
+            set(settings.set_boolean("show-active", self.show_active))?;
+            set(settings.set_boolean("all-outputs", self.all_outputs))?;
+            set(settings.set_boolean("show-system", self.show_system))?;
+            set(settings.set_uint("recent-count", self.recent_count as u32))?;
+            set_enum_key(settings, "click-action", self.click_action)?;
+            set_enum_key(settings, "sort-order", self.sort_order)?;
+            set_enum_key(settings, "grouping-mode", self.grouping_mode)?;
+            set(settings.set_uint("icon-size", self.icon_size))?;
+            set_enum_key(settings, "display-mode", self.display_mode)?;
+            set(settings.set_boolean("hover-previews", self.hover_previews))?;
+            set(settings.set_boolean("animations", self.animations))
+        })();
+        if result.is_ok() {
+            settings.apply();
+        } else {
+            settings.revert();
+        }
+        result
+    }
+
+    pub fn from_settings(settings: &gio::Settings) -> Self {
+        Self {
+            poll_interval_ms: settings.uint64("poll-interval-ms"),
+            show_saved: settings.boolean("show-saved"),
+            show_active: settings.boolean("show-active"),
+            all_outputs: settings.boolean("all-outputs"),
+            show_system: settings.boolean("show-system"),
+            recent_count: settings.uint("recent-count") as usize,
+            click_action: enum_key(settings, "click-action"),
+            sort_order: enum_key(settings, "sort-order"),
+            grouping_mode: enum_key(settings, "grouping-mode"),
+            icon_size: settings.uint("icon-size"),
+            display_mode: enum_key(settings, "display-mode"),
+            hover_previews: settings.boolean("hover-previews"),
+            animations: settings.boolean("animations"),
+        }
+    }
+
+    /// Animations are off if either the config or the desktop turns them off.
+    pub fn animations_enabled(&self) -> bool {
+        self.animations && style::desktop_animations()
+    }
+
+    pub fn poll_interval(&self) -> Duration {
+        Duration::from_millis(self.poll_interval_ms)
+    }
+}
@@ -0,0 +1,123 @@
+// SPDX-License-Identifier: MPL-2.0-only
+
+//! Saved apps without a desktop entry that open a URL or run a command, e.g. web apps. They are
+//! stored in the saved apps file next to the desktop ids and get ids starting with
+//! `custom-launcher:`, which the rest of the dock handles like desktop ids.
+
+use crate::utils::Item;
+use gio::{AppInfo, AppLaunchContext, Icon};
+use gtk4::prelude::*;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+const ID_PREFIX: &str = "custom-launcher:";
+
+/// What a custom launcher starts.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LaunchTarget {
+    /// opened in the default handler, usually the browser
+    Url(String),
+    /// a command line
+    Exec(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct CustomLauncher {
+    pub id: String,
+    pub name: String,
+    /// icon name or path, empty for a generic icon
+    #[serde(default)]
+    pub icon: String,
+    pub target: LaunchTarget,
+    /// windows whose app id is this or whose title contains it belong to the launcher,
+    /// nothing is matched if it is empty
+    #[serde(default)]
+    pub match_string: String,
+}
+
+/// An entry of the saved apps file, which only held desktop ids before.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum StoredApp {
+    Id(String),
+    Custom(CustomLauncher),
+}
+
+static LAUNCHERS: Lazy<RwLock<HashMap<String, CustomLauncher>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+pub fn is_custom(id: &str) -> bool {
+    id.starts_with(ID_PREFIX)
+}
+
+pub fn get(id: &str) -> Option<CustomLauncher> {
+    LAUNCHERS.read().unwrap().get(id).cloned()
+}
+
+/// Makes the launcher known by its id, replacing a launcher with the same id.
+pub fn register(launcher: CustomLauncher) {
+    LAUNCHERS.write().unwrap().insert(launcher.id.clone(), launcher);
+}
+
+impl CustomLauncher {
+    /// A launcher with an id derived from `name` that no other launcher has.
+    pub fn new(name: &str, icon: &str, target: LaunchTarget, match_string: &str) -> Self {
+        let slug: String = name
+            .to_lowercase()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+            .collect();
+        let slug = match slug.trim_matches('-') {
+            "" => "launcher",
+            slug => slug,
+        };
+        let launchers = LAUNCHERS.read().unwrap();
+        let id = (1..)
+            .map(|i| match i {
+                1 => format!("{}{}", ID_PREFIX, slug),
+                i => format!("{}{}-{}", ID_PREFIX, slug, i),
+            })
+            .find(|id| !launchers.contains_key(id))
+            .expect("Ran out of launcher ids");
+        Self {
+            id,
+            name: name.to_string(),
+            icon: icon.to_string(),
+            target,
+            match_string: match_string.to_string(),
+        }
+    }
+
+    pub fn icon(&self) -> Icon {
+        Icon::for_string(&self.icon)
+            .or_else(|_| Icon::for_string("web-browser"))
+            .expect("Failed to set default icon")
+    }
+
+    pub fn launch(&self, context: &AppLaunchContext) -> Result<(), String> {
+        match &self.target {
+            LaunchTarget::Url(url) => {
+                AppInfo::launch_default_for_uri(url, Some(context)).map_err(|err| err.to_string())
+            }
+            LaunchTarget::Exec(exec) => AppInfo::create_from_commandline(
+                exec,
+                Some(&self.name),
+                gio::AppInfoCreateFlags::SUPPORTS_STARTUP_NOTIFICATION,
+            )
+            .and_then(|app_info| app_info.launch(&[], Some(context)))
+            .map_err(|err| err.to_string()),
+        }
+    }
+}
+
+/// Whether the window belongs to a launcher with `match_string`.
+pub fn window_matches(match_string: &str, item: &Item) -> bool {
+    if match_string.is_empty() {
+        return false;
+    }
+    let match_string = match_string.to_lowercase();
+    item.app_id.to_lowercase() == match_string || item.name.to_lowercase().contains(&match_string)
+}
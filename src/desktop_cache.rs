@@ -0,0 +1,319 @@
+// SPDX-License-Identifier: MPL-2.0-only
+
+//! Desktop entries resolved for windows. Resolving scans every desktop file, so it happens on a
+//! worker thread and the results, including windows without an entry, are kept until the
+//! installed apps change.
+
+use crate::utils::{item_matches_app_info, Item};
+use gio::DesktopAppInfo;
+use gtk4::prelude::*;
+use once_cell::sync::Lazy;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{mpsc as std_mpsc, Mutex};
+use std::thread;
+
+#[derive(Debug, Clone)]
+enum Entry {
+    Pending,
+    /// path of the desktop file, `None` if no entry matches
+    Resolved(Option<String>),
+}
+
+static ENTRIES: Lazy<Mutex<HashMap<String, Entry>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+static WORKER: Lazy<Mutex<Option<std_mpsc::Sender<(String, Item)>>>> =
+    Lazy::new(|| Mutex::new(None));
+
+thread_local! {
+    // app infos are only used on the main thread, so they are loaded once per path there
+    static APP_INFOS: RefCell<HashMap<String, Option<DesktopAppInfo>>> = RefCell::new(HashMap::new());
+    // the scans of the desktop files made on this thread
+    #[cfg(test)]
+    static SCANS: std::cell::Cell<usize> = std::cell::Cell::new(0);
+}
+
+// the fields the matching looks at
+fn cache_key(item: &Item) -> String {
+    format!("{}\n{}\n{}", item.desktop_entry, item.app_id, item.description)
+}
+
+/// Starts the worker thread, which calls `resolved` whenever it resolved new windows.
+/// A worker started before stops once its queue is dropped.
+pub fn start<F: Fn() + Send + 'static>(resolved: F) {
+    let (request_tx, request_rx) = std_mpsc::channel::<(String, Item)>();
+    let spawned = thread::Builder::new()
+        .name("app-list-desktop-entries".into())
+        .spawn(move || {
+            while let Ok(request) = request_rx.recv() {
+                // windows opening together are resolved with a single scan
+                let mut requests = vec![request];
+                requests.extend(request_rx.try_iter());
+                let paths = resolve(requests.iter().map(|(_, item)| item));
+                let mut entries = ENTRIES.lock().unwrap();
+                for ((key, _), path) in requests.into_iter().zip(paths) {
+                    // the cache may have been cleared meanwhile, those results are outdated
+                    if let Some(entry) = entries.get_mut(&key) {
+                        *entry = Entry::Resolved(path);
+                    }
+                }
+                drop(entries);
+                resolved();
+            }
+        });
+    match spawned {
+        Ok(_) => *WORKER.lock().unwrap() = Some(request_tx),
+        Err(err) => log::warn!("Failed to start the desktop entry lookup: {}", err),
+    }
+}
+
+/// Stops the worker thread once it finished the lookups it already started.
+pub fn stop() {
+    drop(WORKER.lock().unwrap().take());
+}
+
+/// The desktop entry of the windows like `item`. Windows that weren't seen before are resolved
+/// in the background and have no entry until the refresh queued after that.
+pub fn lookup(item: &Item) -> Option<DesktopAppInfo> {
+    let key = cache_key(item);
+    let mut entries = ENTRIES.lock().unwrap();
+    let path = match entries.get(&key) {
+        Some(Entry::Resolved(path)) => path.clone()?,
+        Some(Entry::Pending) => return None,
+        None => {
+            match WORKER.lock().unwrap().as_ref() {
+                Some(worker) if worker.send((key.clone(), item.clone())).is_ok() => {
+                    entries.insert(key, Entry::Pending);
+                }
+                // without the worker the lookup blocks
+                _ => {
+                    let path = resolve(std::iter::once(item)).pop().flatten();
+                    entries.insert(key, Entry::Resolved(path.clone()));
+                    drop(entries);
+                    return path.and_then(|path| app_info(&path));
+                }
+            }
+            return None;
+        }
+    };
+    drop(entries);
+    app_info(&path)
+}
+
+fn app_info(path: &str) -> Option<DesktopAppInfo> {
+    APP_INFOS.with(|app_infos| {
+        app_infos
+            .borrow_mut()
+            .entry(path.to_string())
+            .or_insert_with(|| DesktopAppInfo::from_filename(path))
+            .clone()
+    })
+}
+
+/// Forgets every resolved window, called when apps are installed or removed.
+pub fn invalidate() {
+    ENTRIES.lock().unwrap().clear();
+    APP_INFOS.with(|app_infos| app_infos.borrow_mut().clear());
+}
+
+// the desktop file path for each item, hidden entries are only used when no visible entry matches
+fn resolve<'a>(items: impl Iterator<Item = &'a Item>) -> Vec<Option<String>> {
+    match xdg::BaseDirectories::new() {
+        Ok(dirs) => {
+            let mut data_dirs = vec![dirs.get_data_home()];
+            data_dirs.extend(dirs.get_data_dirs());
+            resolve_in(&data_dirs, items)
+        }
+        Err(err) => {
+            log::warn!("Could not access the XDG base directories: {}", err);
+            items.map(|_| None).collect()
+        }
+    }
+}
+
+// like `resolve` with the desktop files of `data_dirs`, earlier directories override the
+// entries of later ones
+fn resolve_in<'a>(
+    data_dirs: &[PathBuf],
+    items: impl Iterator<Item = &'a Item>,
+) -> Vec<Option<String>> {
+    #[cfg(test)]
+    SCANS.with(|scans| scans.set(scans.get() + 1));
+    let items: Vec<&Item> = items.collect();
+    let mut shown: Vec<Option<String>> = vec![None; items.len()];
+    let mut hidden: Vec<Option<String>> = vec![None; items.len()];
+    let mut file_names = HashSet::new();
+    let app_infos = data_dirs
+        .iter()
+        .filter_map(|data_dir| std::fs::read_dir(data_dir.join("applications")).ok())
+        .flatten()
+        .filter_map(|dir_entry| {
+            let path = dir_entry.ok()?.path();
+            let file_name = path.file_name()?.to_str()?.to_string();
+            if !file_names.insert(file_name) {
+                return None;
+            }
+            DesktopAppInfo::from_filename(&path)
+        });
+    for app_info in app_infos {
+        let path = match app_info.filename() {
+            Some(path) => path.to_string_lossy().to_string(),
+            None => continue,
+        };
+        let visible = app_info.should_show();
+        for (i, item) in items.iter().enumerate() {
+            let found = if visible { &mut shown[i] } else { &mut hidden[i] };
+            if found.is_none() && item_matches_app_info(item, &app_info) {
+                *found = Some(path.clone());
+            }
+        }
+    }
+    shown
+        .into_iter()
+        .zip(hidden)
+        .map(|(shown, hidden)| shown.or(hidden))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{GroupingMode, SortOrder};
+    use crate::dock_state::{apply_window_list, DockState, StackHistory};
+    use std::path::Path;
+
+    // a data directory of fixture desktop files for each test, as they run in parallel
+    fn fixture_data_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "cosmic-dock-desktop-entries-{}-{}",
+            std::process::id(),
+            name
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        let apps = dir.join("applications");
+        std::fs::create_dir_all(&apps).unwrap();
+        let entries = [
+            ("org.example.Viewer", "Viewer", "viewer", ""),
+            (
+                "org.example.ViewerHelper",
+                "Viewer Helper",
+                "viewer",
+                "NoDisplay=true\n",
+            ),
+            (
+                "org.example.Daemon",
+                "Daemon",
+                "exampled",
+                "NoDisplay=true\n",
+            ),
+        ];
+        for (id, name, class, extra) in entries {
+            let entry = format!(
+                "[Desktop Entry]\nType=Application\nName={}\nExec=true\nStartupWMClass={}\n{}",
+                name, class, extra
+            );
+            std::fs::write(apps.join(format!("{}.desktop", id)), entry).unwrap();
+        }
+        dir
+    }
+
+    fn window(app_id: &str) -> Item {
+        Item {
+            app_id: app_id.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn fixture_path(dir: &Path, id: &str) -> Option<String> {
+        let path = dir.join("applications").join(format!("{}.desktop", id));
+        Some(path.to_string_lossy().to_string())
+    }
+
+    fn scans() -> usize {
+        SCANS.with(|scans| scans.get())
+    }
+
+    #[test]
+    fn visible_entries_win_over_hidden_ones() {
+        let dir = fixture_data_dir("visible");
+        let items = [window("viewer"), window("exampled"), window("unknown")];
+        assert_eq!(
+            resolve_in(&[dir.clone()], items.iter()),
+            vec![
+                fixture_path(&dir, "org.example.Viewer"),
+                // hidden entries are still better than none
+                fixture_path(&dir, "org.example.Daemon"),
+                None,
+            ]
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn earlier_data_dirs_override_later_ones() {
+        let user_dir = fixture_data_dir("user");
+        let system_dir = fixture_data_dir("system");
+        // the user hid the viewer, the visible system entry is overridden
+        let viewer = user_dir.join("applications/org.example.Viewer.desktop");
+        let entry = std::fs::read_to_string(&viewer).unwrap();
+        std::fs::write(&viewer, format!("{}NoDisplay=true\n", entry)).unwrap();
+        let helper = user_dir.join("applications/org.example.ViewerHelper.desktop");
+        std::fs::remove_file(helper).unwrap();
+
+        let items = [window("viewer")];
+        assert_eq!(
+            resolve_in(&[user_dir.clone(), system_dir.clone()], items.iter()),
+            vec![fixture_path(&user_dir, "org.example.Viewer")]
+        );
+        std::fs::remove_dir_all(user_dir).unwrap();
+        std::fs::remove_dir_all(system_dir).unwrap();
+    }
+
+    #[test]
+    fn lookup_without_worker_resolves_right_away() {
+        let item = window("cosmic-dock-test-not-installed");
+        let start = scans();
+        assert!(lookup(&item).is_none());
+        assert_eq!(scans(), start + 1);
+        // no entry is remembered as well
+        assert!(matches!(
+            ENTRIES.lock().unwrap().get(&cache_key(&item)),
+            Some(Entry::Resolved(None))
+        ));
+        assert!(lookup(&item).is_none());
+        assert_eq!(scans(), start + 1);
+    }
+
+    #[test]
+    fn warm_sync_of_200_windows_does_not_rescan() {
+        let windows: Vec<Item> = (0..200)
+            .map(|i| Item {
+                entity: (i, 0),
+                app_id: format!("cosmic-dock-test-app-{}", i % 40),
+                ..Default::default()
+            })
+            .collect();
+        // windows of the same app share one scan
+        let start = scans();
+        for window in &windows {
+            lookup(window);
+        }
+        assert_eq!(scans(), start + 40);
+
+        for window in &windows {
+            assert!(lookup(window).is_none());
+        }
+        let mut state = DockState::default();
+        let mut history = StackHistory::default();
+        history.update(&windows);
+        apply_window_list(
+            &mut state,
+            windows,
+            &history,
+            SortOrder::MostRecentlyUsed,
+            GroupingMode::GroupByApp,
+        );
+        assert_eq!(state.active.len(), 40);
+        assert_eq!(scans(), start + 40);
+    }
+}
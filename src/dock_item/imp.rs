@@ -1,5 +1,6 @@
 // SPDX-License-Identifier: MPL-2.0-only
 
+use cosmic_panel_config::config::Anchor;
 use glib::subclass::Signal;
 use gtk4::glib;
 use gtk4::prelude::*;
@@ -10,6 +11,7 @@ use std::cell::{Cell, RefCell};
 use std::rc::Rc;
 use tokio::sync::mpsc::Sender;
 
+use crate::dock_object::DockObject;
 use crate::dock_popover::DockPopover;
 use crate::utils::Event;
 
@@ -17,11 +19,34 @@ use crate::utils::Event;
 pub struct DockItem {
     pub image: Rc<RefCell<Option<gtk4::Image>>>,
     pub dots: Rc<RefCell<gtk4::Box>>,
+    pub icon_box: OnceCell<gtk4::Box>,
+    pub label: OnceCell<gtk4::Label>,
+    pub badge: OnceCell<gtk4::Label>,
+    pub progress_bar: OnceCell<gtk4::ProgressBar>,
+    pub revealer: OnceCell<gtk4::Revealer>,
     pub item_box: Rc<RefCell<gtk4::Box>>,
     pub popover: Rc<RefCell<gtk4::Popover>>,
     pub popover_menu: Rc<RefCell<Option<DockPopover>>>,
+    pub context_menu: RefCell<Option<gtk4::PopoverMenu>>,
+    pub favorite_action: OnceCell<gtk4::gio::SimpleAction>,
     pub tx: OnceCell<Sender<Event>>,
     pub icon_size: Rc<Cell<u32>>,
+    pub position: Cell<Anchor>,
+    pub dock_object: RefCell<Option<DockObject>>,
+    pub object_handlers: RefCell<Option<(DockObject, Vec<glib::SignalHandlerId>)>>,
+    pub launch_animation: RefCell<Option<glib::SourceId>>,
+    pub launch_failed_timeout: RefCell<Option<glib::SourceId>>,
+    pub animations: Cell<bool>,
+    pub hover_previews: Cell<bool>,
+    pub hover_timeout: RefCell<Option<glib::SourceId>>,
+    /// thumbnails of the windows shown while the pointer rests on the item
+    pub flyout: OnceCell<gtk4::Popover>,
+    pub flyout_box: OnceCell<gtk4::Box>,
+    pub pointer_on_item: Cell<bool>,
+    pub pointer_on_flyout: Cell<bool>,
+    /// offers to keep a closed app in the dock, see `AppsContainer::offer_keep_in_dock`
+    pub keep_prompt: OnceCell<gtk4::Popover>,
+    pub keep_prompt_label: OnceCell<gtk4::Label>,
 }
 
 #[glib::object_subclass]
@@ -29,6 +54,11 @@ impl ObjectSubclass for DockItem {
     const NAME: &'static str = "DockItem";
     type Type = super::DockItem;
     type ParentType = gtk4::Button;
+
+    fn class_init(klass: &mut Self::Class) {
+        // themes style the items by node name, see `AppsContainer` for the node tree
+        klass.set_css_name("dockitem");
+    }
 }
 
 impl ObjectImpl for DockItem {
@@ -46,6 +76,18 @@ impl ObjectImpl for DockItem {
         });
         SIGNALS.as_ref()
     }
+
+    fn dispose(&self, _obj: &Self::Type) {
+        if let Some(source_id) = self.launch_animation.take() {
+            source_id.remove();
+        }
+        if let Some(source_id) = self.launch_failed_timeout.take() {
+            source_id.remove();
+        }
+        if let Some(source_id) = self.hover_timeout.take() {
+            source_id.remove();
+        }
+    }
 }
 
 impl WidgetImpl for DockItem {}
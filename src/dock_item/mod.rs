@@ -1,23 +1,54 @@
 // SPDX-License-Identifier: MPL-2.0-only
 
+use crate::apps_container::AppsContainer;
 use crate::dock_object::DockObject;
 use crate::dock_popover::DockPopover;
+use crate::fl;
+use crate::generated_entry;
 use crate::utils::BoxedWindowList;
 use crate::utils::Event;
+use crate::utils::send_event;
+use crate::utils::icon_paintable;
+use crate::utils::{daemon_supports, sort_windows, window_preview, Item};
 use cascade::cascade;
 use cosmic_panel_config::config::Anchor;
 use gtk4::glib;
 use gtk4::prelude::*;
 use gtk4::subclass::prelude::*;
+use gio::DesktopAppInfo;
 use gtk4::Box;
+use gtk4::DropTarget;
+use gtk4::IconTheme;
 use gtk4::Image;
+use gtk4::Inhibit;
+use gtk4::PopoverMenu;
+use gtk4::{gdk, gio};
+use gtk4::{EventControllerKey, EventControllerMotion, GestureClick, PropagationPhase};
+use gtk4::{EventControllerScroll, EventControllerScrollFlags};
 use gtk4::Orientation;
+use gtk4::{Button, Label, Overlay, Picture, ProgressBar};
 use gtk4::Popover;
+use gtk4::{Revealer, RevealerTransitionType};
+use gtk4::pango::EllipsizeMode;
 use gtk4::{Align, PositionType};
+use std::time::Duration;
 use tokio::sync::mpsc::Sender;
 
 mod imp;
 
+const MAX_DOTS: usize = 3;
+/// How long items take to grow in and shrink out of the dock.
+pub const REVEAL_DURATION_MS: u32 = 150;
+// labels all have the same width so the overflow can be computed from any item
+const LABEL_WIDTH_CHARS: i32 = 12;
+// how long the pointer rests on a running app before its window thumbnails are shown
+const HOVER_DELAY_MS: u64 = 600;
+// lets the pointer cross the gap between the item and the thumbnails
+const FLYOUT_GRACE_MS: u64 = 200;
+const FLYOUT_PREVIEW_WIDTH: i32 = 128;
+const FLYOUT_PREVIEW_HEIGHT: i32 = 72;
+const MAX_FLYOUT_PREVIEWS: usize = 6;
+
 glib::wrapper! {
     pub struct DockItem(ObjectSubclass<imp::DockItem>)
         @extends gtk4::Button, gtk4::Widget,
@@ -30,11 +61,36 @@ impl DockItem {
 
         let item_box = Box::new(Orientation::Vertical, 0);
         item_box.add_css_class("transparent");
-        cascade! {
-            &self_;
+        item_box.add_css_class("dock-item-box");
+        // badge and progress reported by the app are drawn on top of the icon
+        let badge = cascade! {
+            Label::new(None);
+            ..set_halign(Align::End);
+            ..set_valign(Align::Start);
+            ..set_visible(false);
+            ..add_css_class("dock_badge");
+        };
+        let progress_bar = cascade! {
+            ProgressBar::new();
+            ..set_valign(Align::End);
+            ..set_visible(false);
+            ..add_css_class("dock_progress");
+        };
+        let overlay = cascade! {
+            Overlay::new();
             ..set_child(Some(&item_box));
-            ..add_css_class("dock_item");
+            ..add_overlay(&badge);
+            ..add_overlay(&progress_bar);
         };
+        // new items grow in and removed ones shrink out instead of popping
+        let revealer = cascade! {
+            Revealer::new();
+            ..set_transition_type(RevealerTransitionType::SlideRight);
+            ..set_transition_duration(REVEAL_DURATION_MS);
+            ..set_reveal_child(true);
+            ..set_child(Some(&overlay));
+        };
+        self_.set_child(Some(&revealer));
 
         let image = cascade! {
             Image::new();
@@ -44,19 +100,35 @@ impl DockItem {
             ..add_css_class("dock");
         };
         let dots = cascade! {
-            Box::new(Orientation::Horizontal, 4);
+            Box::new(Orientation::Horizontal, 0);
             ..set_hexpand(true);
             ..set_halign(Align::Center);
             ..set_valign(Align::Center);
             ..add_css_class("transparent");
+            ..add_css_class("dock-indicators");
+        };
+        let label = cascade! {
+            Label::new(None);
+            ..set_ellipsize(EllipsizeMode::End);
+            ..set_width_chars(LABEL_WIDTH_CHARS);
+            ..set_max_width_chars(LABEL_WIDTH_CHARS);
+            ..set_xalign(0.0);
+            ..set_visible(false);
+            ..add_css_class("dock_label");
+        };
+        let icon_box = cascade! {
+            Box::new(Orientation::Horizontal, 4);
+            ..append(&image);
+            ..append(&label);
         };
         // TODO dots inverse color of parent with gsk blend modes?
-        item_box.append(&image);
+        item_box.append(&icon_box);
         item_box.append(&dots);
         let popover = cascade! {
             Popover::new();
             ..set_autohide(true);
             ..add_css_class("dock");
+            ..add_css_class("window-popover");
             ..set_has_arrow(false);
         };
         item_box.append(&popover);
@@ -70,6 +142,10 @@ impl DockItem {
             ..add_css_class("popover_menu");
         };
         popover.set_child(Some(&popover_menu));
+        popover.connect_closed(glib::clone!(@weak popover_menu => move |_| {
+            popover_menu.stop_previews();
+            popover_menu.clear_filter();
+        }));
         popover_menu.connect_local(
             "menu-hide",
             false,
@@ -80,45 +156,322 @@ impl DockItem {
             }),
         );
 
+        // the thumbnails are dismissed by hand, autohide would grab the pointer from the dock
+        let flyout_box = cascade! {
+            Box::new(Orientation::Horizontal, 8);
+            ..add_css_class("dock_flyout");
+        };
+        let flyout = cascade! {
+            Popover::new();
+            ..set_autohide(false);
+            ..set_has_arrow(false);
+            ..add_css_class("dock");
+            ..set_child(Some(&flyout_box));
+        };
+        item_box.append(&flyout);
+        let flyout_motion = EventControllerMotion::new();
+        flyout_motion.connect_enter(glib::clone!(@weak self_ => move |_, _, _| {
+            imp::DockItem::from_instance(&self_).pointer_on_flyout.set(true);
+        }));
+        flyout_motion.connect_leave(glib::clone!(@weak self_ => move |_| {
+            imp::DockItem::from_instance(&self_).pointer_on_flyout.set(false);
+            self_.queue_flyout_dismiss();
+        }));
+        flyout.add_controller(&flyout_motion);
+
+        let motion = EventControllerMotion::new();
+        motion.connect_enter(glib::clone!(@weak self_ => move |_, _, _| {
+            imp::DockItem::from_instance(&self_).pointer_on_item.set(true);
+            self_.schedule_flyout();
+        }));
+        motion.connect_leave(glib::clone!(@weak self_ => move |_| {
+            let imp = imp::DockItem::from_instance(&self_);
+            imp.pointer_on_item.set(false);
+            self_.cancel_flyout();
+            self_.queue_flyout_dismiss();
+        }));
+        self_.add_controller(&motion);
+        // clicks show the window list or focus a window, either way the thumbnails are in the way
+        let hover_click = GestureClick::builder().button(0).build();
+        hover_click.set_propagation_phase(PropagationPhase::Capture);
+        hover_click.connect_pressed(glib::clone!(@weak self_ => move |_, _, _, _| {
+            self_.hide_flyout();
+        }));
+        self_.add_controller(&hover_click);
+        popover.connect_show(glib::clone!(@weak self_ => move |_| {
+            self_.hide_flyout();
+        }));
+
+        let keep_prompt_label = cascade! {
+            Label::new(None);
+            ..set_wrap(true);
+            ..set_max_width_chars(24);
+            ..set_xalign(0.0);
+        };
+        let keep_button = cascade! {
+            Button::with_label(&fl!("pin"));
+            ..add_css_class("suggested-action");
+        };
+        keep_button.connect_clicked(glib::clone!(@weak self_ => move |_| {
+            self_.answer_keep_prompt(true);
+        }));
+        let dismiss_button = Button::with_label(&fl!("dismiss"));
+        dismiss_button.connect_clicked(glib::clone!(@weak self_ => move |_| {
+            self_.answer_keep_prompt(false);
+        }));
+        let keep_prompt_buttons = cascade! {
+            Box::new(Orientation::Horizontal, 8);
+            ..set_halign(Align::End);
+            ..append(&dismiss_button);
+            ..append(&keep_button);
+        };
+        let keep_prompt_box = cascade! {
+            Box::new(Orientation::Vertical, 8);
+            ..add_css_class("dock_keep_prompt");
+            ..append(&keep_prompt_label);
+            ..append(&keep_prompt_buttons);
+        };
+        // like the thumbnails the prompt doesn't grab the pointer from whatever the user does next
+        let keep_prompt = cascade! {
+            Popover::new();
+            ..set_autohide(false);
+            ..set_has_arrow(false);
+            ..add_css_class("dock");
+            ..set_child(Some(&keep_prompt_box));
+        };
+        item_box.append(&keep_prompt);
+        // closing it otherwise, e.g. by opening another popover of the dock, dismisses it
+        keep_prompt.connect_closed(glib::clone!(@weak self_ => move |_| {
+            self_.answer_keep_prompt(false);
+        }));
+
+        let context_menu = cascade! {
+            PopoverMenu::from_model(None::<&gio::MenuModel>);
+            ..set_has_arrow(false);
+            ..add_css_class("dock");
+        };
+        item_box.append(&context_menu);
+        self_.insert_action_group("item", Some(&self_.context_menu_actions(tx.clone())));
+
+        let context_click = GestureClick::builder().button(3).build();
+        context_click.connect_released(glib::clone!(@weak self_ => move |_, _, _, _| {
+            self_.popup_context_menu();
+        }));
+        self_.add_controller(&context_click);
+
+        let key_controller = EventControllerKey::new();
+        key_controller.connect_key_pressed(
+            glib::clone!(@weak self_ => @default-return Inhibit(false), move |_, key, _, modifier| {
+                if key == gdk::Key::Menu || (key == gdk::Key::F10 && modifier.contains(gdk::ModifierType::SHIFT_MASK)) {
+                    self_.popup_context_menu();
+                    Inhibit(true)
+                } else {
+                    Inhibit(false)
+                }
+            }),
+        );
+        self_.add_controller(&key_controller);
+
+        let scroll_controller = EventControllerScroll::new(
+            EventControllerScrollFlags::BOTH_AXES | EventControllerScrollFlags::DISCRETE,
+        );
+        scroll_controller.connect_scroll(
+            glib::clone!(@weak self_ => @default-return Inhibit(false), move |_, dx, dy| {
+                let imp = imp::DockItem::from_instance(&self_);
+                // scroll along the dock first, then fall back to the wheel axis
+                let delta = match imp.position.get() {
+                    Anchor::Left | Anchor::Right if dy != 0.0 => dy,
+                    Anchor::Top | Anchor::Bottom if dx != 0.0 => dx,
+                    _ => dx + dy,
+                };
+                if delta == 0.0 {
+                    return Inhibit(false);
+                }
+                self_.cycle_windows(delta > 0.0);
+                Inhibit(true)
+            }),
+        );
+        self_.add_controller(&scroll_controller);
+        self_.setup_file_drop_target();
+
         let imp = imp::DockItem::from_instance(&self_);
         imp.icon_size.set(icon_size);
         imp.image.replace(Some(image));
         imp.dots.replace(dots);
+        imp.label.set(label).unwrap();
+        imp.icon_box.set(icon_box).unwrap();
         imp.item_box.replace(item_box);
         imp.popover.replace(popover);
+        imp.context_menu.replace(Some(context_menu));
         imp.popover_menu.replace(Some(popover_menu));
         imp.tx.set(tx).unwrap();
+        imp.badge.set(badge).unwrap();
+        imp.progress_bar.set(progress_bar).unwrap();
+        imp.revealer.set(revealer).unwrap();
+        imp.flyout.set(flyout).unwrap();
+        imp.flyout_box.set(flyout_box).unwrap();
+        imp.keep_prompt.set(keep_prompt).unwrap();
+        imp.keep_prompt_label.set(keep_prompt_label).unwrap();
+        imp.animations.set(true);
+        self_.track_popover(&*imp.popover.borrow(), true);
+        self_.track_popover(imp.context_menu.borrow().as_ref().unwrap(), true);
+        self_.track_popover(imp.flyout.get().unwrap(), false);
+        self_.track_popover(imp.keep_prompt.get().unwrap(), false);
+
+        if let Some(display) = gdk::Display::default() {
+            IconTheme::for_display(&display).connect_changed(
+                glib::clone!(@weak self_ => move |_| {
+                    self_.update_image();
+                }),
+            );
+        }
+        // e.g. the dock moved to an output with another scale
+        self_.connect_scale_factor_notify(|self_| self_.update_image());
         self_
     }
 
     // refactor to emit event for removing the item?
     pub fn set_dock_object(&self, dock_object: &DockObject) {
         let imp = imp::DockItem::from_instance(self);
+        // the thumbnails belong to the app the item showed before
+        self.hide_flyout();
+        imp.dock_object.replace(Some(dock_object.clone()));
         let image = cascade! {
-            dock_object.get_image();
+            Image::new();
             ..set_hexpand(true);
             ..set_halign(Align::Center);
             ..set_pixel_size(imp.icon_size.get().try_into().unwrap());
-            ..set_tooltip_text(dock_object.get_name().as_deref());
         };
         let old_image = imp.image.replace(None);
         if let Some(old_image) = old_image {
-            imp.item_box.borrow().remove(&old_image);
-            imp.item_box.borrow().prepend(&image);
+            imp.icon_box.get().unwrap().remove(&old_image);
+            imp.icon_box.get().unwrap().prepend(&image);
             imp.image.replace(Some(image));
         }
-        let active = dock_object.property::<BoxedWindowList>("active");
-        let dots = imp.dots.borrow();
-        while let Some(c) = dots.first_child() {
-            dots.remove(&c);
+        self.update_image();
+        self.update_dots(&dock_object.property::<BoxedWindowList>("active"));
+        self.set_launching(dock_object.property::<bool>("launching"));
+        self.set_focused(dock_object.property::<bool>("focused"));
+        self.set_missing(dock_object.property::<bool>("missing"));
+        self.update_launcher_entry(dock_object);
+        self.update_tooltip(dock_object);
+        self.update_label(dock_object);
+        self.update_accessible(dock_object);
+        if dock_object.mark_shown() {
+            self.set_revealed(false, false);
+            // reveal once the collapsed item has been allocated so the transition is visible
+            glib::idle_add_local_once(glib::clone!(@weak self as self_ => move || {
+                let imp = imp::DockItem::from_instance(&self_);
+                let removing = imp
+                    .dock_object
+                    .borrow()
+                    .as_ref()
+                    .map(|o| o.property::<bool>("removing"))
+                    .unwrap_or_default();
+                self_.set_revealed(!removing, true);
+            }));
+        } else {
+            self.set_revealed(!dock_object.property::<bool>("removing"), false);
         }
-        for _ in active.0 {
-            dots.append(&cascade! {
-                Box::new(Orientation::Horizontal, 0);
-                ..set_halign(Align::Center);
-                ..set_valign(Align::Center);
-                ..add_css_class("dock_dots");
-            });
+        self.update_keep_prompt(dock_object);
+        let handlers = vec![
+            dock_object.connect_notify_local(
+                Some("keep-prompt"),
+                glib::clone!(@weak self as self_ => move |dock_object, _| {
+                    self_.update_keep_prompt(dock_object);
+                }),
+            ),
+            dock_object.connect_notify_local(
+                Some("removing"),
+                glib::clone!(@weak self as self_ => move |dock_object, _| {
+                    self_.set_revealed(!dock_object.property::<bool>("removing"), true);
+                }),
+            ),
+            dock_object.connect_notify_local(
+                Some("count"),
+                glib::clone!(@weak self as self_ => move |dock_object, _| {
+                    self_.update_launcher_entry(dock_object);
+                }),
+            ),
+            dock_object.connect_notify_local(
+                Some("progress"),
+                glib::clone!(@weak self as self_ => move |dock_object, _| {
+                    self_.update_launcher_entry(dock_object);
+                }),
+            ),
+            dock_object.connect_notify_local(
+                Some("urgent"),
+                glib::clone!(@weak self as self_ => move |dock_object, _| {
+                    self_.update_attention(dock_object);
+                    self_.update_accessible(dock_object);
+                }),
+            ),
+            // the icon may come from a desktop file installed after the item was created
+            dock_object.connect_notify_local(
+                Some("appinfo"),
+                glib::clone!(@weak self as self_ => move |dock_object, _| {
+                    self_.update_image();
+                    self_.update_tooltip(dock_object);
+                    self_.update_label(dock_object);
+                    self_.update_accessible(dock_object);
+                }),
+            ),
+            // the trash icon changes when it is emptied or filled
+            dock_object.connect_notify_local(
+                Some("location"),
+                glib::clone!(@weak self as self_ => move |dock_object, _| {
+                    self_.update_image();
+                    self_.update_tooltip(dock_object);
+                    self_.update_label(dock_object);
+                    self_.update_accessible(dock_object);
+                }),
+            ),
+            dock_object.connect_notify_local(
+                Some("missing"),
+                glib::clone!(@weak self as self_ => move |dock_object, _| {
+                    self_.set_missing(dock_object.property::<bool>("missing"));
+                }),
+            ),
+            dock_object.connect_notify_local(
+                Some("focused"),
+                glib::clone!(@weak self as self_ => move |dock_object, _| {
+                    self_.set_focused(dock_object.property::<bool>("focused"));
+                }),
+            ),
+            dock_object.connect_notify_local(
+                Some("active"),
+                glib::clone!(@weak self as self_ => move |dock_object, _| {
+                    self_.update_dots(&dock_object.property::<BoxedWindowList>("active"));
+                    self_.update_attention(dock_object);
+                    self_.update_tooltip(dock_object);
+                    // windows without a desktop file are labeled with their title
+                    self_.update_label(dock_object);
+                    self_.update_accessible(dock_object);
+                    if dock_object.get_id().is_none() {
+                        self_.update_image();
+                    }
+                }),
+            ),
+            dock_object.connect_notify_local(
+                Some("launching"),
+                glib::clone!(@weak self as self_ => move |dock_object, _| {
+                    self_.set_launching(dock_object.property::<bool>("launching"));
+                    self_.update_accessible(dock_object);
+                }),
+            ),
+            dock_object.connect_local(
+                "launch-failed",
+                false,
+                glib::clone!(@weak self as self_ => @default-return None, move |_| {
+                    self_.show_launch_failed();
+                    None
+                }),
+            ),
+        ];
+        if let Some((old_object, old_handlers)) = imp.object_handlers.replace(Some((dock_object.clone(), handlers))) {
+            for handler in old_handlers {
+                old_object.disconnect(handler);
+            }
         }
 
         let popover = dock_object.property::<bool>("popover");
@@ -131,42 +484,772 @@ impl DockItem {
         }
     }
 
-    pub fn set_position(&self, position: Anchor) {
+    fn set_revealed(&self, revealed: bool, animate: bool) {
+        let imp = imp::DockItem::from_instance(self);
+        let revealer = imp.revealer.get().unwrap();
+        let transition = revealer.transition_type();
+        if !animate || !imp.animations.get() {
+            revealer.set_transition_type(RevealerTransitionType::None);
+        }
+        revealer.set_reveal_child(revealed);
+        revealer.set_transition_type(transition);
+        if revealed {
+            self.remove_css_class("hidden_item");
+        } else {
+            self.add_css_class("hidden_item");
+        }
+    }
+
+    fn update_dots(&self, active: &BoxedWindowList) {
         let imp = imp::DockItem::from_instance(self);
-        let item_box = imp.item_box.borrow();
         let dots = imp.dots.borrow();
-        if let Some(image) = imp.image.borrow().as_ref() {
-            match position {
-                Anchor::Left => {
-                    item_box.set_orientation(Orientation::Horizontal);
-                    dots.set_orientation(Orientation::Vertical);
-                    dots.set_margin_bottom(4);
-                    dots.set_margin_top(4);
-                    item_box.reorder_child_after(&image.clone(), Some(&dots.clone()));
+        while let Some(c) = dots.first_child() {
+            dots.remove(&c);
+        }
+        for window in active.0.iter().take(MAX_DOTS) {
+            let dot = cascade! {
+                Box::new(Orientation::Horizontal, 0);
+                ..set_halign(Align::Center);
+                ..set_valign(Align::Center);
+                ..add_css_class("dock-indicator");
+            };
+            if window.focused {
+                dot.add_css_class("focused");
+            }
+            if window.minimized {
+                dot.add_css_class("minimized");
+            }
+            dots.append(&dot);
+        }
+        if active.all_minimized() {
+            self.add_css_class("all-minimized");
+        } else {
+            self.remove_css_class("all-minimized");
+        }
+    }
+
+    fn update_launcher_entry(&self, dock_object: &DockObject) {
+        let imp = imp::DockItem::from_instance(self);
+        let badge = imp.badge.get().unwrap();
+        let count = dock_object.property::<i64>("count");
+        badge.set_visible(count > 0);
+        badge.set_label(&if count > 99 {
+            "99+".to_string()
+        } else {
+            count.to_string()
+        });
+        let progress_bar = imp.progress_bar.get().unwrap();
+        let progress = dock_object.property::<f64>("progress");
+        progress_bar.set_visible(progress >= 0.0);
+        progress_bar.set_fraction(progress.max(0.0));
+        self.update_attention(dock_object);
+    }
+
+    // windows with the urgency hint and apps asking through their launcher entry both get attention
+    fn update_attention(&self, dock_object: &DockObject) {
+        let active = dock_object.property::<BoxedWindowList>("active");
+        if active.urgent().is_some() || dock_object.property::<bool>("urgent") {
+            self.add_css_class("attention");
+        } else {
+            self.remove_css_class("attention");
+        }
+    }
+
+    // the icon is looked up at the size in device pixels, the image is sized in logical pixels
+    fn update_image(&self) {
+        let imp = imp::DockItem::from_instance(self);
+        if let (Some(image), Some(dock_object)) =
+            (imp.image.borrow().as_ref(), imp.dock_object.borrow().as_ref())
+        {
+            let size = imp.icon_size.get() as i32;
+            image.set_paintable(Some(&icon_paintable(&dock_object.get_icon(), size, self)));
+        }
+    }
+
+    // the app name and how many windows it has, or the window title for windows without a desktop file
+    fn update_tooltip(&self, dock_object: &DockObject) {
+        let active = dock_object.property::<BoxedWindowList>("active");
+        let name = match dock_object.display_name() {
+            Some(name) => name,
+            None => {
+                self.set_tooltip_markup(None);
+                return;
+            }
+        };
+        let mut markup = glib::markup_escape_text(&name).to_string();
+        let count = active.0.len();
+        if count > 0 {
+            let windows = fl!("window-count", count = count);
+            markup.push_str(" · ");
+            markup.push_str(&glib::markup_escape_text(&windows));
+        }
+        self.set_tooltip_markup(Some(&markup));
+    }
+
+    fn update_accessible(&self, dock_object: &DockObject) {
+        let active = dock_object.property::<BoxedWindowList>("active");
+        let (label, description) = accessible_text(
+            &dock_object.display_name().unwrap_or_default(),
+            dock_object.property::<bool>("saved"),
+            active.0.len(),
+            dock_object.property::<bool>("launching"),
+            active.urgent().is_some() || dock_object.property::<bool>("urgent"),
+        );
+        self.update_property(&[
+            (gtk4::AccessibleProperty::Label, &label),
+            (gtk4::AccessibleProperty::Description, &description),
+        ]);
+    }
+
+    fn update_label(&self, dock_object: &DockObject) {
+        let imp = imp::DockItem::from_instance(self);
+        imp.label
+            .get()
+            .unwrap()
+            .set_text(&dock_object.display_name().unwrap_or_default());
+    }
+
+    /// Shows the app name next to the icon.
+    pub fn set_show_label(&self, show_label: bool) {
+        let imp = imp::DockItem::from_instance(self);
+        imp.label.get().unwrap().set_visible(show_label);
+    }
+
+    /// Reveals and hides the item instantly and stops pulsing the icon while the app launches
+    /// if animations are off.
+    pub fn set_animations_enabled(&self, enabled: bool) {
+        let imp = imp::DockItem::from_instance(self);
+        if imp.animations.replace(enabled) == enabled {
+            return;
+        }
+        if let Some(dock_object) = self.dock_object() {
+            self.set_launching(dock_object.property::<bool>("launching"));
+        }
+    }
+
+    fn set_missing(&self, missing: bool) {
+        if missing {
+            self.add_css_class("missing");
+        } else {
+            self.remove_css_class("missing");
+        }
+    }
+
+    fn set_focused(&self, focused: bool) {
+        if focused {
+            self.add_css_class("active");
+        } else {
+            self.remove_css_class("active");
+        }
+    }
+
+    fn context_menu_actions(&self, tx: Sender<Event>) -> gio::SimpleActionGroup {
+        let actions = gio::SimpleActionGroup::new();
+        let send = move |event: Event| send_event(&tx, event);
+
+        let favorite = gio::SimpleAction::new("favorite", None);
+        let send_favorite = send.clone();
+        favorite.connect_activate(glib::clone!(@weak self as self_ => move |_, _| {
+            if let Some(dock_object) = self_.dock_object() {
+                if let Some(id) = dock_object.get_id() {
+                    send_favorite(Event::Favorite {
+                        id,
+                        favorite: !dock_object.property::<bool>("saved"),
+                        index: None,
+                    });
+                }
+            }
+        }));
+        actions.add_action(&favorite);
+        let imp = imp::DockItem::from_instance(self);
+        imp.favorite_action.set(favorite).unwrap();
+
+        // windows without a desktop entry get one written for them
+        let pin_window = gio::SimpleAction::new("pin-window", None);
+        let send_pin_window = send.clone();
+        pin_window.connect_activate(glib::clone!(@weak self as self_ => move |_, _| {
+            if let Some(window) = self_.dock_object().and_then(|o| o.pinnable_window()) {
+                send_pin_window(Event::PinWindow(window));
+            }
+        }));
+        actions.add_action(&pin_window);
+
+        let delete_generated = gio::SimpleAction::new("delete-generated", None);
+        let send_delete_generated = send.clone();
+        delete_generated.connect_activate(glib::clone!(@weak self as self_ => move |_, _| {
+            if let Some(id) = self_.dock_object().and_then(|o| o.get_id()) {
+                send_delete_generated(Event::DeleteGenerated(id));
+            }
+        }));
+        actions.add_action(&delete_generated);
+
+        let launch = gio::SimpleAction::new("launch", None);
+        let send_launch = send.clone();
+        launch.connect_activate(glib::clone!(@weak self as self_ => move |_, _| {
+            if let Some(id) = self_.dock_object().and_then(|o| o.get_id()) {
+                send_launch(Event::Launch(id));
+            }
+        }));
+        actions.add_action(&launch);
+
+        let quit_all = gio::SimpleAction::new("quit-all", None);
+        let send_quit_all = send.clone();
+        quit_all.connect_activate(glib::clone!(@weak self as self_ => move |_, _| {
+            if let Some(dock_object) = self_.dock_object() {
+                let active = dock_object.property::<BoxedWindowList>("active").0;
+                send_quit_all(Event::CloseAll(active.into_iter().map(|w| w.entity).collect()));
+            }
+        }));
+        actions.add_action(&quit_all);
+
+        let desktop_action = gio::SimpleAction::new("desktop-action", Some(glib::VariantTy::STRING));
+        desktop_action.connect_activate(glib::clone!(@weak self as self_ => move |_, action| {
+            let app_info = self_
+                .dock_object()
+                .and_then(|o| o.property::<Option<DesktopAppInfo>>("appinfo"));
+            if let (Some(app_info), Some(action)) = (app_info, action.and_then(|a| a.get::<String>())) {
+                let context = gdk::Display::default().unwrap().app_launch_context();
+                app_info.launch_action(&action, Some(&context));
+            }
+        }));
+        actions.add_action(&desktop_action);
+
+        let empty_trash = gio::SimpleAction::new("empty-trash", None);
+        empty_trash.connect_activate(|_, _| crate::system_items::empty_trash());
+        actions.add_action(&empty_trash);
+
+        let eject = gio::SimpleAction::new("eject", None);
+        eject.connect_activate(glib::clone!(@weak self as self_ => move |_, _| {
+            if let Some(mount) = self_.dock_object().and_then(|o| o.location()).and_then(|l| l.mount) {
+                crate::system_items::eject(&mount);
+            }
+        }));
+        actions.add_action(&eject);
+
+        let clear_badge = gio::SimpleAction::new("clear-badge", None);
+        let send_clear_badge = send.clone();
+        clear_badge.connect_activate(glib::clone!(@weak self as self_ => move |_, _| {
+            if let Some(id) = self_.dock_object().and_then(|o| o.get_id()) {
+                send_clear_badge(Event::ClearNotifications(id));
+            }
+        }));
+        actions.add_action(&clear_badge);
+
+        let clear_recent = gio::SimpleAction::new("clear-recent", None);
+        clear_recent.connect_activate(move |_, _| send(Event::ClearRecent));
+        actions.add_action(&clear_recent);
+
+        actions
+    }
+
+    // files dropped on the icon are opened with the app
+    fn setup_file_drop_target(&self) {
+        let drop_target = DropTarget::builder()
+            .preload(true)
+            .actions(gdk::DragAction::COPY)
+            .formats(&gdk::ContentFormats::for_type(gdk::FileList::static_type()))
+            .build();
+        // apps that can't open files leave the drop to the dock, which pins desktop files
+        drop_target.connect_accept(
+            glib::clone!(@weak self as self_ => @default-return false, move |_, _| {
+                self_.file_drop_app_info().is_some()
+            }),
+        );
+        drop_target.connect_motion(
+            glib::clone!(@weak self as self_ => @default-return gdk::DragAction::empty(), move |drop_target, _, _| {
+                let files = drop_target
+                    .value()
+                    .and_then(|value| value.get::<gdk::FileList>().ok())
+                    .map(|files| files.files())
+                    .unwrap_or_default();
+                // an empty action shows the "no drop" cursor
+                let action = if self_.can_open(&files) {
+                    gdk::DragAction::COPY
+                } else {
+                    gdk::DragAction::empty()
+                };
+                self_.set_drop_highlight(!action.is_empty());
+                action
+            }),
+        );
+        drop_target.connect_leave(glib::clone!(@weak self as self_ => move |_| {
+            self_.set_drop_highlight(false);
+        }));
+        drop_target.connect_drop(
+            glib::clone!(@weak self as self_ => @default-return false, move |_, value, _, _| {
+                self_.set_drop_highlight(false);
+                let files = match value.get::<gdk::FileList>() {
+                    Ok(files) => files.files(),
+                    Err(_) => return false,
+                };
+                if !self_.can_open(&files) {
+                    return false;
                 }
-                Anchor::Right => {
-                    item_box.set_orientation(Orientation::Horizontal);
-                    dots.set_orientation(Orientation::Vertical);
-                    dots.set_margin_bottom(4);
-                    dots.set_margin_top(4);
-                    item_box.reorder_child_after(&dots.clone(), Some(&image.clone()));
+                self_.open_files(&files);
+                true
+            }),
+        );
+        self.add_controller(&drop_target);
+    }
+
+    fn file_drop_app_info(&self) -> Option<DesktopAppInfo> {
+        let dock_object = self.dock_object()?;
+        dock_object
+            .property::<Option<DesktopAppInfo>>("appinfo")
+            .filter(|_| dock_object.can_launch())
+            .filter(|app_info| app_info.supports_files() || app_info.supports_uris())
+    }
+
+    // every file has to match one of the mime types listed in the desktop entry
+    fn can_open(&self, files: &[gio::File]) -> bool {
+        let app_info = match self.file_drop_app_info() {
+            Some(app_info) => app_info,
+            None => return false,
+        };
+        let supported_types = app_info.supported_types();
+        !files.is_empty()
+            && files.iter().all(|file| {
+                if !app_info.supports_uris() && file.path().is_none() {
+                    return false;
                 }
-                Anchor::Top => {
-                    item_box.set_orientation(Orientation::Vertical);
-                    dots.set_orientation(Orientation::Horizontal);
-                    dots.set_margin_start(4);
-                    dots.set_margin_end(4);
-                    item_box.reorder_child_after(&image.clone(), Some(&dots.clone()));
+                let (content_type, _) = gio::content_type_guess(file.basename(), &[]);
+                supported_types
+                    .iter()
+                    .any(|supported| gio::content_type_is_a(&content_type, supported))
+            })
+    }
+
+    fn open_files(&self, files: &[gio::File]) {
+        let (dock_object, app_info) = match (self.dock_object(), self.file_drop_app_info()) {
+            (Some(dock_object), Some(app_info)) => (dock_object, app_info),
+            _ => return,
+        };
+        let uris: Vec<String> = files.iter().map(|file| file.uri().to_string()).collect();
+        let uris: Vec<&str> = uris.iter().map(String::as_str).collect();
+        let context = gdk::Display::default().map(|display| display.app_launch_context());
+        dock_object.set_launching(true);
+        if let Err(err) = app_info.launch_uris(&uris, context.as_ref()) {
+            log::warn!("Failed to open files with {}: {}", app_info.name(), err);
+            dock_object.set_launching(false);
+            dock_object.emit_launch_failed();
+        }
+    }
+
+    fn set_drop_highlight(&self, highlight: bool) {
+        if highlight {
+            self.add_css_class("drop_target");
+        } else {
+            self.remove_css_class("drop_target");
+        }
+    }
+
+    pub(crate) fn dock_object(&self) -> Option<DockObject> {
+        let imp = imp::DockItem::from_instance(self);
+        imp.dock_object.borrow().clone()
+    }
+
+    fn apps_container(&self) -> Option<AppsContainer> {
+        self.ancestor(AppsContainer::static_type())
+            .and_then(|container| container.downcast::<AppsContainer>().ok())
+    }
+
+    // the container keeps one popover of the dock open, closing a menu gives the focus back
+    // to the item, e.g. after escape
+    fn track_popover(&self, popover: &impl IsA<Popover>, return_focus: bool) {
+        popover.connect_show(glib::clone!(@weak self as self_ => move |popover| {
+            if let Some(apps_container) = self_.apps_container() {
+                apps_container.popover_shown(popover, &self_);
+            }
+        }));
+        popover.connect_closed(glib::clone!(@weak self as self_ => move |popover| {
+            let was_open = self_
+                .apps_container()
+                .map(|apps_container| apps_container.popover_hidden(popover))
+                .unwrap_or_default();
+            let window_active = self_
+                .root()
+                .and_then(|root| root.downcast::<gtk4::Window>().ok())
+                .map(|window| window.is_active())
+                .unwrap_or_default();
+            if was_open && return_focus && window_active {
+                self_.grab_focus();
+            }
+        }));
+    }
+
+    // the menu is built on every popup so it reflects the current state of the object
+    fn popup_context_menu(&self) {
+        let imp = imp::DockItem::from_instance(self);
+        let dock_object = match self.dock_object() {
+            Some(dock_object) => dock_object,
+            None => return,
+        };
+        let app_info = dock_object.property::<Option<DesktopAppInfo>>("appinfo");
+        let active = dock_object.property::<BoxedWindowList>("active");
+        let menu = gio::Menu::new();
+        // menu items can't have tooltips, the popover explains why hidden apps can't be pinned
+        imp.favorite_action
+            .get()
+            .unwrap()
+            .set_enabled(dock_object.can_pin());
+
+        if let Some(app_info) = app_info.as_ref() {
+            let actions_section = gio::Menu::new();
+            for action in app_info.list_actions() {
+                let item = gio::MenuItem::new(Some(&app_info.action_name(&action)), None);
+                item.set_action_and_target_value(
+                    Some("item.desktop-action"),
+                    Some(&action.to_variant()),
+                );
+                actions_section.append_item(&item);
+            }
+            menu.append_section(None, &actions_section);
+        }
+
+        let section = gio::Menu::new();
+        if let Some(location) = dock_object.location() {
+            if location.mount.is_some() {
+                section.append(Some(&fl!("eject")), Some("item.eject"));
+            } else if crate::system_items::can_empty_trash() {
+                section.append(Some(&fl!("empty-trash")), Some("item.empty-trash"));
+            }
+        }
+        if app_info.is_some() || dock_object.custom_launcher().is_some() {
+            if dock_object.can_launch() {
+                section.append(Some(&fl!("launch-new-window")), Some("item.launch"));
+            }
+            section.append(
+                Some(&if dock_object.property::<bool>("saved") {
+                    fl!("unpin")
+                } else {
+                    fl!("pin")
+                }),
+                Some("item.favorite"),
+            );
+            let generated = dock_object
+                .get_id()
+                .map(|id| generated_entry::is_generated(&id))
+                .unwrap_or_default();
+            if generated && dock_object.property::<bool>("saved") {
+                section.append(Some(&fl!("unpin-delete")), Some("item.delete-generated"));
+            }
+        } else if dock_object.pinnable_window().is_some() {
+            section.append(Some(&fl!("pin-anyway")), Some("item.pin-window"));
+        }
+        if dock_object.property::<i64>("count") > 0 {
+            section.append(Some(&fl!("clear-badge")), Some("item.clear-badge"));
+        }
+        if !active.0.is_empty() {
+            section.append(Some(&fl!("quit-all")), Some("item.quit-all"));
+        }
+        if dock_object.is_recent() {
+            section.append(Some(&fl!("clear-recent")), Some("item.clear-recent"));
+        }
+        menu.append_section(None, &section);
+
+        self.hide_flyout();
+        if let Some(context_menu) = imp.context_menu.borrow().as_ref() {
+            context_menu.set_position(imp.popover.borrow().position());
+            context_menu.set_menu_model(Some(&menu));
+            context_menu.popup();
+        }
+    }
+
+    // pulse the icon while the app is starting up, it is only dimmed without animations
+    fn set_launching(&self, launching: bool) {
+        let imp = imp::DockItem::from_instance(self);
+        let pulse = launching && imp.animations.get();
+        if launching == self.has_css_class("launching")
+            && pulse == imp.launch_animation.borrow().is_some()
+        {
+            return;
+        }
+        if let Some(source_id) = imp.launch_animation.replace(None) {
+            source_id.remove();
+        }
+        if launching {
+            self.add_css_class("launching");
+        } else {
+            self.remove_css_class("launching");
+        }
+        if pulse {
+            let source_id = glib::timeout_add_local(
+                Duration::from_millis(500),
+                glib::clone!(@weak self as self_ => @default-return glib::Continue(false), move || {
+                    if self_.has_css_class("launching") {
+                        self_.remove_css_class("launching");
+                    } else {
+                        self_.add_css_class("launching");
+                    }
+                    glib::Continue(true)
+                }),
+            );
+            imp.launch_animation.replace(Some(source_id));
+        }
+    }
+
+    fn update_keep_prompt(&self, dock_object: &DockObject) {
+        let imp = imp::DockItem::from_instance(self);
+        let keep_prompt = imp.keep_prompt.get().unwrap();
+        if !dock_object.property::<bool>("keep-prompt") {
+            keep_prompt.popdown();
+            return;
+        }
+        let app = dock_object.display_name().unwrap_or_default();
+        imp.keep_prompt_label
+            .get()
+            .unwrap()
+            .set_text(&fl!("keep-in-dock", app = app));
+        self.hide_flyout();
+        keep_prompt.set_position(imp.popover.borrow().position());
+        keep_prompt.popup();
+    }
+
+    // answers for the object the item shows now, which has no prompt if the item was rebound
+    fn answer_keep_prompt(&self, keep: bool) {
+        let apps_container = self.apps_container();
+        if let (Some(apps_container), Some(dock_object)) = (apps_container, self.dock_object()) {
+            apps_container.answer_keep_prompt(&dock_object, keep);
+        }
+    }
+
+    // another failure while the class is set shows it for the full time again
+    fn show_launch_failed(&self) {
+        let imp = imp::DockItem::from_instance(self);
+        if let Some(source_id) = imp.launch_failed_timeout.take() {
+            source_id.remove();
+        }
+        self.add_css_class("launch_failed");
+        let source_id = glib::timeout_add_local_once(
+            Duration::from_secs(2),
+            glib::clone!(@weak self as self_ => move || {
+                let imp = imp::DockItem::from_instance(&self_);
+                imp.launch_failed_timeout.replace(None);
+                self_.remove_css_class("launch_failed");
+            }),
+        );
+        imp.launch_failed_timeout.replace(Some(source_id));
+    }
+
+    // focus the next or previous window of the app, launching it if there are none
+    fn cycle_windows(&self, forward: bool) {
+        let imp = imp::DockItem::from_instance(self);
+        let dock_object = match imp.dock_object.borrow().as_ref() {
+            Some(dock_object) => dock_object.clone(),
+            None => return,
+        };
+        let event = match (dock_object.next_window(forward), dock_object.get_id()) {
+            (Some(entity), _) => {
+                dock_object.set_last_activated(entity);
+                Event::Activate(entity)
+            }
+            (None, Some(id)) => Event::Launch(id),
+            (None, None) => return,
+        };
+        send_event(imp.tx.get().unwrap(), event);
+    }
+
+    /// Scales the icon and the popover offset, the spacing around the icon is styled per size
+    /// tier.
+    pub fn set_icon_size(&self, icon_size: u32) {
+        let imp = imp::DockItem::from_instance(self);
+        if imp.icon_size.replace(icon_size) == icon_size {
+            return;
+        }
+        if let Some(image) = imp.image.borrow().as_ref() {
+            image.set_pixel_size(icon_size.try_into().unwrap());
+        }
+        self.update_image();
+        let spacing = (icon_size / 8) as i32;
+        imp.popover.borrow().set_offset(0, spacing);
+        imp.flyout.get().unwrap().set_offset(0, spacing);
+    }
+
+    /// Shows thumbnails of the windows when the pointer rests on the item.
+    pub fn set_hover_previews(&self, hover_previews: bool) {
+        let imp = imp::DockItem::from_instance(self);
+        imp.hover_previews.set(hover_previews);
+        if !hover_previews {
+            self.hide_flyout();
+        }
+    }
+
+    fn schedule_flyout(&self) {
+        let imp = imp::DockItem::from_instance(self);
+        self.cancel_flyout();
+        if !imp.hover_previews.get() {
+            return;
+        }
+        let source_id = glib::timeout_add_local_once(
+            Duration::from_millis(HOVER_DELAY_MS),
+            glib::clone!(@weak self as self_ => move || {
+                let imp = imp::DockItem::from_instance(&self_);
+                imp.hover_timeout.replace(None);
+                self_.show_flyout();
+            }),
+        );
+        imp.hover_timeout.replace(Some(source_id));
+    }
+
+    fn cancel_flyout(&self) {
+        let imp = imp::DockItem::from_instance(self);
+        if let Some(source_id) = imp.hover_timeout.take() {
+            source_id.remove();
+        }
+    }
+
+    // the thumbnails stay while the pointer is on the item or on them
+    fn queue_flyout_dismiss(&self) {
+        glib::timeout_add_local_once(
+            Duration::from_millis(FLYOUT_GRACE_MS),
+            glib::clone!(@weak self as self_ => move || {
+                let imp = imp::DockItem::from_instance(&self_);
+                if !imp.pointer_on_item.get() && !imp.pointer_on_flyout.get() {
+                    self_.hide_flyout();
                 }
-                Anchor::Bottom => {
-                    item_box.set_orientation(Orientation::Vertical);
-                    dots.set_orientation(Orientation::Horizontal);
-                    dots.set_margin_start(4);
-                    dots.set_margin_end(4);
-                    item_box.reorder_child_after(&dots.clone(), Some(&image.clone()));
+            }),
+        );
+    }
+
+    fn hide_flyout(&self) {
+        let imp = imp::DockItem::from_instance(self);
+        self.cancel_flyout();
+        if let Some(flyout) = imp.flyout.get() {
+            flyout.popdown();
+        }
+    }
+
+    // never shown over the window list or the context menu
+    fn show_flyout(&self) {
+        let imp = imp::DockItem::from_instance(self);
+        let dock_object = match self.dock_object() {
+            Some(dock_object) => dock_object,
+            None => return,
+        };
+        let active = dock_object.property::<BoxedWindowList>("active");
+        let menu_open = imp
+            .context_menu
+            .borrow()
+            .as_ref()
+            .map(|context_menu| context_menu.is_visible())
+            .unwrap_or_default();
+        // thumbnails don't replace a menu another item has open
+        let other_open = self
+            .apps_container()
+            .map(|apps_container| apps_container.has_open_popover())
+            .unwrap_or_default();
+        if active.0.is_empty() || imp.popover.borrow().is_visible() || menu_open || other_open {
+            return;
+        }
+        let (windows, _) = sort_windows(active.0);
+        let flyout_box = imp.flyout_box.get().unwrap();
+        while let Some(child) = flyout_box.first_child() {
+            flyout_box.remove(&child);
+        }
+        for window in windows.iter().take(MAX_FLYOUT_PREVIEWS) {
+            flyout_box.append(&self.flyout_thumbnail(&dock_object, window));
+        }
+        let flyout = imp.flyout.get().unwrap();
+        flyout.set_position(imp.popover.borrow().position());
+        flyout.popup();
+    }
+
+    fn flyout_thumbnail(&self, dock_object: &DockObject, window: &Item) -> Button {
+        let imp = imp::DockItem::from_instance(self);
+        let picture = cascade! {
+            Picture::new();
+            ..set_size_request(FLYOUT_PREVIEW_WIDTH, FLYOUT_PREVIEW_HEIGHT);
+            ..set_can_shrink(true);
+            ..set_paintable(Some(&icon_paintable(&dock_object.get_icon(), FLYOUT_PREVIEW_HEIGHT, self)));
+        };
+        let title = cascade! {
+            Label::new(Some(&window.name));
+            ..set_ellipsize(EllipsizeMode::End);
+            ..set_max_width_chars(16);
+        };
+        let content = cascade! {
+            Box::new(Orientation::Vertical, 4);
+            ..append(&picture);
+            ..append(&title);
+        };
+        let entity = window.entity;
+        let button = cascade! {
+            Button::new();
+            ..set_child(Some(&content));
+            ..set_tooltip_text(Some(&window.name));
+            ..add_css_class("dock_flyout_item");
+        };
+        let tx = imp.tx.get().unwrap().clone();
+        button.connect_clicked(glib::clone!(@weak self as self_, @weak dock_object => move |_| {
+            dock_object.set_last_activated(entity);
+            send_event(&tx, Event::Activate(entity));
+            self_.hide_flyout();
+        }));
+        // the app icon stays if the daemon can't capture windows
+        if daemon_supports("WindowCapture") {
+            glib::MainContext::default().spawn_local(glib::clone!(@weak picture => async move {
+                match window_preview(entity).await {
+                    Ok(Some(texture)) => picture.set_paintable(Some(&texture)),
+                    Ok(None) => {}
+                    Err(err) => log::warn!("Failed to capture window preview: {}", err),
                 }
-            };
+            }));
         }
+        button
+    }
+
+    pub fn set_position(&self, position: Anchor) {
+        let imp = imp::DockItem::from_instance(self);
+        imp.position.set(position);
+        let item_box = imp.item_box.borrow();
+        let dots = imp.dots.borrow();
+        let label = imp.label.get().unwrap();
+        // labels go beside the icon along a horizontal dock and below it along a vertical one
+        let icon_box = imp.icon_box.get().unwrap();
+        match position {
+            Anchor::Left | Anchor::Right => {
+                icon_box.set_orientation(Orientation::Vertical);
+                label.set_xalign(0.5);
+            }
+            Anchor::Top | Anchor::Bottom => {
+                icon_box.set_orientation(Orientation::Horizontal);
+                label.set_xalign(0.0);
+            }
+        }
+        match position {
+            Anchor::Left => {
+                item_box.set_orientation(Orientation::Horizontal);
+                dots.set_orientation(Orientation::Vertical);
+                dots.set_margin_bottom(4);
+                dots.set_margin_top(4);
+                item_box.reorder_child_after(icon_box, Some(&*dots));
+            }
+            Anchor::Right => {
+                item_box.set_orientation(Orientation::Horizontal);
+                dots.set_orientation(Orientation::Vertical);
+                dots.set_margin_bottom(4);
+                dots.set_margin_top(4);
+                item_box.reorder_child_after(&*dots, Some(icon_box));
+            }
+            Anchor::Top => {
+                item_box.set_orientation(Orientation::Vertical);
+                dots.set_orientation(Orientation::Horizontal);
+                dots.set_margin_start(4);
+                dots.set_margin_end(4);
+                item_box.reorder_child_after(icon_box, Some(&*dots));
+            }
+            Anchor::Bottom => {
+                item_box.set_orientation(Orientation::Vertical);
+                dots.set_orientation(Orientation::Horizontal);
+                dots.set_margin_start(4);
+                dots.set_margin_end(4);
+                item_box.reorder_child_after(&*dots, Some(icon_box));
+            }
+        };
+        imp.revealer.get().unwrap().set_transition_type(match position {
+            Anchor::Left | Anchor::Right => RevealerTransitionType::SlideDown,
+            Anchor::Top | Anchor::Bottom => RevealerTransitionType::SlideRight,
+        });
         let popover = imp.popover.borrow();
         popover.set_position(match position {
             Anchor::Left => PositionType::Right,
@@ -195,3 +1278,61 @@ impl DockItem {
         }
     }
 }
+
+// screen readers announce e.g. "Firefox, pinned, 3 windows", states go in the description
+fn accessible_text(
+    name: &str,
+    saved: bool,
+    windows: usize,
+    launching: bool,
+    attention: bool,
+) -> (String, String) {
+    let mut label = vec![name.to_string()];
+    if saved {
+        label.push(fl!("pinned-state"));
+    }
+    if windows > 0 {
+        label.push(fl!("window-count", count = windows));
+    }
+    let mut description = Vec::new();
+    if launching {
+        description.push(fl!("launching-state"));
+    }
+    if attention {
+        description.push(fl!("attention-state"));
+    }
+    (label.join(", "), description.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // without the isolation marks fluent puts around arguments
+    fn plain((label, description): (String, String)) -> (String, String) {
+        let strip = |text: String| text.replace(['\u{2068}', '\u{2069}'], "");
+        (strip(label), strip(description))
+    }
+
+    #[test]
+    fn accessible_labels() {
+        assert_eq!(
+            plain(accessible_text("Firefox", true, 3, false, false)),
+            ("Firefox, pinned, 3 windows".to_string(), String::new())
+        );
+        assert_eq!(
+            plain(accessible_text("Terminal", false, 1, false, true)),
+            (
+                "Terminal, 1 window".to_string(),
+                "needs attention".to_string()
+            )
+        );
+        assert_eq!(
+            plain(accessible_text("Files", true, 0, true, true)),
+            (
+                "Files, pinned".to_string(),
+                "launching, needs attention".to_string()
+            )
+        );
+    }
+}
@@ -2,14 +2,20 @@
 
 use cosmic_panel_config::config::{Anchor, CosmicPanelConfig};
 use glib::SignalHandlerId;
+use glib::subclass::Signal;
+use gtk4::prelude::*;
 use gtk4::subclass::prelude::*;
 use gtk4::{gio, glib};
 use gtk4::{Box, DragSource, DropTarget, GestureClick, ListView};
+use once_cell::sync::Lazy;
 use once_cell::sync::OnceCell;
 use std::cell::{Cell, RefCell};
 use std::rc::Rc;
 use tokio::sync::mpsc;
 
+use crate::config::ClickAction;
+use crate::dock_object::DockObject;
+use crate::saved_writer::SavedWriter;
 use crate::utils::Event;
 
 #[derive(Debug, Default)]
@@ -17,15 +23,33 @@ pub struct DockList {
     pub list_view: OnceCell<ListView>,
     pub type_: OnceCell<super::DockListType>,
     pub model: OnceCell<gio::ListStore>,
+    pub slice_model: OnceCell<gtk4::SliceListModel>,
     pub click_controller: OnceCell<GestureClick>,
     pub drop_controller: OnceCell<DropTarget>,
     pub drag_source: OnceCell<DragSource>,
     pub drag_end_signal: Rc<RefCell<Option<SignalHandlerId>>>,
     pub drag_cancel_signal: Rc<RefCell<Option<SignalHandlerId>>>,
-    pub popover_menu_index: Rc<Cell<Option<u32>>>,
+    // dragging a saved app off the dock unpins it, see `DockList::begin_drag_out`
+    pub drag_start: Cell<(f64, f64)>,
+    pub drag_paintable: RefCell<Option<gtk4::IconPaintable>>,
+    pub dragged_out: Cell<bool>,
+    pub drag_remove_cue: RefCell<Option<gtk4::Image>>,
+    pub drag_motion_signals: RefCell<Vec<SignalHandlerId>>,
+    // tracked by object so the popover follows its item when the model is updated
+    pub popover_menu_object: Rc<RefCell<Option<DockObject>>>,
     pub position: Rc<Cell<Anchor>>,
+    pub icon_size: Rc<Cell<u32>>,
+    pub show_labels: Rc<Cell<bool>>,
+    pub hover_previews: Rc<Cell<bool>>,
+    pub animations: Rc<Cell<bool>>,
+    pub click_action: Rc<Cell<ClickAction>>,
     pub tx: OnceCell<mpsc::Sender<Event>>,
-    pub config: OnceCell<CosmicPanelConfig>
+    pub config: OnceCell<CosmicPanelConfig>,
+    pub unresolved_saved: RefCell<Vec<(u32, String)>>,
+    pub saved_writer: RefCell<Option<SavedWriter>>,
+    pub store_timeout: RefCell<Option<glib::SourceId>>,
+    // moves the focus along with a saved item moved by keyboard once the reorder lands
+    pub reorder_focus: RefCell<Option<SignalHandlerId>>,
 }
 
 #[glib::object_subclass]
@@ -33,9 +57,27 @@ impl ObjectSubclass for DockList {
     const NAME: &'static str = "DockList";
     type Type = super::DockList;
     type ParentType = Box;
+
+    fn class_init(klass: &mut Self::Class) {
+        // each list is announced as a section, labeled by its type
+        klass.set_accessible_role(gtk4::AccessibleRole::Group);
+    }
 }
 
-impl ObjectImpl for DockList {}
+impl ObjectImpl for DockList {
+    fn signals() -> &'static [Signal] {
+        static SIGNALS: Lazy<Vec<Signal>> = Lazy::new(|| {
+            vec![Signal::builder(
+                // emitted when keyboard focus should leave the list, true if moving forward
+                "focus-boundary",
+                &[bool::static_type().into()],
+                <()>::static_type().into(),
+            )
+            .build()]
+        });
+        SIGNALS.as_ref()
+    }
+}
 
 impl WidgetImpl for DockList {}
 
@@ -1,9 +1,14 @@
 // SPDX-License-Identifier: MPL-2.0-only
 
+use crate::apps_container::AppsContainer;
+use crate::config::ClickAction;
+use crate::custom_launcher::{self, StoredApp};
 use crate::dock_item::DockItem;
 use crate::dock_object::DockObject;
+use crate::fl;
+use crate::saved_writer::{self, SavedWriter};
 use crate::utils::data_path;
-use crate::utils::{BoxedWindowList, Event, Item};
+use crate::utils::{icon_paintable, queue_refresh, send_event, BoxedWindowList, Event, Item};
 use cascade::cascade;
 use cosmic_panel_config::config::{Anchor, CosmicPanelConfig};
 use gio::DesktopAppInfo;
@@ -12,24 +17,31 @@ use glib::Object;
 use glib::Type;
 use gtk4::gdk;
 use gtk4::gdk::ContentProvider;
-use gtk4::gdk::Display;
+use gtk4::gdk::Key;
 use gtk4::gdk::ModifierType;
 use gtk4::glib;
 use gtk4::prelude::ListModelExt;
 use gtk4::prelude::*;
 use gtk4::subclass::prelude::*;
 use gtk4::DropTarget;
-use gtk4::IconTheme;
 use gtk4::ListView;
 use gtk4::Orientation;
 use gtk4::SignalListItemFactory;
-use gtk4::{DragSource, GestureClick};
-use std::fs::File;
+use gtk4::SliceListModel;
+use gtk4::{Align, DragIcon, Image, Overlay};
+use gtk4::{DragSource, EventControllerKey, GestureClick, PropagationPhase};
+use std::cell::Cell;
+use std::collections::HashSet;
 use std::path::Path;
+use std::rc::Rc;
+use std::time::Duration;
 use tokio::sync::mpsc::Sender;
 
 mod imp;
 
+// how long the saved apps have to stay unchanged before they are written
+const STORE_DELAY_MS: u64 = 500;
+
 glib::wrapper! {
     pub struct DockList(ObjectSubclass<imp::DockList>)
         @extends gtk4::Widget, gtk4::Box,
@@ -40,6 +52,10 @@ glib::wrapper! {
 pub enum DockListType {
     Saved,
     Active,
+    /// removable drives and the trash, these can't be dragged or dropped on
+    System,
+    /// recently launched apps that are neither saved nor running, also without drag and drop
+    Recent,
 }
 
 impl Default for DockListType {
@@ -54,13 +70,25 @@ impl DockList {
         let imp = imp::DockList::from_instance(&self_);
         imp.type_.set(type_).unwrap();
         imp.tx.set(tx).unwrap();
+        imp.icon_size.set(config.get_applet_icon_size());
+        imp.animations.set(true);
         imp.config.set(config).unwrap();
+        let label = match type_ {
+            DockListType::Saved => fl!("saved-apps"),
+            DockListType::Active => fl!("running-apps-group"),
+            DockListType::Recent => fl!("recent-apps"),
+            DockListType::System => fl!("system-items"),
+        };
+        self_.update_property(&[(gtk4::AccessibleProperty::Label, &label)]);
         self_.layout();
         //dnd behavior is different for each type, as well as the data in the model
         self_.setup_model();
         self_.setup_click_controller();
-        self_.setup_drag();
-        self_.setup_drop_target();
+        self_.setup_key_controller();
+        if matches!(type_, DockListType::Saved | DockListType::Active) {
+            self_.setup_drag();
+            self_.setup_drop_target();
+        }
         self_.setup_factory();
         self_
     }
@@ -77,30 +105,74 @@ impl DockList {
         imp.drop_controller.get().expect("Could not get model")
     }
 
+    pub fn set_click_action(&self, click_action: ClickAction) {
+        let imp = imp::DockList::from_instance(self);
+        imp.click_action.set(click_action);
+    }
+
     pub fn popover_index(&self) -> Option<u32> {
         // Get state
         let imp = imp::DockList::from_instance(self);
-        imp.popover_menu_index.get()
+        imp.popover_menu_object
+            .borrow()
+            .as_ref()
+            .and_then(|dock_object| dock_object.position_in(self.model()))
+    }
+
+    /// Desktop ids of the apps in the model, in dock order.
+    pub fn saved_ids(&self) -> Vec<String> {
+        let model = self.model();
+        let mut ids = Vec::new();
+        let mut i = 0;
+        while let Some(item) = model.item(i) {
+            if let Ok(dock_object) = item.downcast::<DockObject>() {
+                if let Some(id) = dock_object.get_id() {
+                    ids.push(id);
+                }
+            }
+            i += 1;
+        }
+        ids
     }
 
     fn restore_data(&self) {
-        if let Ok(file) = File::open(data_path()) {
-            if let Ok(data) = serde_json::from_reader::<_, Vec<String>>(file) {
-                // dbg!(&data);
-                let dock_objects: Vec<Object> = data
-                    .into_iter()
-                    .filter_map(|d| {
-                        DockObject::from_app_info_path(&d)
-                            .map(|dockobject| dockobject.upcast::<Object>())
-                    })
-                    .collect();
-                // dbg!(&dock_objects);
-
-                let model = self.model();
-                model.splice(model.n_items(), 0, &dock_objects);
+        if let Some(data) = saved_writer::load(&data_path()) {
+            // dbg!(&data);
+            let imp = imp::DockList::from_instance(self);
+            let mut unresolved = imp.unresolved_saved.borrow_mut();
+            let mut dock_objects: Vec<Object> = Vec::new();
+            // earlier versions could save an app twice, only the first entry is kept
+            let mut seen = HashSet::new();
+            for d in data {
+                let d = match d {
+                    StoredApp::Id(d) => d,
+                    StoredApp::Custom(launcher) => {
+                        if seen.insert(launcher.id.clone()) {
+                            custom_launcher::register(launcher.clone());
+                            dock_objects.push(DockObject::from_custom_launcher(launcher).upcast());
+                        }
+                        continue;
+                    }
+                };
+                let id = Path::new(&d)
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_else(|| d.clone());
+                if !seen.insert(id) {
+                    continue;
+                }
+                match DockObject::from_app_info_path(&d) {
+                    Some(dockobject) => dock_objects.push(dockobject.upcast::<Object>()),
+                    // keep apps that are not installed right now so they come back later
+                    None => unresolved.push((dock_objects.len() as u32, d)),
+                }
             }
+            // dbg!(&dock_objects);
+
+            let model = self.model();
+            model.splice(model.n_items(), 0, &dock_objects);
         } else {
-            eprintln!("Error loading saved apps!");
+            log::error!("Error loading saved apps!");
             let model = &self.model();
             xdg::BaseDirectories::new()
                 .expect("could not access XDG Base directory")
@@ -135,28 +207,74 @@ impl DockList {
         }
     }
 
-    fn store_data(model: &gio::ListStore) {
-        // Store todo data in vector
-        let mut backup_data = Vec::new();
-        let mut i = 0;
-        while let Some(item) = model.item(i) {
-            // Get `AppGroup` from `glib::Object`
-            let dock_object = item
-                .downcast_ref::<DockObject>()
-                .expect("The object needs to be of type `AppGroupData`.");
-            // Add todo data to vector and increase position
-            if let Some(app_info) = dock_object.property::<Option<DesktopAppInfo>>("appinfo") {
-                if let Some(f) = app_info.filename() {
-                    backup_data.push(f);
-                }
-            }
-            i += 1;
+    /// Like `saved_ids`, including apps that are not installed right now.
+    pub fn pinned_ids(&self) -> Vec<String> {
+        let imp = imp::DockList::from_instance(self);
+        let mut ids = self.saved_ids();
+        // put uninstalled apps back at their previous position
+        for (index, id) in imp.unresolved_saved.borrow().iter() {
+            let index = (*index as usize).min(ids.len());
+            ids.insert(index, id.clone());
         }
+        ids
+    }
+
+    /// Replaces the apps that are not installed right now, by their position among all pinned apps.
+    pub fn set_unresolved(&self, unresolved: Vec<(u32, String)>) {
+        let imp = imp::DockList::from_instance(self);
+        if *imp.unresolved_saved.borrow() == unresolved {
+            return;
+        }
+        imp.unresolved_saved.replace(unresolved);
+        self.queue_store_data();
+    }
+
+    // a drag or a burst of D-Bus requests changes the model many times, it is written once
+    // the changes settled
+    fn queue_store_data(&self) {
+        let imp = imp::DockList::from_instance(self);
+        if let Some(source_id) = imp.store_timeout.replace(None) {
+            source_id.remove();
+        }
+        let source_id = glib::timeout_add_local_once(
+            Duration::from_millis(STORE_DELAY_MS),
+            glib::clone!(@weak self as self_ => move || {
+                imp::DockList::from_instance(&self_).store_timeout.replace(None);
+                self_.store_data();
+            }),
+        );
+        imp.store_timeout.replace(Some(source_id));
+    }
+
+    /// Writes a pending change of the saved apps and waits until it is on disk, called before
+    /// the app list exits.
+    pub fn flush_saved(&self) {
+        let imp = imp::DockList::from_instance(self);
+        if let Some(source_id) = imp.store_timeout.replace(None) {
+            source_id.remove();
+            self.store_data();
+        }
+        if let Some(mut saved_writer) = imp.saved_writer.replace(None) {
+            saved_writer.finish();
+        }
+    }
+
+    fn store_data(&self) {
+        // custom launchers are stored in full, there is nothing else to load them from
+        let backup_data: Vec<StoredApp> = self
+            .pinned_ids()
+            .into_iter()
+            .map(|id| match custom_launcher::get(&id) {
+                Some(launcher) => StoredApp::Custom(launcher),
+                None => StoredApp::Id(id),
+            })
+            .collect();
         // dbg!(&backup_data);
-        // Save state in file
-        let file = File::create(data_path()).expect("Could not create json file.");
-        serde_json::to_writer_pretty(file, &backup_data)
-            .expect("Could not write data to json file");
+        // Save state in file, the model keeps the order if writing fails
+        let imp = imp::DockList::from_instance(self);
+        if let Some(saved_writer) = imp.saved_writer.borrow().as_ref() {
+            saved_writer.write(backup_data);
+        }
         // TODO save plugins here for now examples are hardcoded and don't need to be saved
     }
 
@@ -168,7 +286,7 @@ impl DockList {
             ..add_css_class("transparent");
         };
         if imp.type_.get().unwrap() == &DockListType::Saved {
-            list_view.set_width_request(64);
+            list_view.set_width_request(imp.icon_size.get() as i32 * 2);
         }
         self.append(&list_view);
         imp.list_view.set(list_view).unwrap();
@@ -187,23 +305,112 @@ impl DockList {
             .set_orientation(position.into());
     }
 
+    /// Only shows the first `limit` items of the model, or all of them when `None`.
+    pub fn set_visible_limit(&self, limit: Option<u32>) {
+        let imp = imp::DockList::from_instance(self);
+        imp.slice_model
+            .get()
+            .unwrap()
+            .set_size(limit.unwrap_or(u32::MAX));
+    }
+
+    /// Number of items that are actually displayed.
+    pub fn visible_n_items(&self) -> u32 {
+        let imp = imp::DockList::from_instance(self);
+        imp.slice_model.get().unwrap().n_items()
+    }
+
+    /// Rebinds every item, which looks up the icons again.
+    pub fn refresh_items(&self) {
+        let model = self.model();
+        model.items_changed(0, model.n_items(), model.n_items());
+    }
+
+    /// Changes the icon size of every item, rebinding the existing ones.
+    pub fn set_icon_size(&self, icon_size: u32) {
+        let imp = imp::DockList::from_instance(self);
+        imp.icon_size.set(icon_size);
+        self.refresh_items();
+        if imp.type_.get().unwrap() == &DockListType::Saved {
+            imp.list_view.get().unwrap().set_width_request(icon_size as i32 * 2);
+        }
+    }
+
+    /// Shows the app names next to the icons, rebinding the existing items.
+    pub fn set_show_labels(&self, show_labels: bool) {
+        let imp = imp::DockList::from_instance(self);
+        if imp.show_labels.replace(show_labels) == show_labels {
+            return;
+        }
+        self.refresh_items();
+    }
+
+    /// Shows window thumbnails when the pointer rests on an item, rebinding the existing items.
+    pub fn set_hover_previews(&self, hover_previews: bool) {
+        let imp = imp::DockList::from_instance(self);
+        if imp.hover_previews.replace(hover_previews) == hover_previews {
+            return;
+        }
+        self.refresh_items();
+    }
+
+    /// Turns the animations of the items on or off, rebinding the existing items.
+    pub fn set_animations_enabled(&self, enabled: bool) {
+        let imp = imp::DockList::from_instance(self);
+        if imp.animations.replace(enabled) == enabled {
+            return;
+        }
+        self.refresh_items();
+    }
+
+    /// Moves the item at `from` to `to`, which is clamped to the model. The model changes
+    /// once, so the order is stored and announced once.
+    pub fn move_item(&self, from: u32, to: u32) {
+        let model = self.model();
+        let n_items = model.n_items();
+        if from >= n_items {
+            return;
+        }
+        let to = to.min(n_items - 1);
+        if from == to {
+            return;
+        }
+        let (start, end) = (from.min(to), from.max(to));
+        let mut items: Vec<Object> = (start..=end).filter_map(|i| model.item(i)).collect();
+        if from < to {
+            items.rotate_left(1);
+        } else {
+            items.rotate_right(1);
+        }
+        model.splice(start, end - start + 1, &items);
+    }
+
     fn setup_model(&self) {
         let imp = imp::DockList::from_instance(self);
         let model = gio::ListStore::new(DockObject::static_type());
 
-        let selection_model = gtk4::NoSelection::new(Some(&model));
+        // the slice hides trailing items when the dock overflows
+        let slice_model = SliceListModel::new(Some(&model), 0, u32::MAX);
+        let selection_model = gtk4::NoSelection::new(Some(&slice_model));
 
         // Wrap model with selection and pass it to the list view
         let list_view = imp.list_view.get().unwrap();
         list_view.set_model(Some(&selection_model));
         imp.model.set(model).expect("Could not set model");
+        imp.slice_model.set(slice_model).expect("Could not set slice model");
 
         if imp.type_.get().unwrap() == &DockListType::Saved {
             let model = self.model();
             self.restore_data();
-            model.connect_items_changed(|model, _, _removed, _added| {
-                Self::store_data(model);
-            });
+            imp.saved_writer.replace(Some(SavedWriter::new(
+                data_path(),
+                imp.tx.get().unwrap().clone(),
+            )));
+            model.connect_items_changed(
+                glib::clone!(@weak self as self_ => move |_, _, _removed, _added| {
+                    self_.queue_store_data();
+                }),
+            );
         }
     }
 
@@ -216,11 +423,26 @@ impl DockList {
             .build();
         self.add_controller(&controller);
 
+        // releases that end a drag of the item are not clicks
+        let press_position = Rc::new(Cell::new((0.0, 0.0)));
+        controller.connect_pressed(glib::clone!(@weak press_position => move |_, _, x, y| {
+            press_position.set((x, y));
+        }));
+
         let model = self.model();
+        let slice_model = imp.slice_model.get().unwrap();
         let list_view = &imp.list_view.get().unwrap();
-        let popover_menu_index = &imp.popover_menu_index;
+        let popover_menu_object = &imp.popover_menu_object;
+        let click_action = &imp.click_action;
         let tx = imp.tx.get().unwrap().clone();
-        controller.connect_released(glib::clone!(@weak model, @weak list_view, @weak popover_menu_index => move |self_, _, x, y| {
+        controller.connect_released(glib::clone!(@weak model, @weak slice_model, @weak list_view, @weak popover_menu_object, @weak click_action, @weak press_position => move |self_, _, x, y| {
+            let drag_threshold = gtk4::Settings::default()
+                .map(|settings| settings.property::<i32>("gtk-dnd-drag-threshold"))
+                .unwrap_or(8);
+            let (press_x, press_y) = press_position.get();
+            if (x - press_x).hypot(y - press_y) > f64::from(drag_threshold) {
+                return;
+            }
             let max_x = list_view.allocated_width();
             let max_y = list_view.allocated_height();
             let (indexing_dim, indexing_length, other_dim, other_length) = match list_view.orientation() {
@@ -230,7 +452,7 @@ impl DockList {
             };
             // dbg!(max_y);
             // dbg!(y);
-            let n_buckets = model.n_items();
+            let n_buckets = slice_model.n_items();
             let index = (indexing_dim * n_buckets as f64 / (indexing_length as f64 + 0.1)) as u32;
             // dbg!(self_.current_button());
             // dbg!(self_.last_event(self_.current_sequence().as_ref()));
@@ -238,61 +460,95 @@ impl DockList {
             // dbg!(click_modifier);
             // Launch the application when an item of the list is activated
 
+            let launch_tx = tx.clone();
+            let toggle_tx = tx.clone();
+            let custom_tx = tx.clone();
             let tx = tx.clone();
-            let focus_window = move |first_focused_item: &Item| {
+            let focus_window = move |dock_object: &DockObject, first_focused_item: &Item| {
                 let entity = first_focused_item.entity;
-                let tx = tx.clone();
-                glib::MainContext::default().spawn_local(async move {
-                   let _ = tx.clone().send(Event::Activate(entity)).await;
-                });
+                dock_object.set_last_activated(entity);
+                send_event(&tx, Event::Activate(entity));
             };
-            let old_index = popover_menu_index.get();
-            if let Some(old_index) = old_index  {
-                if let Some(old_item) = model.item(old_index) {
-                    if let Ok(old_dock_object) = old_item.downcast::<DockObject>() {
-                        old_dock_object.set_popover(false);
-                        popover_menu_index.replace(None);
-                        model.items_changed(old_index, 0, 0);
-                        //TODO signal dock to check if it should hide
-                    }
+            let launch_app = move |app_info: &DesktopAppInfo| {
+                if let Some(id) = app_info.id() {
+                    send_event(&launch_tx, Event::Launch(id.to_string()));
+                }
+            };
+            let old_dock_object = popover_menu_object.borrow().clone();
+            if let Some(old_dock_object) = old_dock_object {
+                old_dock_object.set_popover(false);
+                popover_menu_object.replace(None);
+                if let Some(old_index) = old_dock_object.position_in(&model) {
+                    model.items_changed(old_index, 0, 0);
+                    //TODO signal dock to check if it should hide
                 }
                 return;
             }
-            if other_dim > f64::from(other_length) || y < 0.0 || indexing_dim > f64::from(indexing_length) || indexing_dim < 0.0 {
+            if other_dim > f64::from(other_length) || other_dim < 0.0 || indexing_dim > f64::from(indexing_length) || indexing_dim < 0.0 {
                 // println!("out of bounds click...");
                 return;
             }
 
             if let Some(item) = model.item(index) {
                 if let Ok(dock_object) = item.downcast::<DockObject>() {
+                    if let Some(location) = dock_object.location() {
+                        if self_.current_button() == 1 {
+                            crate::system_items::open(&location);
+                        }
+                        return;
+                    }
                     let active = dock_object.property::<BoxedWindowList>("active");
-                    let app_info = dock_object.property::<Option<DesktopAppInfo>>("appinfo");
+                    // apps whose desktop file was removed can't be launched anymore
+                    let app_info = dock_object
+                        .property::<Option<DesktopAppInfo>>("appinfo")
+                        .filter(|_| dock_object.can_launch());
                     match (self_.current_button(), click_modifier, active.0.get(0), app_info) {
-                        (click, Some(click_modifier), Some(first_focused_item), _) if click == 1 && !click_modifier.contains(ModifierType::CONTROL_MASK) => focus_window(first_focused_item),
-                        (click, None, Some(first_focused_item), _) if click == 1 => focus_window(first_focused_item),
-                        (click, _, _, Some(app_info)) | (click, _, None, Some(app_info)) if click != 3  => {
-                            let context = gdk::Display::default().unwrap().app_launch_context();
-                            if let Err(err) = app_info.launch(&[], Some(&context)) {
-                                dbg!(err);
-                            }
-
-                        }
-                        (click, _, _, _) if click == 3 => {
-                            // println!("handling right click");
-                            if let Some(old_index) = popover_menu_index.get() {
-                                if let Some(item) = model.item(old_index) {
-                                    if let Ok(dock_object) = item.downcast::<DockObject>() {
-                                        dock_object.set_popover(false);
-                                        popover_menu_index.replace(Some(index));
-                                        model.items_changed(old_index, 0, 0);
+                        // windows demanding attention are focused before anything else
+                        (1, _, Some(_), _) if active.urgent().is_some() => focus_window(&dock_object, active.urgent().unwrap()),
+                        // ctrl + click opens a new instance instead
+                        (1, click_modifier, Some(_), _) if !click_modifier.map(|m| m.contains(ModifierType::CONTROL_MASK)).unwrap_or_default() => {
+                            match click_action.get().for_window_count(active.0.len()) {
+                                ClickAction::ShowPopover => {
+                                    dock_object.set_popover(true);
+                                    popover_menu_object.replace(Some(dock_object.clone()));
+                                    model.items_changed(index, 0, 0);
+                                }
+                                ClickAction::CycleWindows => {
+                                    if let Some(entity) = dock_object.next_window(true) {
+                                        if let Some(window) = active.0.iter().find(|w| w.entity == entity) {
+                                            focus_window(&dock_object, window);
+                                        }
+                                    }
+                                }
+                                ClickAction::FocusLast => {
+                                    if let Some(window) = dock_object.last_activated_window() {
+                                        focus_window(&dock_object, &window);
+                                    }
+                                }
+                                ClickAction::MinimizeToggle => {
+                                    match dock_object.last_activated_window() {
+                                        Some(window) if window.focused => {
+                                            send_event(&toggle_tx, Event::Toggle(window.entity));
+                                        }
+                                        Some(window) => focus_window(&dock_object, &window),
+                                        None => {}
                                     }
                                 }
                             }
-                            dock_object.set_popover(true);
-                            popover_menu_index.replace(Some(index));
-                            model.items_changed(index, 0, 0);
                         }
-                        _ => eprintln!("Failed to process click.")
+                        // middle click always opens a new instance
+                        (2, _, _, Some(app_info)) => launch_app(&app_info),
+                        // custom launchers have no desktop entry to launch
+                        (2, _, _, None) | (1, _, None, None) if dock_object.custom_launcher().is_some() => {
+                            if let Some(id) = dock_object.get_id() {
+                                send_event(&custom_tx, Event::Launch(id));
+                            }
+                        }
+                        (2, _, Some(first_focused_item), None) => focus_window(&dock_object, first_focused_item),
+                        (click, _, _, Some(app_info)) | (click, _, None, Some(app_info)) if click != 3  => launch_app(&app_info),
+                        // the dock item shows its own context menu
+                        (3, _, _, _) => {}
+                        _ => log::warn!("Failed to process click.")
                     }
                 }
             }
@@ -300,17 +556,321 @@ impl DockList {
         imp.click_controller.set(controller).unwrap();
     }
 
-    fn setup_drop_target(&self) {
+    /// Index of the item that currently has keyboard focus.
+    pub fn focused_index(&self) -> Option<u32> {
+        let imp = imp::DockList::from_instance(self);
+        let list_view = imp.list_view.get().unwrap();
+        let focus_child = list_view.focus_child()?;
+        let mut i = 0;
+        let mut child = list_view.first_child();
+        while let Some(c) = child {
+            if c == focus_child {
+                return Some(i);
+            }
+            i += 1;
+            child = c.next_sibling();
+        }
+        None
+    }
+
+    /// Moves keyboard focus to the item at `index`, returns false if there is none.
+    pub fn focus_index(&self, index: u32) -> bool {
+        let imp = imp::DockList::from_instance(self);
+        let list_view = imp.list_view.get().unwrap();
+        let mut child = list_view.first_child();
+        for _ in 0..index {
+            child = child.and_then(|c| c.next_sibling());
+        }
+        match child.and_then(|row| row.first_child()) {
+            Some(dock_item) => dock_item.grab_focus(),
+            None => false,
+        }
+    }
+
+    /// Moves keyboard focus to the first or last visible item.
+    pub fn focus_edge(&self, first: bool) -> bool {
+        let n_items = self.visible_n_items();
+        if !self.is_visible() || n_items == 0 {
+            return false;
+        }
+        self.focus_index(if first { 0 } else { n_items - 1 })
+    }
+
+    /// Focuses the most recent window of the app at `index`, or launches it if it isn't running.
+    pub fn activate_index(&self, index: u32) {
+        let imp = imp::DockList::from_instance(self);
+        let dock_object = match self.model().item(index).and_then(|item| item.downcast::<DockObject>().ok()) {
+            Some(dock_object) => dock_object,
+            None => return,
+        };
+        let active = dock_object.property::<BoxedWindowList>("active");
+        let event = match active.urgent().or_else(|| active.0.get(0)) {
+            Some(item) => {
+                dock_object.set_last_activated(item.entity);
+                Event::Activate(item.entity)
+            }
+            None => match dock_object.get_id().filter(|_| dock_object.can_launch()) {
+                Some(id) => Event::Launch(id),
+                None => return,
+            },
+        };
+        send_event(imp.tx.get().unwrap(), event);
+    }
+
+    // move a saved item one step and keep it focused, the event loop moves it like a drop so
+    // the change is saved and announced
+    fn move_saved_item(&self, index: u32, forward: bool) {
+        let imp = imp::DockList::from_instance(self);
+        let model = self.model();
+        let target = match (forward, index) {
+            (true, i) if i + 1 < model.n_items() => i + 1,
+            (false, i) if i > 0 => i - 1,
+            _ => return,
+        };
+        let dock_object = match model
+            .item(index)
+            .and_then(|item| item.downcast::<DockObject>().ok())
+        {
+            Some(dock_object) => dock_object,
+            None => return,
+        };
+        let id = match dock_object.get_id() {
+            Some(id) => id,
+            None => return,
+        };
+        if let Some(old_handler) = imp.reorder_focus.replace(None) {
+            model.disconnect(old_handler);
+        }
+        let handler = model.connect_items_changed(
+            glib::clone!(@weak self as self_, @weak dock_object => move |model, _, _, _| {
+                if dock_object.position_in(model) != Some(target) {
+                    return;
+                }
+                let imp = imp::DockList::from_instance(&self_);
+                if let Some(handler) = imp.reorder_focus.replace(None) {
+                    model.disconnect(handler);
+                }
+                // the rows are rebuilt after the model changed
+                glib::idle_add_local_once(glib::clone!(@weak self_ => move || {
+                    self_.focus_index(target);
+                }));
+            }),
+        );
+        imp.reorder_focus.replace(Some(handler));
+        send_event(
+            imp.tx.get().unwrap(),
+            Event::Reorder {
+                id,
+                new_index: target,
+            },
+        );
+    }
+
+    fn setup_key_controller(&self) {
+        let imp = imp::DockList::from_instance(self);
+        let controller = EventControllerKey::new();
+        // handle the keys before the focused button or the list view see them
+        controller.set_propagation_phase(PropagationPhase::Capture);
+        controller.connect_key_pressed(glib::clone!(@weak self as self_ => @default-return Inhibit(false), move |_, key, _, modifier| {
+            let imp = imp::DockList::from_instance(&self_);
+            let index = match self_.focused_index() {
+                Some(index) => index,
+                None => return Inhibit(false),
+            };
+            let horizontal = imp.list_view.get().unwrap().orientation() == Orientation::Horizontal;
+            let forward = match key {
+                Key::Right if horizontal => Some(true),
+                Key::Left if horizontal => Some(false),
+                Key::Down if !horizontal => Some(true),
+                Key::Up if !horizontal => Some(false),
+                _ => None,
+            };
+            match (key, forward) {
+                (_, Some(forward)) if modifier.contains(ModifierType::CONTROL_MASK) => {
+                    if imp.type_.get().unwrap() == &DockListType::Saved {
+                        self_.move_saved_item(index, forward);
+                    }
+                }
+                (_, Some(forward)) => {
+                    let target = if forward { index.checked_add(1) } else { index.checked_sub(1) };
+                    let moved = target
+                        .filter(|target| *target < self_.visible_n_items())
+                        .map(|target| self_.focus_index(target))
+                        .unwrap_or_default();
+                    if !moved {
+                        self_.emit_by_name::<()>("focus-boundary", &[&forward]);
+                    }
+                }
+                (Key::Return | Key::KP_Enter | Key::space, None) => self_.activate_index(index),
+                _ => return Inhibit(false),
+            }
+            Inhibit(true)
+        }));
+        imp.list_view.get().unwrap().add_controller(&controller);
+    }
+
+    // the position of an item moved from `old_index` into the gap at `drop_index`
+    fn moved_index(old_index: u32, drop_index: u32) -> u32 {
+        if drop_index > old_index {
+            drop_index - 1
+        } else {
+            drop_index
+        }
+    }
+
+    // calculate the insertion location for a drop at (x, y)
+    fn drop_index(list_view: &ListView, n_items: u32, x: f64, y: f64) -> u32 {
+        let max_x = list_view.allocated_width();
+        let max_y = list_view.allocated_height();
+        let n_buckets = n_items * 2;
+
+        let (indexing_dim, indexing_length) = match list_view.orientation() {
+            Orientation::Horizontal => (x, max_x),
+            Orientation::Vertical => (y, max_y),
+            _ => (x, max_x),
+        };
+
+        let drop_bucket = (indexing_dim * n_buckets as f64 / (indexing_length as f64 + 0.1)) as u32;
+        if drop_bucket == 0 {
+            0
+        } else if drop_bucket == n_buckets - 1 {
+            n_items
+        } else {
+            (drop_bucket + 1) / 2
+        }
+    }
+
+    // a saved app released away from the dock is unpinned, a cue on the drag icon shows when it
+    // would be. The pointer must have moved a bit before leaving the dock, so a short drag off
+    // an item at the edge doesn't unpin it.
+    fn begin_drag_out(&self, drag: &gdk::Drag) {
         let imp = imp::DockList::from_instance(self);
-        if imp.type_.get().unwrap() != &DockListType::Saved {
+        imp.dragged_out.set(false);
+        let apps_container = match self
+            .ancestor(AppsContainer::static_type())
+            .and_then(|container| container.downcast::<AppsContainer>().ok())
+        {
+            Some(apps_container) => apps_container,
+            None => return,
+        };
+
+        let cue = cascade! {
+            Image::from_icon_name(Some("list-remove-symbolic"));
+            ..set_halign(Align::End);
+            ..set_valign(Align::Start);
+            ..set_visible(false);
+            ..add_css_class("drag_remove_cue");
+        };
+        let icon = cascade! {
+            Image::new();
+            ..set_pixel_size(64);
+        };
+        if let Some(paintable) = imp.drag_paintable.borrow().as_ref() {
+            icon.set_paintable(Some(paintable));
+        }
+        let overlay = cascade! {
+            Overlay::new();
+            ..set_child(Some(&icon));
+            ..add_overlay(&cue);
+        };
+        if let Ok(drag_icon) = DragIcon::for_drag(drag).downcast::<DragIcon>() {
+            drag_icon.set_child(Some(&overlay));
+            drag.set_hotspot(32, 32);
+        }
+        imp.drag_remove_cue.replace(Some(cue));
+
+        let list_view = imp.list_view.get().unwrap();
+        let (x, y) = imp.drag_start.get();
+        let start = list_view
+            .translate_coordinates(&apps_container, x, y)
+            .unwrap_or((x, y));
+        let last = Rc::new(Cell::new(start));
+        let threshold = 2 * gtk4::Settings::default()
+            .map(|settings| settings.property::<i32>("gtk-dnd-drag-threshold"))
+            .unwrap_or(8);
+
+        let drag_motion = apps_container.drag_motion();
+        let signals = vec![
+            drag_motion.connect_enter(glib::clone!(@weak self as self_, @strong last => move |_, x, y| {
+                last.set((x, y));
+                self_.set_dragged_out(false);
+            })),
+            drag_motion.connect_motion(glib::clone!(@weak self as self_, @strong last => move |_, x, y| {
+                last.set((x, y));
+                self_.set_dragged_out(false);
+            })),
+            drag_motion.connect_leave(glib::clone!(@weak self as self_, @strong last => move |_| {
+                let (x, y) = last.get();
+                if (x - start.0).hypot(y - start.1) >= f64::from(threshold) {
+                    self_.set_dragged_out(true);
+                }
+            })),
+        ];
+        imp.drag_motion_signals.replace(signals);
+    }
+
+    fn set_dragged_out(&self, dragged_out: bool) {
+        let imp = imp::DockList::from_instance(self);
+        imp.dragged_out.set(dragged_out);
+        if let Some(cue) = imp.drag_remove_cue.borrow().as_ref() {
+            cue.set_visible(dragged_out);
+        }
+    }
+
+    fn end_drag_out(&self) {
+        let imp = imp::DockList::from_instance(self);
+        imp.dragged_out.set(false);
+        imp.drag_remove_cue.replace(None);
+        imp.drag_paintable.replace(None);
+        let signals: Vec<_> = imp.drag_motion_signals.borrow_mut().drain(..).collect();
+        if signals.is_empty() {
             return;
         }
+        if let Some(apps_container) = self
+            .ancestor(AppsContainer::static_type())
+            .and_then(|container| container.downcast::<AppsContainer>().ok())
+        {
+            for signal in signals {
+                apps_container.drag_motion().disconnect(signal);
+            }
+        }
+    }
+
+    // mark the gap where a dropped item would be inserted
+    fn set_drop_indicator(list_view: &ListView, index: Option<u32>) {
+        let mut i = 0;
+        let mut child = list_view.first_child();
+        let mut last_child = None;
+        while let Some(c) = child {
+            c.remove_css_class("drop_before");
+            c.remove_css_class("drop_after");
+            if Some(i) == index {
+                c.add_css_class("drop_before");
+            }
+            i += 1;
+            child = c.next_sibling();
+            last_child = Some(c);
+        }
+        if let Some(last_child) = last_child {
+            if index.map(|index| index >= i).unwrap_or_default() {
+                last_child.add_css_class("drop_after");
+            }
+        }
+    }
+
+    fn setup_drop_target(&self) {
+        let imp = imp::DockList::from_instance(self);
+        let type_ = *imp.type_.get().unwrap();
 
         let drop_target_widget = &imp.list_view.get().unwrap();
         let mut drop_actions = gdk::DragAction::COPY;
         drop_actions.insert(gdk::DragAction::MOVE);
-        let drop_format = gdk::ContentFormats::for_type(Type::STRING);
-        let drop_format = drop_format.union(&gdk::ContentFormats::for_type(Type::U32));
+        let drop_format = match type_ {
+            DockListType::Saved => gdk::ContentFormats::for_type(Type::STRING)
+                .union(&gdk::ContentFormats::for_type(Type::U32)),
+            // only saved apps can be dropped on the active list
+            _ => gdk::ContentFormats::for_type(Type::U32),
+        };
         let drop_controller = DropTarget::builder()
             .preload(true)
             .actions(drop_actions)
@@ -318,32 +878,38 @@ impl DockList {
             .build();
         drop_target_widget.add_controller(&drop_controller);
 
+        if type_ == DockListType::Active {
+            // the saved list removes the dragged item when the drag ends, which unfavorites it
+            drop_controller.connect_drop(|_self, drop_value, _x, _y| drop_value.get::<u32>().is_ok());
+            imp.drop_controller
+                .set(drop_controller)
+                .expect("Could not set dock dnd drop controller");
+            return;
+        }
+
         let model = self.model();
         let list_view = &imp.list_view.get().unwrap();
+        drop_controller.connect_motion(
+            glib::clone!(@weak model, @weak list_view => @default-return gdk::DragAction::empty(), move |_self, x, y| {
+                let index = Self::drop_index(&list_view, model.n_items(), x, y);
+                Self::set_drop_indicator(&list_view, Some(index));
+                match _self.value() {
+                    Some(value) if value.get::<u32>().is_ok() => gdk::DragAction::MOVE,
+                    _ => gdk::DragAction::COPY,
+                }
+            }),
+        );
+        drop_controller.connect_leave(glib::clone!(@weak list_view => move |_self| {
+            Self::set_drop_indicator(&list_view, None);
+        }));
+
         let drag_end = &imp.drag_end_signal;
         let drag_source = &imp.drag_source.get().unwrap();
         let tx = imp.tx.get().unwrap().clone();
         drop_controller.connect_drop(
             glib::clone!(@weak model, @weak list_view, @weak drag_end, @weak drag_source => @default-return true, move |_self, drop_value, x, y| {
-                //calculate insertion location
-                let max_x = list_view.allocated_width();
-                let max_y = list_view.allocated_height();
-                let n_buckets = model.n_items() * 2;
-
-                let (indexing_dim, indexing_length, _other_dim, _other_length) = match list_view.orientation() {
-                    Orientation::Horizontal => (x, max_x, y, max_y),
-                    Orientation::Vertical => (y, max_y, x, max_x),
-                    _ => (x, max_x, y, max_y),
-                };
-
-                let drop_bucket = (indexing_dim * n_buckets as f64 / (indexing_length as f64 + 0.1)) as u32;
-                let index = if drop_bucket == 0 {
-                    0
-                } else if drop_bucket == n_buckets - 1 {
-                    model.n_items()
-                } else {
-                    (drop_bucket + 1) / 2
-                };
+                Self::set_drop_indicator(&list_view, None);
+                let index = Self::drop_index(&list_view, model.n_items(), x, y);
 
                 if let Ok(Some(path_str)) = drop_value.get::<Option<String>>() {
                     let desktop_path = &Path::new(&path_str);
@@ -363,24 +929,47 @@ impl DockList {
                                 i += 1;
                             }
                             if let Some(index_of_existing_app) = index_of_existing_app {
-                                // remove existing entry
-                                model.remove(index_of_existing_app);
                                 if let Some(old_handle) = drag_end.replace(None) {
                                     glib::signal_handler_disconnect(&drag_source, old_handle);
                                 }
+                                if let Some(id) = app_info.id() {
+                                    send_event(
+                                        &tx,
+                                        Event::Reorder {
+                                            id: id.to_string(),
+                                            new_index: Self::moved_index(index_of_existing_app, index),
+                                        },
+                                    );
+                                }
+                            } else if let Some(id) = app_info.id() {
+                                // new favorites are pinned where they were dropped
+                                send_event(
+                                    &tx,
+                                    Event::Favorite {
+                                        id: id.to_string(),
+                                        favorite: true,
+                                        index: Some(index),
+                                    },
+                                );
                             }
-                            model.insert(index, &DockObject::new(app_info));
                         }
                     }
                 }
                 else if let Ok(old_index) = drop_value.get::<u32>() {
                     if let Some(item) = model.item(old_index) {
                         if let Ok(dock_object) = item.downcast::<DockObject>() {
-                            model.remove(old_index);
-                            model.insert(index, &dock_object);
                             if let Some(old_handle) = drag_end.replace(None) {
                                 glib::signal_handler_disconnect(&drag_source, old_handle);
                             }
+                            if let Some(id) = dock_object.get_id() {
+                                send_event(
+                                    &tx,
+                                    Event::Reorder {
+                                        id,
+                                        new_index: Self::moved_index(old_index, index),
+                                    },
+                                );
+                            }
                         }
                     }
                 }
@@ -388,10 +977,7 @@ impl DockList {
                     // dbg!("rejecting drop");
                     _self.reject();
                 }
-                let tx = tx.clone();
-                glib::MainContext::default().spawn_local(async move {
-                   let _ = tx.send(Event::RefreshFromCache).await;
-                });
+                queue_refresh(&tx);
                 true
             }),
         );
@@ -407,7 +993,7 @@ impl DockList {
 
         let actions = match *type_ {
             DockListType::Saved => gdk::DragAction::MOVE,
-            DockListType::Active => gdk::DragAction::COPY,
+            _ => gdk::DragAction::COPY,
         };
         let drag_source = DragSource::builder()
             .name("dock drag source")
@@ -415,19 +1001,22 @@ impl DockList {
             .build();
 
         let model = self.model();
+        let slice_model = imp.slice_model.get().unwrap();
         let list_view = imp.list_view.get().unwrap();
         let drag_end = &imp.drag_end_signal;
         let drag_cancel = &imp.drag_cancel_signal;
         let type_ = *type_;
         let tx = imp.tx.get().unwrap().clone();
         list_view.add_controller(&drag_source);
-        drag_source.connect_prepare(glib::clone!(@weak model, @weak list_view, @weak drag_end, @weak drag_cancel => @default-return None, move |self_, x, _y| {
-            let max_x = list_view.allocated_width();
-            // dbg!(max_x);
-            // dbg!(max_y);
-            let n_buckets = model.n_items();
+        drag_source.connect_prepare(glib::clone!(@weak self as dock_list, @weak model, @weak slice_model, @weak list_view, @weak drag_end, @weak drag_cancel => @default-return None, move |self_, x, y| {
+            imp::DockList::from_instance(&dock_list).drag_start.set((x, y));
+            let (indexing_dim, indexing_length) = match list_view.orientation() {
+                Orientation::Vertical => (y, list_view.allocated_height()),
+                _ => (x, list_view.allocated_width()),
+            };
+            let n_buckets = slice_model.n_items();
 
-            let index = (x * n_buckets as f64 / (max_x as f64 + 0.1)) as u32;
+            let index = (indexing_dim * n_buckets as f64 / (indexing_length as f64 + 0.1)) as u32;
             if let Some(item) = model.item(index) {
                 if type_ == DockListType::Saved {
                     let tx1 = tx.clone();
@@ -435,10 +1024,7 @@ impl DockList {
                         glib::clone!(@weak model => move |_self, _drag, _delete_data| {
                             if _delete_data {
                                 model.remove(index);
-                                let tx = tx1.clone();
-                                glib::MainContext::default().spawn_local(async move {
-                                    let _ = tx.send(Event::RefreshFromCache).await;
-                                });
+                                queue_refresh(&tx1);
                             };
                         }),
                     ))) {
@@ -447,17 +1033,29 @@ impl DockList {
 
                     let tx = tx.clone();
                     if let Some(old_handle) = drag_cancel.replace(Some(self_.connect_drag_cancel(
-                        glib::clone!(@weak model => @default-return false, move |_self, _drag, cancel_reason| {
-                            if cancel_reason != gdk::DragCancelReason::UserCancelled {
-                                model.remove(index);
-                                let tx = tx.clone();
-                                glib::MainContext::default().spawn_local(async move {
-                                    let _ = tx.send(Event::RefreshFromCache).await;
-                                });
-                                true
-                            } else  {
-                                false
+                        glib::clone!(@weak dock_list, @weak model => @default-return false, move |_self, _drag, cancel_reason| {
+                            // released away from the dock, released on nothing in the dock the
+                            // icon goes back
+                            if cancel_reason != gdk::DragCancelReason::NoTarget
+                                || !imp::DockList::from_instance(&dock_list).dragged_out.get()
+                            {
+                                return false;
                             }
+                            let id = model
+                                .item(index)
+                                .and_then(|item| item.downcast::<DockObject>().ok())
+                                .and_then(|dock_object| dock_object.get_id());
+                            if let Some(id) = id {
+                                send_event(
+                                    &tx,
+                                    Event::Favorite {
+                                        id,
+                                        favorite: false,
+                                        index: None,
+                                    },
+                                );
+                            }
+                            true
                         }),
                     ))) {
                         glib::signal_handler_disconnect(self_, old_handle);
@@ -469,22 +1067,18 @@ impl DockList {
                             .icon()
                             .unwrap_or_else(|| Icon::for_string("image-missing").expect("Failed to set default icon"));
 
-                        if let Some(default_display) = &Display::default() {
-                            let icon_theme = IconTheme::for_display(default_display);
-                            let paintable_icon = icon_theme.lookup_by_gicon(
-                                &icon,
-                                64,
-                                1,
-                                gtk4::TextDirection::None,
-                                gtk4::IconLookupFlags::empty(),
-                            );
-                            self_.set_icon(Some(&paintable_icon), 32, 32);
+                        if let Some(widget) = self_.widget() {
+                            let paintable = icon_paintable(&icon, 64, &widget);
+                            self_.set_icon(Some(&paintable), 32, 32);
+                            imp::DockList::from_instance(&dock_list)
+                                .drag_paintable
+                                .replace(Some(paintable));
                         }
 
                         // saved app list provides index
                         return match type_ {
                             DockListType::Saved => Some(ContentProvider::for_value(&index.to_value())),
-                            DockListType::Active => app_info.filename().map(|file| ContentProvider::for_value(&file.to_string_lossy().to_value()))
+                            _ => app_info.filename().map(|file| ContentProvider::for_value(&file.to_string_lossy().to_value()))
                         }
                     }
                 }
@@ -494,7 +1088,7 @@ impl DockList {
 
         // TODO investigate why drop does not finish when dropping on some surfaces
         // for now this is a fix that will cancel the drop after 100 ms and not completing.
-        drag_source.connect_drag_begin(|_self, drag| {
+        drag_source.connect_drag_begin(glib::clone!(@weak self as dock_list => move |_self, drag| {
             drag.connect_drop_performed(|_self| {
                 glib::timeout_add_local_once(
                     std::time::Duration::from_millis(100),
@@ -503,7 +1097,15 @@ impl DockList {
                     }),
                 );
             });
-        });
+            if type_ == DockListType::Saved {
+                dock_list.begin_drag_out(drag);
+            }
+        }));
+        // drag-cancel is emitted before drag-end, so the cancel handler still knows whether the
+        // drag was released away from the dock
+        drag_source.connect_drag_end(glib::clone!(@weak self as dock_list => move |_, _, _| {
+            dock_list.end_drag_out();
+        }));
 
         imp.drag_source
             .set(drag_source)
@@ -512,22 +1114,20 @@ impl DockList {
 
     fn setup_factory(&self) {
         let imp = imp::DockList::from_instance(self);
-        let popover_menu_index = &imp.popover_menu_index;
+        let popover_menu_object = &imp.popover_menu_object;
         let factory = SignalListItemFactory::new();
         let model = imp.model.get().expect("Failed to get saved app model.");
         let tx = imp.tx.get().unwrap().clone();
-        let icon_size = imp.config.get().unwrap().get_applet_icon_size();
+        let icon_size = imp.icon_size.get();
         factory.connect_setup(
-            glib::clone!(@weak popover_menu_index, @weak model => move |_, list_item| {
+            glib::clone!(@weak popover_menu_object, @weak model => move |_, list_item| {
                 let dock_item = DockItem::new(tx.clone(), icon_size);
                 dock_item
                     .connect_local("popover-closed", false, move |_| {
-                        if let Some(old_index) = popover_menu_index.replace(None) {
-                            if let Some(item) = model.item(old_index) {
-                                if let Ok(dock_object) = item.downcast::<DockObject>() {
-                                    dock_object.set_popover(false);
-                                    model.items_changed(old_index, 0, 0);
-                                }
+                        if let Some(dock_object) = popover_menu_object.replace(None) {
+                            dock_object.set_popover(false);
+                            if let Some(old_index) = dock_object.position_in(&model) {
+                                model.items_changed(old_index, 0, 0);
                             }
                         }
 
@@ -537,7 +1137,7 @@ impl DockList {
             }),
         );
         factory.connect_bind(
-            glib::clone!(@weak imp.position as position => move |_, list_item| {
+            glib::clone!(@weak imp.position as position, @weak imp.icon_size as icon_size, @weak imp.show_labels as show_labels, @weak imp.hover_previews as hover_previews, @weak imp.animations as animations => move |_, list_item| {
                 let dock_object = list_item
                     .item()
                     .expect("The item has to exist.")
@@ -548,6 +1148,10 @@ impl DockList {
                     .expect("The list item child needs to exist.")
                     .downcast::<DockItem>()
                     .expect("The list item type needs to be `DockItem`");
+                dock_item.set_icon_size(icon_size.get());
+                dock_item.set_show_label(show_labels.get());
+                dock_item.set_hover_previews(hover_previews.get());
+                dock_item.set_animations_enabled(animations.get());
                 dock_item.set_dock_object(&dock_object);
                 dock_item.set_position(position.get());
             }),
@@ -2,12 +2,17 @@
 
 use std::cell::Cell;
 use std::cell::RefCell;
+use std::time::{Duration, Instant};
 
+use crate::custom_launcher::CustomLauncher;
 use crate::utils::BoxedWindowList;
 use gio::DesktopAppInfo;
+use glib::subclass::Signal;
 use glib::{ParamFlags, ParamSpec, Value};
 use gtk4::gdk::glib::ParamSpecBoolean;
 use gtk4::gdk::glib::ParamSpecBoxed;
+use gtk4::gdk::glib::ParamSpecDouble;
+use gtk4::gdk::glib::ParamSpecInt64;
 use gtk4::gdk::glib::ParamSpecObject;
 use gtk4::glib;
 use gtk4::prelude::*;
@@ -19,8 +24,27 @@ use once_cell::sync::Lazy;
 pub struct DockObject {
     pub(super) appinfo: RefCell<Option<DesktopAppInfo>>,
     pub(super) active: RefCell<BoxedWindowList>,
+    pub(super) location: RefCell<super::BoxedLocation>,
     pub(super) saved: Cell<bool>,
     pub(super) popover: Cell<bool>,
+    pub(super) last_activated: Cell<Option<(u32, u32)>>,
+    pub(super) launching: Cell<bool>,
+    pub(super) focused: Cell<bool>,
+    pub(super) missing: Cell<bool>,
+    pub(super) count: Cell<i64>,
+    pub(super) progress: Cell<f64>,
+    pub(super) urgent: Cell<bool>,
+    pub(super) removing: Cell<bool>,
+    pub(super) keep_prompt: Cell<bool>,
+    pub(super) keep_prompt_timeout: RefCell<Option<glib::SourceId>>,
+    // how long the app was focused, see `DockObject::focused_time`
+    pub(super) focused_since: Cell<Option<Instant>>,
+    pub(super) focused_time: Cell<Duration>,
+    pub(super) shown: Cell<bool>,
+    pub(super) recent: Cell<bool>,
+    pub(super) single_window: Cell<bool>,
+    pub(super) custom_launcher: RefCell<Option<CustomLauncher>>,
+    pub(super) launch_timeout: RefCell<Option<glib::SourceId>>,
 }
 
 // The central trait for subclassing a GObject
@@ -33,6 +57,36 @@ impl ObjectSubclass for DockObject {
 
 // Trait shared by all GObjects
 impl ObjectImpl for DockObject {
+    fn signals() -> &'static [Signal] {
+        static SIGNALS: Lazy<Vec<Signal>> = Lazy::new(|| {
+            vec![Signal::builder(
+                // Signal name
+                "launch-failed",
+                // Types of the values which will be sent to the signal handler
+                &[],
+                // Type of the value the signal handler sends back
+                <()>::static_type().into(),
+            )
+            .build()]
+        });
+        SIGNALS.as_ref()
+    }
+
+    fn constructed(&self, obj: &Self::Type) {
+        self.parent_constructed(obj);
+        // no progress until an app reports one
+        self.progress.set(-1.0);
+    }
+
+    fn dispose(&self, _obj: &Self::Type) {
+        if let Some(source_id) = self.launch_timeout.take() {
+            source_id.remove();
+        }
+        if let Some(source_id) = self.keep_prompt_timeout.take() {
+            source_id.remove();
+        }
+    }
+
     fn properties() -> &'static [ParamSpec] {
         static PROPERTIES: Lazy<Vec<ParamSpec>> = Lazy::new(|| {
             vec![
@@ -58,6 +112,13 @@ impl ObjectImpl for DockObject {
                     // The property can be read and written to
                     ParamFlags::READWRITE,
                 ),
+                ParamSpecBoxed::new(
+                    "location",
+                    "location",
+                    "The place shown instead of an app, e.g. the trash",
+                    super::BoxedLocation::static_type(),
+                    ParamFlags::READWRITE,
+                ),
                 ParamSpecBoolean::new(
                     "saved",
                     "saved",
@@ -65,6 +126,66 @@ impl ObjectImpl for DockObject {
                     false,
                     ParamFlags::READWRITE,
                 ),
+                ParamSpecBoolean::new(
+                    "launching",
+                    "launching",
+                    "Indicates whether the app was launched and has no window yet",
+                    false,
+                    ParamFlags::READWRITE,
+                ),
+                ParamSpecBoolean::new(
+                    "focused",
+                    "focused",
+                    "Indicates whether one of the app's windows has keyboard focus",
+                    false,
+                    ParamFlags::READWRITE,
+                ),
+                ParamSpecBoolean::new(
+                    "missing",
+                    "missing",
+                    "Indicates whether the desktop file of the app was removed",
+                    false,
+                    ParamFlags::READWRITE,
+                ),
+                ParamSpecInt64::new(
+                    "count",
+                    "count",
+                    "Badge count the app reported, 0 if no badge is shown",
+                    0,
+                    i64::MAX,
+                    0,
+                    ParamFlags::READWRITE,
+                ),
+                ParamSpecDouble::new(
+                    "progress",
+                    "progress",
+                    "Progress the app reported, negative if no progress is shown",
+                    -1.0,
+                    1.0,
+                    -1.0,
+                    ParamFlags::READWRITE,
+                ),
+                ParamSpecBoolean::new(
+                    "urgent",
+                    "urgent",
+                    "Indicates whether the app asked for attention through its launcher entry",
+                    false,
+                    ParamFlags::READWRITE,
+                ),
+                ParamSpecBoolean::new(
+                    "removing",
+                    "removing",
+                    "Indicates whether the object is animating out before it is removed from its model",
+                    false,
+                    ParamFlags::READWRITE,
+                ),
+                ParamSpecBoolean::new(
+                    "keep-prompt",
+                    "keep-prompt",
+                    "Indicates whether the object of a closed app stays to offer keeping it in the dock",
+                    false,
+                    ParamFlags::READWRITE,
+                ),
                 ParamSpecBoolean::new(
                     "popover",
                     "popover",
@@ -89,6 +210,10 @@ impl ObjectImpl for DockObject {
                 let active = value.get().expect("Value needs to be BoxedWindowList");
                 self.active.replace(active);
             }
+            "location" => {
+                let location = value.get().expect("Value needs to be BoxedLocation");
+                self.location.replace(location);
+            }
             "saved" => {
                 self.saved
                     .replace(value.get().expect("Value needs to be a boolean"));
@@ -97,6 +222,46 @@ impl ObjectImpl for DockObject {
                 self.popover
                     .replace(value.get().expect("Value needs to be a boolean"));
             }
+            "launching" => {
+                self.launching
+                    .replace(value.get().expect("Value needs to be a boolean"));
+            }
+            "focused" => {
+                let focused = value.get().expect("Value needs to be a boolean");
+                match (self.focused.replace(focused), focused) {
+                    (false, true) => self.focused_since.set(Some(Instant::now())),
+                    (true, false) => {
+                        if let Some(since) = self.focused_since.take() {
+                            self.focused_time.set(self.focused_time.get() + since.elapsed());
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            "missing" => {
+                self.missing
+                    .replace(value.get().expect("Value needs to be a boolean"));
+            }
+            "count" => {
+                self.count
+                    .replace(value.get().expect("Value needs to be an i64"));
+            }
+            "progress" => {
+                self.progress
+                    .replace(value.get().expect("Value needs to be an f64"));
+            }
+            "urgent" => {
+                self.urgent
+                    .replace(value.get().expect("Value needs to be a boolean"));
+            }
+            "removing" => {
+                self.removing
+                    .replace(value.get().expect("Value needs to be a boolean"));
+            }
+            "keep-prompt" => {
+                self.keep_prompt
+                    .replace(value.get().expect("Value needs to be a boolean"));
+            }
             _ => unimplemented!(),
         }
     }
@@ -105,8 +270,17 @@ impl ObjectImpl for DockObject {
         match pspec.name() {
             "appinfo" => self.appinfo.borrow().to_value(),
             "active" => self.active.borrow().to_value(),
+            "location" => self.location.borrow().to_value(),
             "saved" => self.saved.get().to_value(),
             "popover" => self.popover.get().to_value(),
+            "launching" => self.launching.get().to_value(),
+            "focused" => self.focused.get().to_value(),
+            "missing" => self.missing.get().to_value(),
+            "count" => self.count.get().to_value(),
+            "progress" => self.progress.get().to_value(),
+            "urgent" => self.urgent.get().to_value(),
+            "removing" => self.removing.get().to_value(),
+            "keep-prompt" => self.keep_prompt.get().to_value(),
             _ => unimplemented!(),
         }
     }
@@ -1,15 +1,22 @@
 // SPDX-License-Identifier: MPL-2.0-only
 
-use crate::utils::BoxedWindowList;
+use crate::custom_launcher::CustomLauncher;
+use crate::desktop_cache;
+use crate::generated_entry;
+use crate::overrides;
+use crate::utils::{record_focus, BoxedWindowList, Item};
 use gio::{DesktopAppInfo, Icon};
 use gtk4::gdk::glib::Object;
 use gtk4::gdk::subclass::prelude::ObjectSubclassExt;
 use gtk4::prelude::*;
-use gtk4::{glib, Image};
+use gtk4::glib;
 use std::path::Path;
+use std::time::Duration;
 
 mod imp;
 
+const LAUNCH_TIMEOUT_SECS: u64 = 15;
+
 glib::wrapper! {
     pub struct DockObject(ObjectSubclass<imp::DockObject>);
 }
@@ -21,19 +28,14 @@ impl DockObject {
     }
 
     pub fn from_app_info_path(path: &str) -> Option<Self> {
-        if let Some(path) = Path::new(path).file_name() {
-            if let Some(path) = path.to_str() {
-                if let Some(appinfo) = gio::DesktopAppInfo::new(path) {
-                    if appinfo.should_show() {
-                        return Some(
-                            Object::new(&[("appinfo", &Some(appinfo)), ("saved", &true)])
-                                .expect("Failed to create `DockObject`."),
-                        );
-                    }
-                }
-            }
-        }
-        None
+        // generated entries are in a subdirectory, so their id isn't the file name
+        let id = generated_entry::desktop_id(Path::new(path))
+            .or_else(|| Some(Path::new(path).file_name()?.to_str()?.to_string()))?;
+        let appinfo = generated_entry::app_info(&id).filter(|appinfo| appinfo.should_show())?;
+        Some(
+            Object::new(&[("appinfo", &Some(appinfo)), ("saved", &true)])
+                .expect("Failed to create `DockObject`."),
+        )
     }
 
     pub fn get_path(&self) -> Option<String> {
@@ -47,27 +49,97 @@ impl DockObject {
         }
     }
 
+    pub fn get_id(&self) -> Option<String> {
+        let imp = imp::DockObject::from_instance(self);
+        if let Some(launcher) = imp.custom_launcher.borrow().as_ref() {
+            return Some(launcher.id.clone());
+        }
+        imp.appinfo
+            .borrow()
+            .as_ref()
+            .and_then(generated_entry::app_info_id)
+    }
+
+    /// A dock object for the trash or a mounted volume.
+    pub fn from_location(location: Location) -> Self {
+        Object::new(&[("location", &BoxedLocation(Some(location)))])
+            .expect("Failed to create `DockObject`.")
+    }
+
+    /// A saved launcher that opens a URL or runs a command.
+    pub fn from_custom_launcher(launcher: CustomLauncher) -> Self {
+        let self_: Self =
+            Object::new(&[("saved", &true)]).expect("Failed to create `DockObject`.");
+        let imp = imp::DockObject::from_instance(&self_);
+        imp.custom_launcher.replace(Some(launcher));
+        self_
+    }
+
+    pub fn custom_launcher(&self) -> Option<CustomLauncher> {
+        let imp = imp::DockObject::from_instance(self);
+        imp.custom_launcher.borrow().clone()
+    }
+
+    pub fn location(&self) -> Option<Location> {
+        let imp = imp::DockObject::from_instance(self);
+        imp.location.borrow().0.clone()
+    }
+
     pub fn get_name(&self) -> Option<String> {
         let imp = imp::DockObject::from_instance(self);
+        if let Some(location) = imp.location.borrow().0.as_ref() {
+            return Some(location.name.clone());
+        }
+        if let Some(launcher) = imp.custom_launcher.borrow().as_ref() {
+            return Some(launcher.name.clone());
+        }
+        if let Some(name) = self.get_id().and_then(|id| overrides::name(&id)) {
+            return Some(name);
+        }
         imp.appinfo.borrow().as_ref().map(|app_info| app_info.name().to_string())
     }
 
-    pub fn get_image(&self) -> gtk4::Image {
+    /// The app name, or the title of the first window for windows without a desktop file and
+    /// items of a single window.
+    pub fn display_name(&self) -> Option<String> {
+        if self.is_single_window() {
+            if let Some(window) = self.property::<BoxedWindowList>("active").0.get(0) {
+                return Some(window.name.clone());
+            }
+        }
+        self.get_name().or_else(|| {
+            self.property::<BoxedWindowList>("active")
+                .0
+                .get(0)
+                .map(|window| window.name.clone())
+        })
+    }
+
+    /// The icon from the overrides file, then the icon of the desktop entry, then the icon
+    /// the compositor reported for the windows, then a generic placeholder.
+    pub fn get_icon(&self) -> Icon {
         let imp = imp::DockObject::from_instance(self);
-        if let Some(app_info) = imp.appinfo.borrow().as_ref() {
-            let image = Image::new();
-            let icon = app_info
-                .icon()
-                .unwrap_or_else(|| Icon::for_string("image-missing").expect("Failed to set default icon"));
-            image.set_from_gicon(&icon);
-            image.set_tooltip_text(None);
-            image
-        } else {
-            eprintln!("failed to load image");
-            let image = Image::new();
-            image.set_tooltip_text(None);
-            image
+        if let Some(location) = imp.location.borrow().0.as_ref() {
+            return location.icon.clone();
+        }
+        if let Some(launcher) = imp.custom_launcher.borrow().as_ref() {
+            return launcher.icon();
+        }
+        if let Some(icon) = self.get_id().and_then(|id| overrides::icon(&id)) {
+            return icon;
         }
+        if let Some(icon) = imp.appinfo.borrow().as_ref().and_then(|app_info| app_info.icon()) {
+            return icon;
+        }
+        imp.active
+            .borrow()
+            .0
+            .iter()
+            .filter(|item| !item.icon.is_empty())
+            .find_map(|item| Icon::for_string(&item.icon).ok())
+            .unwrap_or_else(|| {
+                Icon::for_string("application-x-executable").expect("Failed to set default icon")
+            })
     }
 
     pub fn set_saved(&self, is_saved: bool) {
@@ -75,48 +147,232 @@ impl DockObject {
         imp.saved.replace(is_saved);
     }
 
+    /// Resolves the windows to a desktop entry. Windows seen for the first time are resolved
+    /// in the background and show the window icon until the refresh that follows.
     pub fn from_search_results(results: BoxedWindowList) -> Self {
-        let appinfo = if let Some(first) = results.0.get(0) {
-            xdg::BaseDirectories::new()
-                .expect("could not access XDG Base directory")
-                .get_data_dirs()
-                .iter_mut()
-                .filter_map(|xdg_data_path| {
-                    xdg_data_path.push("applications");
-                    std::fs::read_dir(xdg_data_path).ok()
-                })
-                .flatten()
-                .filter_map(|dir_entry| {
-                    if let Ok(dir_entry) = dir_entry {
-                        if let Some(path) = dir_entry.path().file_name() {
-                            if let Some(path) = path.to_str() {
-                                if let Some(app_info) = gio::DesktopAppInfo::new(path) {
-                                    if app_info.should_show()
-                                        && first.description.as_str() == app_info.name().as_str()
-                                    {
-                                        return Some(app_info);
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    None
-                })
-                .next()
-        } else {
-            None
-        };
+        let appinfo = results.0.get(0).and_then(desktop_cache::lookup);
         // dbg!(&appinfo);
-        Object::new(&[("appinfo", &appinfo), ("active", &results)])
+        let focused = results.focused().is_some();
+        Object::new(&[("appinfo", &appinfo), ("active", &results), ("focused", &focused)])
             .expect("Failed to create `DockObject`.")
     }
 
+    /// The item of a single window, when every window gets its own item.
+    pub fn from_window(window: Item) -> Self {
+        let self_ = Self::from_search_results(BoxedWindowList(vec![window]));
+        let imp = imp::DockObject::from_instance(&self_);
+        imp.single_window.set(true);
+        self_
+    }
+
+    pub fn is_single_window(&self) -> bool {
+        let imp = imp::DockObject::from_instance(self);
+        imp.single_window.get()
+    }
+
+    /// The window of this app that was last focused from the dock.
+    pub fn last_activated(&self) -> Option<(u32, u32)> {
+        let imp = imp::DockObject::from_instance(self);
+        imp.last_activated.get()
+    }
+
+    /// Forgets the window last focused from the dock, e.g. when its entity became invalid.
+    pub fn clear_last_activated(&self) {
+        let imp = imp::DockObject::from_instance(self);
+        imp.last_activated.set(None);
+    }
+
+    /// Also records the focus right away, so clicks that follow before the daemon reports it
+    /// start from this window.
+    pub fn set_last_activated(&self, entity: (u32, u32)) {
+        let imp = imp::DockObject::from_instance(self);
+        imp.last_activated.set(Some(entity));
+        record_focus(entity);
+    }
+
+    /// The window that was focused most recently, the one last focused from the dock or the
+    /// first window if that isn't known.
+    pub fn last_activated_window(&self) -> Option<Item> {
+        let active = self.property::<BoxedWindowList>("active");
+        active
+            .most_recent()
+            .or_else(|| {
+                self.last_activated()
+                    .and_then(|entity| active.0.iter().find(|w| w.entity == entity))
+            })
+            .or_else(|| active.0.get(0))
+            .cloned()
+    }
+
+    /// The window after (or before) the most recently focused one, wrapping around.
+    pub fn next_window(&self, forward: bool) -> Option<(u32, u32)> {
+        let active = self.property::<BoxedWindowList>("active").0;
+        let n = active.len();
+        if n == 0 {
+            return None;
+        }
+        let cur = self
+            .last_activated_window()
+            .and_then(|window| active.iter().position(|w| w.entity == window.entity))
+            .unwrap_or(0);
+        let next = if forward { (cur + 1) % n } else { (cur + n - 1) % n };
+        Some(active[next].entity)
+    }
+
+    /// Marks the app as launching until one of its windows appears or the launch times out.
+    pub fn set_launching(&self, launching: bool) {
+        let imp = imp::DockObject::from_instance(self);
+        if let Some(source_id) = imp.launch_timeout.take() {
+            source_id.remove();
+        }
+        if launching {
+            let source_id = glib::timeout_add_local_once(
+                Duration::from_secs(LAUNCH_TIMEOUT_SECS),
+                glib::clone!(@weak self as self_ => move || {
+                    let imp = imp::DockObject::from_instance(&self_);
+                    imp.launch_timeout.replace(None);
+                    self_.set_property("launching", false);
+                }),
+            );
+            imp.launch_timeout.replace(Some(source_id));
+        }
+        if self.property::<bool>("launching") != launching {
+            self.set_property("launching", launching);
+        }
+    }
+
+    /// Notifies the widgets showing this object that launching it failed.
+    pub fn emit_launch_failed(&self) {
+        self.emit_by_name::<()>("launch-failed", &[]);
+    }
+
+    /// Loads the desktop file of the app again after the installed apps changed.
+    /// The old app info is kept if the file is gone, so the app can still be unpinned.
+    pub fn refresh_app_info(&self) {
+        let id = match self.get_id() {
+            Some(id) if self.custom_launcher().is_none() => id,
+            _ => return,
+        };
+        let app_info = generated_entry::app_info(&id);
+        let missing = app_info.is_none();
+        if let Some(app_info) = app_info {
+            self.set_property("appinfo", Some(app_info));
+        }
+        if self.property::<bool>("missing") != missing {
+            self.set_property("missing", missing);
+        }
+    }
+
+    /// Whether the app can be pinned. Apps with a hidden desktop entry can't be, users would
+    /// not find them in the app library, but saved apps can always be unpinned.
+    pub fn can_pin(&self) -> bool {
+        let imp = imp::DockObject::from_instance(self);
+        self.property::<bool>("saved")
+            || imp
+                .appinfo
+                .borrow()
+                .as_ref()
+                .map(|app_info| app_info.should_show())
+                .unwrap_or_default()
+    }
+
+    /// The window a desktop entry can be generated for, if the app has none.
+    pub fn pinnable_window(&self) -> Option<Item> {
+        let imp = imp::DockObject::from_instance(self);
+        if imp.appinfo.borrow().is_some() || imp.custom_launcher.borrow().is_some() {
+            return None;
+        }
+        imp.active
+            .borrow()
+            .0
+            .iter()
+            .find(|window| window.exe().is_some())
+            .cloned()
+    }
+
+    /// Whether the app can be launched, which isn't the case once its desktop file is gone.
+    pub fn can_launch(&self) -> bool {
+        self.get_id().is_some() && !self.property::<bool>("missing")
+    }
+
+    /// A recently launched app that is neither saved nor running.
+    pub fn from_recent(appinfo: DesktopAppInfo) -> Self {
+        let self_: Self = Object::new(&[("appinfo", &Some(appinfo))])
+            .expect("Failed to create `DockObject`.");
+        let imp = imp::DockObject::from_instance(&self_);
+        imp.recent.set(true);
+        self_
+    }
+
+    pub fn is_recent(&self) -> bool {
+        let imp = imp::DockObject::from_instance(self);
+        imp.recent.get()
+    }
+
+    /// Returns true the first time the object is shown, so only new items animate in.
+    pub fn mark_shown(&self) -> bool {
+        let imp = imp::DockObject::from_instance(self);
+        !imp.shown.replace(true)
+    }
+
+    /// Position of this object in `model`, if it is there.
+    pub fn position_in(&self, model: &gio::ListStore) -> Option<u32> {
+        let mut i: u32 = 0;
+        while let Some(item) = model.item(i) {
+            if item.downcast_ref::<DockObject>() == Some(self) {
+                return Some(i);
+            }
+            i += 1;
+        }
+        None
+    }
+
+    /// How long the windows of the app had keyboard focus while the object listed them.
+    pub fn focused_time(&self) -> Duration {
+        let imp = imp::DockObject::from_instance(self);
+        let current = imp
+            .focused_since
+            .get()
+            .map(|since| since.elapsed())
+            .unwrap_or_default();
+        imp.focused_time.get() + current
+    }
+
+    /// Keeps the source that ends the keep in dock prompt, replacing the previous one.
+    pub fn set_keep_prompt_timeout(&self, source_id: Option<glib::SourceId>) {
+        let imp = imp::DockObject::from_instance(self);
+        if let Some(old) = imp.keep_prompt_timeout.replace(source_id) {
+            old.remove();
+        }
+    }
+
+    /// Forgets the source that ends the keep in dock prompt without removing it, for the
+    /// source itself.
+    pub fn forget_keep_prompt_timeout(&self) {
+        let imp = imp::DockObject::from_instance(self);
+        drop(imp.keep_prompt_timeout.take());
+    }
+
     pub fn set_popover(&self, b: bool) {
         let imp = imp::DockObject::from_instance(self);
         imp.popover.replace(b);
     }
 }
 
+/// A place in the system section of the dock.
+#[derive(Clone, Debug)]
+pub struct Location {
+    pub uri: String,
+    pub name: String,
+    pub icon: Icon,
+    /// the mount of a removable volume, `None` for the trash
+    pub mount: Option<gio::Mount>,
+}
+
+#[derive(Clone, Debug, Default, glib::Boxed)]
+#[boxed_type(name = "BoxedLocation")]
+pub struct BoxedLocation(pub Option<Location>);
+
 #[derive(Clone, Debug, Default, glib::Boxed)]
 #[boxed_type(name = "BoxedDockObject")]
 pub struct BoxedDockObject(pub Option<DockObject>);
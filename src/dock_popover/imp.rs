@@ -1,13 +1,14 @@
 // SPDX-License-Identifier: MPL-2.0-only
 
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
 
 use glib::subclass::Signal;
-use gtk4::glib;
 use gtk4::prelude::*;
+use gtk4::{gdk, gio, glib};
 use gtk4::subclass::prelude::*;
-use gtk4::{Box, Button, ListBox, Revealer};
+use gtk4::{Box, Button, CustomFilter, FilterListModel, Label, ListBox, Revealer};
 use once_cell::sync::Lazy;
 use once_cell::sync::OnceCell;
 use tokio::sync::mpsc::Sender;
@@ -27,6 +28,25 @@ pub struct DockPopover {
     //TODO figure out how to use lifetimes with glib::wrapper! macro
     pub dock_object: Rc<RefCell<Option<DockObject>>>,
     pub tx: OnceCell<Sender<Event>>,
+    pub previews: Rc<RefCell<HashMap<(u32, u32), gdk::Texture>>>,
+    pub previews_unsupported: Cell<bool>,
+    pub preview_refresh: RefCell<Option<glib::SourceId>>,
+    pub window_model: OnceCell<gio::ListStore>,
+    pub filter: OnceCell<CustomFilter>,
+    pub filter_model: OnceCell<FilterListModel>,
+    pub filter_text: RefCell<String>,
+    pub filter_label: RefCell<Option<Label>>,
+    pub title_labels: RefCell<HashMap<(u32, u32), (Label, String)>>,
+    pub row_revealers: RefCell<HashMap<(u32, u32), Revealer>>,
+    /// windows that are gone, kept in the list until their rows collapsed
+    pub closing_rows: RefCell<HashSet<(u32, u32)>>,
+    /// rows are only revealed with an animation once the list was built
+    pub animate_rows: Cell<bool>,
+    pub grouped: Cell<bool>,
+    pub last_workspace: Cell<u32>,
+    /// kept by window, so the selection survives the rows being rebuilt
+    pub selected_entity: Cell<Option<(u32, u32)>>,
+    pub active_handler: RefCell<Option<(DockObject, glib::SignalHandlerId)>>,
 }
 
 #[glib::object_subclass]
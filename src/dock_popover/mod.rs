@@ -4,14 +4,32 @@ use cascade::cascade;
 use gio::DesktopAppInfo;
 use gtk4::gdk::pango::EllipsizeMode;
 use gtk4::subclass::prelude::*;
+use gtk4::gdk::{Key, ModifierType};
 use gtk4::{gdk, gio, glib};
 use gtk4::{prelude::*, Label};
-use gtk4::{Box, Button, Image, ListBox, Orientation};
+use gtk4::{
+    Align, Box, Button, CustomFilter, EventControllerKey, FilterChange, FilterListModel,
+    Inhibit, ListBox, ListBoxRow, MenuButton, Orientation, Picture, PropagationPhase,
+    Revealer, RevealerTransitionType, Separator,
+};
+use std::cell::RefCell;
+use std::ops::Range;
+use std::time::Duration;
 use tokio::sync::mpsc::Sender;
 
+use crate::dock_item::REVEAL_DURATION_MS;
 use crate::dock_object::DockObject;
-use crate::utils::BoxedWindowList;
-use crate::utils::Event;
+use crate::fl;
+use crate::utils::{send_event, Event, Item};
+use crate::utils::{
+    current_workspace, daemon_supports, icon_paintable, sort_windows, window_preview,
+    BoxedWindowList,
+};
+use crate::window_object::{update_window_model, WindowObject};
+
+const PREVIEW_WIDTH: i32 = 160;
+const PREVIEW_HEIGHT: i32 = 90;
+const PREVIEW_REFRESH_SECS: u64 = 5;
 
 mod imp;
 
@@ -34,12 +52,33 @@ impl DockPopover {
     pub fn set_dock_object(&self, dock_object: &DockObject, update_layout: bool) {
         let imp = imp::DockPopover::from_instance(self);
         imp.dock_object.replace(Some(dock_object.clone()));
+        // the rows follow the windows of the app, the menu hides once they are all gone
+        let handler = dock_object.connect_notify_local(
+            Some("active"),
+            glib::clone!(@weak self as self_ => move |dock_object, _| {
+                if !dock_object.property::<bool>("popover") {
+                    return;
+                }
+                if dock_object.property::<BoxedWindowList>("active").0.is_empty() {
+                    self_.emit_hide();
+                } else if !self_.update_windows() {
+                    self_.update_layout();
+                }
+            }),
+        );
+        if let Some((old_object, old_handler)) =
+            imp.active_handler.replace(Some((dock_object.clone(), handler)))
+        {
+            old_object.disconnect(old_handler);
+        }
         if update_layout {
             self.update_layout();
         }
     }
 
     pub fn update_layout(&self) {
+        // rebuilding the rows takes the focus from them
+        let had_focus = self.focus_in_window_list();
         self.reset_menu();
         cascade! {
             &self;
@@ -52,6 +91,38 @@ impl DockPopover {
         let dock_object = imp.dock_object.borrow();
         let menu_handle = imp.menu_handle.borrow();
         if let Some(dock_object) = dock_object.as_ref() {
+            // the item of a single window only offers what applies to that window
+            let single_window = dock_object.is_single_window();
+            // desktop file actions, e.g. "New Private Window"
+            if let Some(app_info) = dock_object
+                .property::<Option<DesktopAppInfo>>("appinfo")
+                .filter(|_| !single_window)
+            {
+                let actions = app_info.list_actions();
+                if !actions.is_empty() {
+                    let actions_container = cascade! {
+                        Box::new(Orientation::Vertical, 4);
+                        ..add_css_class("popover_menu");
+                    };
+                    for action in actions {
+                        let action_item = cascade! {
+                            Button::with_label(&app_info.action_name(&action));
+                            ..add_css_class("popover_menu");
+                        };
+                        action_item.connect_clicked(
+                            glib::clone!(@weak self as self_, @weak app_info => move |_| {
+                                let context = gdk::Display::default().unwrap().app_launch_context();
+                                app_info.launch_action(&action, Some(&context));
+                                self_.emit_hide();
+                            }),
+                        );
+                        actions_container.append(&action_item);
+                    }
+                    menu_handle.append(&actions_container);
+                    menu_handle.append(&Separator::new(Orientation::Horizontal));
+                }
+            }
+
             let all_windows_item_container = cascade! {
                 Box::new(Orientation::Vertical, 4);
             };
@@ -59,42 +130,78 @@ impl DockPopover {
             let window_list = dock_object.property::<BoxedWindowList>("active");
             if window_list.0.is_empty() {
                 all_windows_item_container.hide();
+                imp.filter_label.replace(None);
             } else {
+                // the old list would otherwise keep building rows for the shared model
+                imp.window_list
+                    .borrow()
+                    .bind_model(None::<&gio::ListModel>, None);
                 let window_listbox = cascade! {
                     ListBox::new();
                     ..set_activate_on_single_click(true);
                     ..add_css_class("popover_menu");
                 };
+                let filter_label = cascade! {
+                    Label::new(None);
+                    ..set_halign(Align::Start);
+                    ..set_margin_start(4);
+                    ..add_css_class("dock_popover_filter");
+                };
+                all_windows_item_container.append(&filter_label);
+                imp.filter_label.replace(Some(filter_label));
                 all_windows_item_container.append(&window_listbox);
-                for w in window_list.0 {
-                    let window_box = cascade! {
-                        Box::new(Orientation::Vertical, 4);
-                        ..add_css_class("dock_item");
-                    };
-                    window_listbox.append(&window_box);
-
-                    let window_title = cascade! {
-                        Label::new(Some(w.name.as_str()));
-                        ..set_margin_start(4);
-                        ..set_margin_end(4);
-                        ..set_margin_top(4);
-                        ..set_margin_bottom(4);
-                        ..set_wrap(true);
-                        ..set_max_width_chars(20);
-                        ..set_ellipsize(EllipsizeMode::End);
-                        ..add_css_class("title-4");
-                        ..add_css_class("dock_popover_title");
-                    };
-
-                    let window_image = cascade! {
-                        //TODO fill with image of window
-                        Image::from_pixbuf(None);
-                    };
-                    window_box.append(&window_image);
-                    window_box.append(&window_title);
+                let (windows, grouped) = sort_windows(window_list.0);
+                imp.grouped.set(grouped);
+                if grouped {
+                    window_listbox.set_header_func(
+                        glib::clone!(@weak self as self_ => move |row, before| {
+                            let workspace_of =
+                                |row: &ListBoxRow| self_.row_window(row).and_then(|w| w.workspace());
+                            let workspace = workspace_of(row);
+                            if before.is_some() && before.map(workspace_of) == Some(workspace) {
+                                row.set_header(None::<&gtk4::Widget>);
+                                return;
+                            }
+                            let title = match workspace {
+                                Some(ws) => fl!("workspace", number = ws),
+                                None => fl!("other-workspace"),
+                            };
+                            row.set_header(Some(&cascade! {
+                                Label::new(Some(&title));
+                                ..set_halign(Align::Start);
+                                ..set_margin_start(4);
+                                ..set_margin_top(4);
+                                ..add_css_class("dock_popover_workspace");
+                            }));
+                        }),
+                    );
                 }
+                imp.last_workspace.set(last_workspace(&windows));
+                // rows follow the filtered model, so the filter survives the window list changing
+                imp.animate_rows.set(false);
+                update_window_model(imp.window_model.get().unwrap(), &windows, imp.tx.get().unwrap());
+                imp.title_labels.borrow_mut().clear();
+                imp.row_revealers.borrow_mut().clear();
+                imp.closing_rows.borrow_mut().clear();
+                window_listbox.bind_model(
+                    imp.filter_model.get(),
+                    Some(std::boxed::Box::new(glib::clone!(@weak self as self_ => @default-panic, move |item| {
+                        let window = item.downcast_ref::<WindowObject>().unwrap();
+                        self_.window_row(window).upcast()
+                    }))),
+                );
+                // rows are deselected while the model changes, which must not forget the selection
+                window_listbox.connect_row_selected(glib::clone!(@weak self as self_ => move |_, row| {
+                    if let Some(w) = row.and_then(|row| self_.row_window(row)) {
+                        imp::DockPopover::from_instance(&self_).selected_entity.set(Some(w.entity));
+                    }
+                }));
+                imp.animate_rows.set(true);
+                self.update_filter();
+                self.schedule_preview_refresh();
                 // imp.all_windows_item_revealer.replace(window_list_revealer);
                 imp.window_list.replace(window_listbox);
+                self.restore_selection(had_focus);
             }
 
             let launch_item_container = cascade! {
@@ -105,16 +212,33 @@ impl DockPopover {
             menu_handle.append(&launch_item_container);
 
             let launch_new_item = cascade! {
-                Button::with_label("New Window");
+                Button::with_label(&fl!("new-window"));
                 ..add_css_class("popover_menu");
+                ..set_visible(!single_window);
             };
             launch_item_container.append(&launch_new_item);
             imp.launch_new_item.replace(launch_new_item);
 
+            let favorite_label = if dock_object.property::<bool>("saved") {
+                fl!("remove-favorite")
+            } else if dock_object.pinnable_window().is_some() {
+                fl!("pin-anyway")
+            } else {
+                fl!("add-favorite")
+            };
             let favorite_item = cascade! {
-                Button::with_label(if dock_object.property::<bool>("saved") {"Remove from Favorites"} else {"Add to Favorites"});
+                Button::with_label(&favorite_label);
                 ..add_css_class("popover_menu");
+                // windows without a desktop entry can only be saved if their executable is known
+                ..set_sensitive(
+                    (dock_object.get_id().is_some() && dock_object.can_pin())
+                        || dock_object.pinnable_window().is_some()
+                );
             };
+            if dock_object.get_id().is_some() && !dock_object.can_pin() {
+                favorite_item.set_tooltip_text(Some(&fl!("hidden-app")));
+            }
+            favorite_item.set_visible(!single_window);
             menu_handle.append(&favorite_item);
             imp.favorite_item.replace(favorite_item);
 
@@ -122,14 +246,14 @@ impl DockPopover {
 
             if window_list.0.len() > 1 {
                 let quit_all_item = cascade! {
-                    Button::with_label(format!("Quit {} Windows", window_list.0.len()).as_str());
+                    Button::with_label(&fl!("quit-all-windows"));
                     ..add_css_class("popover_menu");
                 };
                 menu_handle.append(&quit_all_item);
                 imp.quit_all_item.replace(quit_all_item);
             } else {
                 let quit_all_item = cascade! {
-                    Button::with_label("Quit");
+                    Button::with_label(&fl!("quit"));
                     ..add_css_class("popover_menu");
                 };
                 menu_handle.append(&quit_all_item);
@@ -143,6 +267,545 @@ impl DockPopover {
         }
     }
 
+    /// Updates the rows in place after the windows of the app changed, so rows keep their
+    /// hover and selection. Returns false if the list has to be built again.
+    fn update_windows(&self) -> bool {
+        let imp = imp::DockPopover::from_instance(self);
+        let dock_object = match imp.dock_object.borrow().clone() {
+            Some(dock_object) => dock_object,
+            None => return false,
+        };
+        if imp.filter_label.borrow().is_none() {
+            return false;
+        }
+        let (mut windows, grouped) =
+            sort_windows(dock_object.property::<BoxedWindowList>("active").0);
+        if grouped != imp.grouped.get() {
+            return false;
+        }
+        imp.last_workspace.set(last_workspace(&windows));
+        let had_focus = self.focus_in_window_list();
+
+        // rows of windows that are gone collapse before they are removed
+        let window_model = imp.window_model.get().unwrap();
+        let mut i = 0;
+        while let Some(window) = window_model
+            .item(i)
+            .and_then(|item| item.downcast::<WindowObject>().ok())
+            .and_then(|window| window.item())
+        {
+            if !windows.iter().any(|w| w.entity == window.entity) {
+                if imp.closing_rows.borrow_mut().insert(window.entity) {
+                    if let Some(revealer) = imp.row_revealers.borrow().get(&window.entity) {
+                        revealer.set_reveal_child(false);
+                    }
+                    glib::timeout_add_local_once(
+                        Duration::from_millis(REVEAL_DURATION_MS.into()),
+                        glib::clone!(@weak self as self_ => move || {
+                            let imp = imp::DockPopover::from_instance(&self_);
+                            imp.closing_rows.borrow_mut().remove(&window.entity);
+                            self_.update_windows();
+                        }),
+                    );
+                }
+                if imp.closing_rows.borrow().contains(&window.entity) {
+                    windows.insert((i as usize).min(windows.len()), window);
+                }
+            }
+            i += 1;
+        }
+        update_window_model(window_model, &windows, imp.tx.get().unwrap());
+        if !imp.filter_text.borrow().is_empty() {
+            // titles may have changed whether they match
+            imp.filter.get().unwrap().changed(FilterChange::Different);
+        }
+        if grouped {
+            imp.window_list.borrow().invalidate_headers();
+        }
+        self.restore_selection(had_focus);
+        true
+    }
+
+    // position of the window in the filtered list
+    fn filtered_index(&self, entity: (u32, u32)) -> Option<u32> {
+        let imp = imp::DockPopover::from_instance(self);
+        let filter_model = imp.filter_model.get()?;
+        (0..filter_model.n_items()).find(|&i| {
+            filter_model
+                .item(i)
+                .and_then(|item| item.downcast::<WindowObject>().ok())
+                .and_then(|w| w.entity())
+                == Some(entity)
+        })
+    }
+
+    // the selected window, if the filter shows it
+    fn selected_window(&self) -> Option<(u32, u32)> {
+        let imp = imp::DockPopover::from_instance(self);
+        imp.selected_entity
+            .get()
+            .filter(|entity| self.filtered_index(*entity).is_some())
+    }
+
+    fn select_index(&self, index: u32, grab_focus: bool) -> bool {
+        let imp = imp::DockPopover::from_instance(self);
+        let window_list = imp.window_list.borrow();
+        match window_list.row_at_index(index as i32) {
+            Some(row) => {
+                window_list.select_row(Some(&row));
+                if grab_focus {
+                    row.grab_focus();
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Selects the row of the selected window again after the rows changed, or the row of the
+    /// window a click on the item would focus. The first row is selected if the filter hides it.
+    fn restore_selection(&self, grab_focus: bool) {
+        let imp = imp::DockPopover::from_instance(self);
+        let entity = imp.selected_entity.get().or_else(|| {
+            imp.dock_object
+                .borrow()
+                .as_ref()?
+                .last_activated_window()
+                .map(|window| window.entity)
+        });
+        let index = entity
+            .and_then(|entity| self.filtered_index(entity))
+            .unwrap_or(0);
+        self.select_index(index, grab_focus);
+    }
+
+    // returns false at either end of the list, so the focus can move on to the buttons
+    fn move_selection(&self, forward: bool) -> bool {
+        let index = match (self.selected_window().and_then(|e| self.filtered_index(e)), forward) {
+            (None, _) => 0,
+            (Some(index), true) => index + 1,
+            (Some(0), false) => return false,
+            (Some(index), false) => index - 1,
+        };
+        self.select_index(index, true)
+    }
+
+    // the focused widget, if it is in the window list
+    fn window_list_focus(&self) -> Option<gtk4::Widget> {
+        let imp = imp::DockPopover::from_instance(self);
+        let window_list = imp.window_list.borrow();
+        self.root()
+            .and_then(|root| root.focus())
+            .filter(|focus| *focus == *window_list || focus.is_ancestor(&*window_list))
+    }
+
+    fn focus_in_window_list(&self) -> bool {
+        self.window_list_focus().is_some()
+    }
+
+    fn window_row(&self, window: &WindowObject) -> ListBoxRow {
+        let imp = imp::DockPopover::from_instance(self);
+        let w = window.item().unwrap();
+        let last_workspace = imp.last_workspace.get();
+        let window_box = cascade! {
+            Box::new(Orientation::Vertical, 4);
+            ..add_css_class("dock_item");
+        };
+        let window_title = cascade! {
+            Label::new(Some(w.name.as_str()));
+            ..set_margin_start(4);
+            ..set_margin_end(4);
+            ..set_margin_top(4);
+            ..set_margin_bottom(4);
+            ..set_wrap(true);
+            ..set_max_width_chars(20);
+            ..set_ellipsize(EllipsizeMode::End);
+            ..add_css_class("title-4");
+            ..add_css_class("dock_popover_title");
+        };
+        window_title.set_markup(&highlight_match(&w.name, &imp.filter_text.borrow()));
+        // activating the row still focuses the window, which unminimizes it
+        if w.minimized {
+            window_title.add_css_class("minimized");
+        }
+        imp.title_labels
+            .borrow_mut()
+            .insert(w.entity, (window_title.clone(), w.name.clone()));
+        // screen readers announce the title instead of the highlighted markup
+        let row = ListBoxRow::new();
+        row.update_property(&[(gtk4::AccessibleProperty::Label, &w.name)]);
+        // the window a click on the dock item would focus
+        let most_recent = imp.dock_object.borrow().as_ref().and_then(|dock_object| {
+            dock_object.last_activated_window().map(|window| window.entity)
+        });
+        if most_recent == Some(w.entity) {
+            row.add_css_class("last-focused");
+        }
+        // the title follows the window, e.g. a browser switching tabs
+        let handlers = ["title", "minimized"].map(|property| {
+            window.connect_notify_local(
+                Some(property),
+                glib::clone!(@weak self as self_, @weak window_title, @weak row => move |window, _| {
+                    self_.update_row_title(&window_title, &row, window);
+                }),
+            )
+        });
+        let handlers = RefCell::new(Some(handlers));
+        window_title.connect_destroy(glib::clone!(@weak window => move |_| {
+            for handler in handlers.borrow_mut().take().into_iter().flatten() {
+                window.disconnect(handler);
+            }
+        }));
+
+        let window_image = cascade! {
+            Picture::new();
+            ..set_can_shrink(true);
+            ..set_size_request(PREVIEW_WIDTH, PREVIEW_HEIGHT);
+        };
+        let close_button = cascade! {
+            Button::from_icon_name("window-close-symbolic");
+            ..set_valign(Align::Center);
+            ..add_css_class("flat");
+            ..add_css_class("dock_popover_close");
+            ..update_property(&[(
+                gtk4::AccessibleProperty::Label,
+                &fl!("close-window", window = w.name.clone()),
+            )]);
+        };
+        let entity = w.entity;
+        close_button.connect_clicked(glib::clone!(@weak self as self_ => move |_| {
+            self_.close_window_row(entity);
+        }));
+        let title_box = cascade! {
+            Box::new(Orientation::Horizontal, 4);
+        };
+        window_title.set_hexpand(true);
+        title_box.append(&window_title);
+        title_box.append(&self.window_menu_button(entity, &w.name, last_workspace));
+        title_box.append(&close_button);
+
+        window_box.append(&window_image);
+        window_box.append(&title_box);
+        self.set_preview(&window_image, w.entity);
+
+        let revealer = cascade! {
+            Revealer::new();
+            ..set_transition_type(RevealerTransitionType::SlideDown);
+            ..set_transition_duration(REVEAL_DURATION_MS);
+            ..set_child(Some(&window_box));
+        };
+        if imp.animate_rows.get() {
+            // the row has to be mapped for the transition to run
+            glib::idle_add_local_once(glib::clone!(@weak revealer => move || {
+                revealer.set_reveal_child(true);
+            }));
+        } else {
+            revealer.set_reveal_child(true);
+        }
+        imp.row_revealers.borrow_mut().insert(w.entity, revealer.clone());
+        row.set_child(Some(&revealer));
+        row
+    }
+
+    fn update_row_title(&self, label: &Label, row: &ListBoxRow, window: &WindowObject) {
+        let imp = imp::DockPopover::from_instance(self);
+        let w = match window.item() {
+            Some(w) => w,
+            None => return,
+        };
+        label.set_markup(&highlight_match(&w.name, &imp.filter_text.borrow()));
+        row.update_property(&[(gtk4::AccessibleProperty::Label, &w.name)]);
+        if w.minimized {
+            label.add_css_class("minimized");
+        } else {
+            label.remove_css_class("minimized");
+        }
+        if let Some((_, title)) = imp.title_labels.borrow_mut().get_mut(&w.entity) {
+            *title = w.name;
+        }
+    }
+
+    // the window shown in `row` of the filtered list
+    fn row_window(&self, row: &ListBoxRow) -> Option<Item> {
+        let imp = imp::DockPopover::from_instance(self);
+        let index = u32::try_from(row.index()).ok()?;
+        imp.filter_model
+            .get()?
+            .item(index)?
+            .downcast::<WindowObject>()
+            .ok()?
+            .item()
+    }
+
+    fn activate_window(&self, entity: (u32, u32)) {
+        let imp = imp::DockPopover::from_instance(self);
+        if let Some(dock_object) = imp.dock_object.borrow().as_ref() {
+            dock_object.set_last_activated(entity);
+        }
+        send_event(imp.tx.get().unwrap(), Event::Activate(entity));
+        self.emit_hide();
+    }
+
+    // typing filters the window list, Escape clears the filter. In the window list or while
+    // filtering, Up and Down move the selection, Enter focuses the selected window and Delete or
+    // Ctrl+W closes it.
+    fn setup_type_ahead(&self) {
+        let controller = EventControllerKey::new();
+        // the popover closes on Escape, so the filter has to see it first
+        controller.set_propagation_phase(PropagationPhase::Capture);
+        controller.connect_key_pressed(glib::clone!(@weak self as self_ => @default-return Inhibit(false), move |_, key, _, modifier| {
+            let imp = imp::DockPopover::from_instance(&self_);
+            let mut filter = imp.filter_text.borrow().clone();
+            // the buttons of a row keep their keys
+            let in_list = !filter.is_empty()
+                || self_
+                    .window_list_focus()
+                    .map(|focus| focus.is::<ListBoxRow>())
+                    .unwrap_or_default();
+            match key {
+                Key::Escape if !filter.is_empty() => filter.clear(),
+                Key::BackSpace if !filter.is_empty() => {
+                    filter.pop();
+                }
+                Key::Up | Key::Down if in_list => {
+                    return Inhibit(self_.move_selection(key == Key::Down));
+                }
+                Key::Return | Key::KP_Enter if in_list => {
+                    if let Some(entity) = self_.selected_window() {
+                        self_.activate_window(entity);
+                    }
+                    return Inhibit(true);
+                }
+                Key::Delete | Key::KP_Delete if in_list => {
+                    self_.close_selected();
+                    return Inhibit(true);
+                }
+                Key::w | Key::W if in_list && modifier.contains(ModifierType::CONTROL_MASK) => {
+                    self_.close_selected();
+                    return Inhibit(true);
+                }
+                _ if modifier.intersects(
+                    ModifierType::CONTROL_MASK | ModifierType::ALT_MASK | ModifierType::SUPER_MASK,
+                ) => return Inhibit(false),
+                _ => match key.to_unicode() {
+                    // space only filters once typing started, it activates the focused row otherwise
+                    Some(' ') if filter.is_empty() => return Inhibit(false),
+                    Some(c) if !c.is_control() && imp.filter_model.get().is_some() => filter.push(c),
+                    _ => return Inhibit(false),
+                },
+            }
+            self_.set_filter_text(filter);
+            Inhibit(true)
+        }));
+        self.add_controller(&controller);
+    }
+
+    fn set_filter_text(&self, filter: String) {
+        let imp = imp::DockPopover::from_instance(self);
+        if *imp.filter_text.borrow() == filter {
+            return;
+        }
+        imp.filter_text.replace(filter);
+        imp.filter.get().unwrap().changed(FilterChange::Different);
+        self.update_filter();
+        // the selection moves to the top match if the filter hides the selected window
+        self.restore_selection(false);
+    }
+
+    /// Shows all windows again and forgets the selected row, called when the popover is closed.
+    pub fn clear_filter(&self) {
+        self.set_filter_text(String::new());
+        let imp = imp::DockPopover::from_instance(self);
+        imp.selected_entity.set(None);
+    }
+
+    // the next row is selected, so the keyboard can close several windows in a row
+    fn close_selected(&self) {
+        let entity = match self.selected_window() {
+            Some(entity) => entity,
+            None => return,
+        };
+        let index = self.filtered_index(entity).unwrap_or(0);
+        self.close_window_row(entity);
+        let imp = imp::DockPopover::from_instance(self);
+        imp.selected_entity.set(None);
+        let n_items = imp.filter_model.get().map(|model| model.n_items()).unwrap_or(0);
+        if n_items > 0 {
+            self.select_index(index.min(n_items - 1), true);
+        }
+    }
+
+    // shows the typed text and highlights it in the titles of the rows
+    fn update_filter(&self) {
+        let imp = imp::DockPopover::from_instance(self);
+        let filter = imp.filter_text.borrow();
+        if let Some(filter_label) = imp.filter_label.borrow().as_ref() {
+            filter_label.set_text(&filter);
+            filter_label.set_visible(!filter.is_empty());
+        }
+        for (label, title) in imp.title_labels.borrow().values() {
+            label.set_markup(&highlight_match(title, &filter));
+        }
+    }
+
+    // fills `picture` with a cached or freshly captured preview of the window
+    fn set_preview(&self, picture: &Picture, entity: (u32, u32)) {
+        let imp = imp::DockPopover::from_instance(self);
+        if let Some(texture) = imp.previews.borrow().get(&entity) {
+            picture.set_paintable(Some(texture));
+            return;
+        }
+        picture.set_paintable(self.fallback_paintable().as_ref());
+        if imp.previews_unsupported.get() || !daemon_supports("WindowCapture") {
+            return;
+        }
+        glib::MainContext::default().spawn_local(
+            glib::clone!(@weak self as self_, @weak picture => async move {
+                let imp = imp::DockPopover::from_instance(&self_);
+                match window_preview(entity).await {
+                    Ok(Some(texture)) => {
+                        picture.set_paintable(Some(&texture));
+                        imp.previews.borrow_mut().insert(entity, texture);
+                    }
+                    Ok(None) => {}
+                    Err(err) => {
+                        // the daemon does not support previews, keep showing the app icon
+                        log::warn!("Failed to capture window preview: {}", err);
+                        imp.previews_unsupported.set(true);
+                    }
+                }
+            }),
+        );
+    }
+
+    fn fallback_paintable(&self) -> Option<gdk::Paintable> {
+        let imp = imp::DockPopover::from_instance(self);
+        let app_info = imp
+            .dock_object
+            .borrow()
+            .as_ref()?
+            .property::<Option<DesktopAppInfo>>("appinfo")?;
+        let icon = app_info.icon()?;
+        Some(icon_paintable(&icon, PREVIEW_HEIGHT, self).upcast())
+    }
+
+    // previews go stale while the popover stays open, so capture them again periodically
+    fn schedule_preview_refresh(&self) {
+        let imp = imp::DockPopover::from_instance(self);
+        if imp.preview_refresh.borrow().is_some() || imp.previews_unsupported.get() {
+            return;
+        }
+        let source_id = glib::timeout_add_local(
+            Duration::from_secs(PREVIEW_REFRESH_SECS),
+            glib::clone!(@weak self as self_ => @default-return glib::Continue(false), move || {
+                let imp = imp::DockPopover::from_instance(&self_);
+                imp.previews.borrow_mut().clear();
+                imp.preview_refresh.replace(None);
+                self_.update_layout();
+                glib::Continue(false)
+            }),
+        );
+        imp.preview_refresh.replace(Some(source_id));
+    }
+
+    /// Drops cached previews and stops refreshing them, called when the popover is closed.
+    pub fn stop_previews(&self) {
+        let imp = imp::DockPopover::from_instance(self);
+        if let Some(source_id) = imp.preview_refresh.replace(None) {
+            source_id.remove();
+        }
+        imp.previews.borrow_mut().clear();
+    }
+
+    // closes a single window, removing its row before the daemon confirms it
+    // minimize, maximize and move to workspace, disabled if the daemon doesn't implement them
+    fn window_menu_button(
+        &self,
+        entity: (u32, u32),
+        title: &str,
+        last_workspace: u32,
+    ) -> MenuButton {
+        let imp = imp::DockPopover::from_instance(self);
+        let tx = imp.tx.get().unwrap().clone();
+        let send = glib::clone!(@weak self as self_ => move |event: Event| {
+            send_event(&tx, event);
+            self_.emit_hide();
+        });
+
+        let actions = gio::SimpleActionGroup::new();
+        let minimize = gio::SimpleAction::new("minimize", None);
+        minimize.set_enabled(daemon_supports("WindowMinimize"));
+        minimize.connect_activate(glib::clone!(@strong send => move |_, _| {
+            send(Event::Minimize(entity));
+        }));
+        actions.add_action(&minimize);
+        let maximize = gio::SimpleAction::new("maximize", None);
+        maximize.set_enabled(daemon_supports("WindowMaximize"));
+        maximize.connect_activate(glib::clone!(@strong send => move |_, _| {
+            send(Event::Maximize(entity));
+        }));
+        actions.add_action(&maximize);
+        let move_to_workspace =
+            gio::SimpleAction::new("move-to-workspace", Some(glib::VariantTy::UINT32));
+        move_to_workspace.set_enabled(daemon_supports("WindowMoveToWorkspace"));
+        move_to_workspace.connect_activate(move |_, workspace| {
+            if let Some(workspace) = workspace.and_then(|w| w.get::<u32>()) {
+                send(Event::MoveToWorkspace(entity, workspace));
+            }
+        });
+        actions.add_action(&move_to_workspace);
+
+        let menu = gio::Menu::new();
+        menu.append(Some(&fl!("minimize")), Some("row.minimize"));
+        menu.append(Some(&fl!("maximize")), Some("row.maximize"));
+        let workspaces = gio::Menu::new();
+        for workspace in 1..=last_workspace {
+            let item = gio::MenuItem::new(Some(&fl!("workspace", number = workspace)), None);
+            item.set_action_and_target_value(
+                Some("row.move-to-workspace"),
+                Some(&workspace.to_variant()),
+            );
+            workspaces.append_item(&item);
+        }
+        menu.append_submenu(Some(&fl!("move-to-workspace")), &workspaces);
+
+        cascade! {
+            MenuButton::new();
+            ..set_icon_name("view-more-symbolic");
+            ..set_menu_model(Some(&menu));
+            ..set_valign(Align::Center);
+            ..add_css_class("flat");
+            ..add_css_class("dock_popover_close");
+            ..insert_action_group("row", Some(&actions));
+            ..update_property(&[(
+                gtk4::AccessibleProperty::Label,
+                &fl!("window-actions", window = title),
+            )]);
+        }
+    }
+
+    fn close_window_row(&self, entity: (u32, u32)) {
+        let imp = imp::DockPopover::from_instance(self);
+        send_event(imp.tx.get().unwrap(), Event::Close(entity));
+
+        let window_model = imp.window_model.get().unwrap();
+        let position = (0..window_model.n_items()).find(|&i| {
+            window_model
+                .item(i)
+                .and_then(|item| item.downcast::<WindowObject>().ok())
+                .and_then(|w| w.entity())
+                == Some(entity)
+        });
+        if let Some(position) = position {
+            window_model.remove(position);
+        }
+        imp.title_labels.borrow_mut().remove(&entity);
+        imp.row_revealers.borrow_mut().remove(&entity);
+        if window_model.n_items() == 0 {
+            self.emit_hide();
+        }
+    }
+
     fn layout(&self) {
         let imp = imp::DockPopover::from_instance(self);
         let menu_handle = cascade! {
@@ -151,6 +814,30 @@ impl DockPopover {
         };
         self.append(&menu_handle);
         imp.menu_handle.replace(menu_handle);
+
+        let window_model = gio::ListStore::new(WindowObject::static_type());
+        let filter = CustomFilter::new(glib::clone!(@weak self as self_ => @default-return true, move |item| {
+            let imp = imp::DockPopover::from_instance(&self_);
+            let filter = imp.filter_text.borrow();
+            filter.is_empty()
+                || item
+                    .downcast_ref::<WindowObject>()
+                    .and_then(|w| w.item())
+                    .and_then(|w| find_match(&w.name, &filter))
+                    .is_some()
+        }));
+        let filter_model = FilterListModel::new(Some(&window_model), Some(&filter));
+        imp.window_model.set(window_model).unwrap();
+        imp.filter.set(filter).unwrap();
+        imp.filter_model.set(filter_model).unwrap();
+        self.setup_type_ahead();
+        // keyboard users start on the window a click on the item would focus, the popover
+        // moves the focus to its first button when it is shown
+        self.connect_map(|self_| {
+            glib::idle_add_local_once(glib::clone!(@weak self_ => move || {
+                self_.restore_selection(true);
+            }));
+        });
     }
 
     fn emit_hide(&self) {
@@ -182,12 +869,10 @@ impl DockPopover {
         if let Some(dock_object) = dock_object.as_ref() {
             // println!("setting up popover menu handlers");
             let self_ = self.clone();
+            let tx = imp.tx.get().unwrap().clone();
             launch_new_item.connect_clicked(glib::clone!(@weak dock_object, => move |_| {
-                let app_info = dock_object.property::<Option<DesktopAppInfo>>("appinfo").expect("Failed to convert value to DesktopAppInfo");
-
-                let context = gdk::Display::default().unwrap().app_launch_context();
-                if let Err(err) = app_info.launch(&[], Some(&context)) {
-                    eprintln!("{}", err);
+                if let Some(id) = dock_object.get_id() {
+                    send_event(&tx, Event::Launch(id));
                 }
                 self_.emit_hide();
             }));
@@ -196,13 +881,8 @@ impl DockPopover {
             let self_ = self.clone();
             quit_all_item.connect_clicked(glib::clone!(@weak dock_object => move |_| {
                 let active = dock_object.property::<BoxedWindowList>("active").0;
-                for w in active {
-                    let entity = w.entity;
-                    let tx = tx.clone();
-                    glib::MainContext::default().spawn_local(async move {
-                        let _ = tx.clone().send(Event::Close(entity)).await;
-                    });
-                }
+                let entities = active.into_iter().map(|w| w.entity).collect();
+                send_event(&tx, Event::CloseAll(entities));
                 self_.emit_hide();
             }));
 
@@ -210,12 +890,18 @@ impl DockPopover {
             let self_ = self.clone();
             favorite_item.connect_clicked(glib::clone!(@weak dock_object => move |_| {
                 let saved = dock_object.property::<bool>("saved");
-                let tx = tx.clone();
-                glib::MainContext::default().spawn_local(async move {
-                    if let Some(name) = dock_object.get_name() {
-                        let _ = tx.clone().send(Event::Favorite((name, !saved))).await;
-                    }
-                });
+                if let Some(window) = dock_object.pinnable_window() {
+                    send_event(&tx, Event::PinWindow(window));
+                } else if let Some(id) = dock_object.get_id() {
+                    send_event(
+                        &tx,
+                        Event::Favorite {
+                            id,
+                            favorite: !saved,
+                            index: None,
+                        },
+                    );
+                }
                 self_.emit_hide();
             }));
 
@@ -227,19 +913,58 @@ impl DockPopover {
             //     }),
             // );
 
-            let tx = imp.tx.get().unwrap().clone();
-            let self_ = self.clone();
-            window_listbox.connect_row_activated(
-                glib::clone!(@weak dock_object => move |_, item| {
-                    let active = dock_object.property::<BoxedWindowList>("active").0;
-                    let entity = active[usize::try_from(item.index()).unwrap()].entity;
-                    let tx = tx.clone();
-                    glib::MainContext::default().spawn_local(async move {
-                        let _ = tx.send(Event::Activate(entity)).await;
-                    });
-                    self_.emit_hide();
-                }),
-            );
+            window_listbox.connect_row_activated(glib::clone!(@weak self as self_ => move |_, row| {
+                // rows may have been closed or filtered, so look up the window by row
+                if let Some(w) = self_.row_window(row) {
+                    self_.activate_window(w.entity);
+                }
+            }));
+        }
+    }
+}
+
+// byte range of the first case-insensitive occurrence of `filter` in `title`
+// windows can be moved to any workspace in use, or the next empty one
+fn last_workspace(windows: &[Item]) -> u32 {
+    windows
+        .iter()
+        .filter_map(|w| w.workspace())
+        .chain(current_workspace())
+        .max()
+        .unwrap_or(0)
+        + 1
+}
+
+fn find_match(title: &str, filter: &str) -> Option<Range<usize>> {
+    let needle: Vec<char> = filter.chars().flat_map(char::to_lowercase).collect();
+    if needle.is_empty() {
+        return None;
+    }
+    title.char_indices().find_map(|(start, _)| {
+        let mut needle = needle.iter().peekable();
+        for (offset, c) in title[start..].char_indices() {
+            for lower in c.to_lowercase() {
+                match needle.next() {
+                    Some(n) if *n == lower => {}
+                    _ => return None,
+                }
+            }
+            if needle.peek().is_none() {
+                return Some(start..start + offset + c.len_utf8());
+            }
         }
+        None
+    })
+}
+
+fn highlight_match(title: &str, filter: &str) -> String {
+    match find_match(title, filter) {
+        Some(range) => format!(
+            "{}<b>{}</b>{}",
+            glib::markup_escape_text(&title[..range.start]),
+            glib::markup_escape_text(&title[range.clone()]),
+            glib::markup_escape_text(&title[range.end..])
+        ),
+        None => glib::markup_escape_text(title).to_string(),
     }
 }
@@ -0,0 +1,845 @@
+// SPDX-License-Identifier: MPL-2.0-only
+
+//! The rules for sorting windows into the saved and active lists, kept free of
+//! GTK objects. Callers turn the returned [`ModelDelta`]s into model operations.
+
+use crate::app_id;
+use crate::config::{GroupingMode, SortOrder};
+use crate::custom_launcher::{self, CustomLauncher};
+use crate::generated_entry;
+use crate::utils::Item;
+use gio::DesktopAppInfo;
+use gtk4::prelude::*;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+
+/// The parts of a desktop entry that are needed to match windows to it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AppEntry {
+    /// desktop id, e.g. "firefox.desktop"
+    pub id: String,
+    /// path of the desktop file
+    pub path: String,
+    pub name: String,
+    /// normalized app ids the windows may report, see [`app_id::app_info_ids`]
+    pub app_ids: Vec<String>,
+    /// set for custom launchers, which match windows by app id or title instead
+    pub match_string: Option<String>,
+}
+
+impl AppEntry {
+    pub fn from_app_info(app_info: &DesktopAppInfo) -> Self {
+        let id = generated_entry::app_info_id(app_info).unwrap_or_default();
+        Self {
+            app_ids: app_id::app_info_ids(app_info, &id),
+            id,
+            path: app_info
+                .filename()
+                .map(|path| path.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            name: app_info.name().to_string(),
+            match_string: None,
+        }
+    }
+
+    pub fn from_custom_launcher(launcher: &CustomLauncher) -> Self {
+        Self {
+            id: launcher.id.clone(),
+            name: launcher.name.clone(),
+            match_string: Some(launcher.match_string.clone()),
+            ..Self::default()
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        if let Some(launcher) = custom_launcher::get(id) {
+            return Some(Self::from_custom_launcher(&launcher));
+        }
+        generated_entry::app_info(id).map(|app_info| Self::from_app_info(&app_info))
+    }
+
+    /// Checks the desktop file and app id of the window, which is compared to the desktop id,
+    /// the WM_CLASS and the ids packaged apps are known by.
+    pub fn matches_id(&self, item: &Item) -> bool {
+        if let Some(match_string) = &self.match_string {
+            return custom_launcher::window_matches(match_string, item);
+        }
+        if !item.desktop_entry.is_empty()
+            && !self.path.is_empty()
+            && Path::new(&item.desktop_entry) == Path::new(&self.path)
+        {
+            return true;
+        }
+        if item.app_id.is_empty() {
+            return false;
+        }
+        let app_id = app_id::normalize(&item.app_id);
+        self.app_ids.contains(&app_id) || app_id::same_app(&self.id, &app_id)
+    }
+
+    /// Checks whether the window belongs to the app, falling back to the window description.
+    pub fn matches(&self, item: &Item) -> bool {
+        self.matches_id(item)
+            || (self.match_string.is_none() && !self.name.is_empty() && item.description == self.name)
+    }
+}
+
+/// A saved app and its open windows.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SavedEntry {
+    pub app: AppEntry,
+    pub windows: Vec<Item>,
+}
+
+/// The windows of a running app that isn't saved.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActiveStack {
+    pub key: String,
+    pub windows: Vec<Item>,
+}
+
+/// The contents of both dock lists, in dock order.
+#[derive(Debug, Clone, Default)]
+pub struct DockState {
+    pub saved: Vec<SavedEntry>,
+    pub active: Vec<ActiveStack>,
+}
+
+/// A change to apply to the dock models.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ModelDelta {
+    /// The windows of the saved app at `index` changed.
+    SavedWindows { index: usize, windows: Vec<Item> },
+    /// `app` was saved at `index`, taking over the active stack at `from_active` if it was running.
+    InsertSaved {
+        index: usize,
+        app: AppEntry,
+        from_active: Option<usize>,
+    },
+    /// The saved app at `index` was removed, it is moved to the active list at `to_active` if it is running.
+    RemoveSaved {
+        index: usize,
+        to_active: Option<usize>,
+    },
+    /// The active list now holds these stacks.
+    ReplaceActive(Vec<ActiveStack>),
+}
+
+/// Windows are stacked by app id. Windows without one are stacked by description and
+/// executable, so unrelated apps with the same description stay apart, and windows without
+/// either are never stacked with other windows.
+pub fn stack_key(item: &Item) -> String {
+    if !item.app_id.is_empty() {
+        item.app_id.clone()
+    } else if !item.description.is_empty() {
+        format!("{}\n{}", item.description, item.exe)
+    } else {
+        // the newline keeps these apart from app ids
+        format!("\n{}:{}", item.entity.0, item.entity.1)
+    }
+}
+
+// the newline keeps these apart from app ids
+const WINDOW_KEY_PREFIX: &str = "\nwindow:";
+
+/// The key of a stack holding only `item`, used when every window gets its own item.
+pub fn window_key(item: &Item) -> String {
+    format!("{}{}:{}", WINDOW_KEY_PREFIX, item.entity.0, item.entity.1)
+}
+
+/// Whether the window `entity` of `windows` is the one the dock showed in `shown`. Closed
+/// windows are gone, and a restarted daemon may give their entities to windows of other apps.
+pub fn same_window(shown: &[Item], windows: &[Item], entity: (u32, u32)) -> bool {
+    let key = |windows: &[Item]| windows.iter().find(|w| w.entity == entity).map(stack_key);
+    let current = key(windows);
+    current.is_some() && current == key(shown)
+}
+
+// the window of a stack made by `window_stacks`
+fn single_window(stack: &ActiveStack) -> Option<(u32, u32)> {
+    stack
+        .windows
+        .first()
+        .filter(|_| stack.key.starts_with(WINDOW_KEY_PREFIX))
+        .map(|window| window.entity)
+}
+
+fn stack_windows(windows: Vec<Item>) -> Vec<ActiveStack> {
+    let mut stacks: BTreeMap<String, Vec<Item>> = BTreeMap::new();
+    for window in windows {
+        stacks
+            .entry(stack_key(&window))
+            .or_default()
+            .push(window);
+    }
+    stacks
+        .into_iter()
+        .map(|(key, windows)| ActiveStack { key, windows })
+        .collect()
+}
+
+// windows of the same app stay next to each other, in the order they are given
+fn window_stacks(mut windows: Vec<Item>) -> Vec<ActiveStack> {
+    windows.sort_by_cached_key(stack_key);
+    windows
+        .into_iter()
+        .map(|window| ActiveStack {
+            key: window_key(&window),
+            windows: vec![window],
+        })
+        .collect()
+}
+
+/// Remembers when apps were first seen and last focused, so the active list
+/// keeps its order across refreshes.
+#[derive(Debug, Default)]
+pub struct StackHistory {
+    counter: u64,
+    first_seen: HashMap<String, u64>,
+    last_focused: HashMap<String, u64>,
+    focused: Option<String>,
+    /// when each window was first seen, which orders the windows of an app
+    window_first_seen: HashMap<(u32, u32), u64>,
+    /// orders the items of single windows, see [`GroupingMode::OnePerWindow`]
+    window_last_focused: HashMap<(u32, u32), u64>,
+    focused_window: Option<(u32, u32)>,
+}
+
+impl StackHistory {
+    /// Records new apps and focus changes, apps without windows are forgotten.
+    pub fn update(&mut self, windows: &[Item]) {
+        let keys: HashSet<String> = windows.iter().map(stack_key).collect();
+        self.first_seen.retain(|key, _| keys.contains(key));
+        self.last_focused.retain(|key, _| keys.contains(key));
+        self.window_first_seen
+            .retain(|entity, _| windows.iter().any(|w| w.entity == *entity));
+        self.window_last_focused
+            .retain(|entity, _| windows.iter().any(|w| w.entity == *entity));
+        // windows are sorted by entity, which follows the order they were opened in
+        for window in windows {
+            let key = stack_key(window);
+            if !self.first_seen.contains_key(&key) {
+                self.counter += 1;
+                self.first_seen.insert(key, self.counter);
+            }
+            if !self.window_first_seen.contains_key(&window.entity) {
+                self.counter += 1;
+                self.window_first_seen.insert(window.entity, self.counter);
+            }
+        }
+        if let Some(focused) = windows.iter().find(|w| w.focused).map(stack_key) {
+            if self.focused.as_ref() != Some(&focused) || !self.last_focused.contains_key(&focused) {
+                self.counter += 1;
+                self.last_focused.insert(focused.clone(), self.counter);
+                self.focused = Some(focused);
+            }
+        }
+        if let Some(focused) = windows.iter().find(|w| w.focused).map(|w| w.entity) {
+            if self.focused_window != Some(focused)
+                || !self.window_last_focused.contains_key(&focused)
+            {
+                self.counter += 1;
+                self.window_last_focused.insert(focused, self.counter);
+                self.focused_window = Some(focused);
+            }
+        }
+    }
+
+    /// Sorts windows in the order they were first seen, so the windows of an app keep their
+    /// order in the popover and when cycling through them while other windows come and go.
+    pub fn sort_windows(&self, windows: &mut [Item]) {
+        windows.sort_by_key(|w| {
+            (
+                self.window_first_seen
+                    .get(&w.entity)
+                    .copied()
+                    .unwrap_or(u64::MAX),
+                w.entity,
+            )
+        });
+    }
+
+    pub fn sort(&self, stacks: &mut [ActiveStack], order: SortOrder) {
+        // stacks of single windows are ordered by their window
+        let first_seen = |stack: &ActiveStack| {
+            match single_window(stack) {
+                Some(entity) => self.window_first_seen.get(&entity),
+                None => self.first_seen.get(&stack.key),
+            }
+            .copied()
+            .unwrap_or(u64::MAX)
+        };
+        let last_focused = |stack: &ActiveStack| {
+            match single_window(stack) {
+                Some(entity) => self.window_last_focused.get(&entity),
+                None => self.last_focused.get(&stack.key),
+            }
+            .copied()
+            .unwrap_or(0)
+        };
+        match order {
+            // the stacks come out of a BTreeMap already sorted by key, single windows by app
+            SortOrder::Alphabetical => {}
+            SortOrder::LaunchOrder => stacks.sort_by_key(first_seen),
+            SortOrder::MostRecentlyUsed => {
+                stacks.sort_by_key(|stack| (Reverse(last_focused(stack)), first_seen(stack)))
+            }
+        }
+    }
+}
+
+/// Assigns the windows to the saved apps, the rest are stacked into the active list
+/// in the order given by `history`, or listed one by one if `grouping` says so.
+pub fn apply_window_list(
+    state: &mut DockState,
+    windows: Vec<Item>,
+    history: &StackHistory,
+    order: SortOrder,
+    grouping: GroupingMode,
+) -> Vec<ModelDelta> {
+    // custom launchers take their windows before they are stacked, so a web app gets its
+    // windows out of the browser's stack
+    let mut windows = windows;
+    let custom_windows: Vec<Vec<Item>> = state
+        .saved
+        .iter()
+        .map(|entry| match entry.app.match_string {
+            Some(_) => {
+                let (matching, rest): (Vec<Item>, Vec<Item>) =
+                    windows.drain(..).partition(|w| entry.app.matches_id(w));
+                windows = rest;
+                matching
+            }
+            None => Vec::new(),
+        })
+        .collect();
+    let mut stacks = stack_windows(windows);
+    let mut deltas = Vec::new();
+    for ((index, entry), custom_windows) in state.saved.iter_mut().enumerate().zip(custom_windows) {
+        // ids are compared first, the window description is only used as a last resort
+        let matching = stacks
+            .iter()
+            .position(|s| s.windows.iter().any(|w| entry.app.matches_id(w)))
+            .or_else(|| {
+                stacks
+                    .iter()
+                    .position(|s| s.windows.iter().any(|w| entry.app.matches(w)))
+            })
+            .filter(|_| entry.app.match_string.is_none());
+        let windows = match matching {
+            Some(i) => stacks.remove(i).windows,
+            None => custom_windows,
+        };
+        if entry.windows != windows {
+            entry.windows = windows.clone();
+            deltas.push(ModelDelta::SavedWindows { index, windows });
+        }
+    }
+    // saved apps took their windows as a whole, the rest are split up
+    if grouping == GroupingMode::OnePerWindow {
+        stacks = window_stacks(stacks.into_iter().flat_map(|stack| stack.windows).collect());
+    }
+    history.sort(&mut stacks, order);
+    if state.active != stacks {
+        state.active = stacks.clone();
+        deltas.push(ModelDelta::ReplaceActive(stacks));
+    }
+    deltas
+}
+
+/// Saves or removes `app`, moving its windows between the lists.
+/// New saved apps are inserted at `index`, or appended if it is `None` or out of range.
+/// Saving an app that is already saved does nothing, so racing requests can't create duplicates.
+pub fn apply_favorite(
+    state: &mut DockState,
+    app: AppEntry,
+    favorite: bool,
+    index: Option<usize>,
+) -> Vec<ModelDelta> {
+    // saved objects without an app info have an empty id, so they must not match
+    if app.id.is_empty() {
+        return Vec::new();
+    }
+    // the same app in another packaging counts as saved, so pinning it twice does nothing
+    let saved_index = state
+        .saved
+        .iter()
+        .position(|e| e.app.id == app.id || app_id::same_app(&e.app.id, &app.id));
+    match (favorite, saved_index) {
+        (true, None) => {
+            // custom launchers only share a stack with the app, they get their windows on the next sync
+            let from_active = state
+                .active
+                .iter()
+                .position(|s| s.windows.iter().any(|w| app.matches(w)))
+                .filter(|_| app.match_string.is_none());
+            let windows = from_active
+                .map(|i| state.active.remove(i).windows)
+                .unwrap_or_default();
+            let index = index
+                .unwrap_or(state.saved.len())
+                .min(state.saved.len());
+            state.saved.insert(
+                index,
+                SavedEntry {
+                    app: app.clone(),
+                    windows,
+                },
+            );
+            vec![ModelDelta::InsertSaved {
+                index,
+                app,
+                from_active,
+            }]
+        }
+        (false, Some(index)) => {
+            let entry = state.saved.remove(index);
+            // running apps go back where the window list sorting would put them, the windows
+            // of custom launchers go back to their app on the next sync
+            let to_active = entry
+                .windows
+                .get(0)
+                .filter(|_| entry.app.match_string.is_none())
+                .map(|first| {
+                    let key = stack_key(first);
+                    let position = state.active.partition_point(|s| s.key < key);
+                    state.active.insert(
+                        position,
+                        ActiveStack {
+                            key,
+                            windows: entry.windows.clone(),
+                        },
+                    );
+                    position
+                });
+            vec![ModelDelta::RemoveSaved { index, to_active }]
+        }
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(entity: u32, app_id: &str) -> Item {
+        Item {
+            entity: (entity, 0),
+            app_id: app_id.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn app(id: &str) -> AppEntry {
+        AppEntry {
+            id: id.to_string(),
+            app_ids: vec![app_id::normalize(id)],
+            ..Default::default()
+        }
+    }
+
+    fn saved(id: &str, windows: Vec<Item>) -> SavedEntry {
+        SavedEntry {
+            app: app(id),
+            windows,
+        }
+    }
+
+    fn stack(key: &str, windows: Vec<Item>) -> ActiveStack {
+        ActiveStack {
+            key: key.to_string(),
+            windows,
+        }
+    }
+
+    fn keys(state: &DockState) -> Vec<&str> {
+        state.active.iter().map(|s| s.key.as_str()).collect()
+    }
+
+    fn sync(
+        state: &mut DockState,
+        windows: Vec<Item>,
+        history: &mut StackHistory,
+    ) -> Vec<ModelDelta> {
+        history.update(&windows);
+        apply_window_list(
+            state,
+            windows,
+            history,
+            SortOrder::LaunchOrder,
+            GroupingMode::GroupByApp,
+        )
+    }
+
+    #[test]
+    fn stack_key_falls_back_to_description_and_exe() {
+        assert_eq!(stack_key(&window(1, "firefox")), "firefox");
+
+        let mut script = window(2, "");
+        script.description = "Untitled".to_string();
+        script.exe = "/usr/bin/python3".to_string();
+        let mut editor = script.clone();
+        editor.exe = "/usr/bin/gedit".to_string();
+        assert_eq!(stack_key(&script), "Untitled\n/usr/bin/python3");
+        assert_ne!(stack_key(&script), stack_key(&editor));
+
+        let anonymous = stack_key(&window(3, ""));
+        assert_eq!(anonymous, "\n3:0");
+        assert_ne!(anonymous, stack_key(&window(4, "")));
+    }
+
+    #[test]
+    fn windows_without_app_id_are_not_merged_with_strangers() {
+        let untitled = |entity: u32, exe: &str| Item {
+            entity: (entity, 0),
+            description: "Untitled".to_string(),
+            exe: exe.to_string(),
+            ..Default::default()
+        };
+        let mut state = DockState::default();
+        let mut history = StackHistory::default();
+        let windows = vec![
+            // nothing is known about these two
+            window(1, ""),
+            window(2, ""),
+            untitled(3, "/usr/bin/python3"),
+            untitled(4, "/usr/bin/gedit"),
+            untitled(5, "/usr/bin/python3"),
+        ];
+        sync(&mut state, windows, &mut history);
+
+        let mut stacks: Vec<Vec<u32>> = state
+            .active
+            .iter()
+            .map(|s| s.windows.iter().map(|w| w.entity.0).collect())
+            .collect();
+        stacks.sort();
+        assert_eq!(stacks, vec![vec![1], vec![2], vec![3, 5], vec![4]]);
+    }
+
+    #[test]
+    fn window_list_fills_saved_apps_first() {
+        let mut state = DockState {
+            saved: vec![saved("firefox.desktop", Vec::new())],
+            active: Vec::new(),
+        };
+        let mut history = StackHistory::default();
+        let windows = vec![
+            window(1, "firefox"),
+            window(2, "org.gnome.Terminal"),
+            window(3, "firefox"),
+        ];
+        let deltas = sync(&mut state, windows, &mut history);
+
+        let firefox = vec![window(1, "firefox"), window(3, "firefox")];
+        let terminal = stack("org.gnome.Terminal", vec![window(2, "org.gnome.Terminal")]);
+        assert_eq!(
+            deltas,
+            vec![
+                ModelDelta::SavedWindows {
+                    index: 0,
+                    windows: firefox.clone(),
+                },
+                ModelDelta::ReplaceActive(vec![terminal.clone()]),
+            ]
+        );
+        assert_eq!(state.saved[0].windows, firefox);
+        assert_eq!(state.active, vec![terminal]);
+    }
+
+    #[test]
+    fn saved_app_matches_other_packagings() {
+        let mut state = DockState {
+            saved: vec![saved("firefox.desktop", Vec::new())],
+            active: Vec::new(),
+        };
+        let mut history = StackHistory::default();
+        sync(
+            &mut state,
+            vec![window(1, "org.mozilla.firefox")],
+            &mut history,
+        );
+        assert_eq!(
+            state.saved[0].windows,
+            vec![window(1, "org.mozilla.firefox")]
+        );
+        assert!(state.active.is_empty());
+    }
+
+    #[test]
+    fn unchanged_window_list_has_no_deltas() {
+        let mut state = DockState {
+            saved: vec![saved("firefox.desktop", Vec::new())],
+            active: Vec::new(),
+        };
+        let mut history = StackHistory::default();
+        let windows = vec![window(1, "firefox"), window(2, "gedit")];
+        assert!(!sync(&mut state, windows.clone(), &mut history).is_empty());
+        assert!(sync(&mut state, windows, &mut history).is_empty());
+    }
+
+    #[test]
+    fn closed_windows_leave_the_saved_app() {
+        let mut state = DockState {
+            saved: vec![saved("firefox.desktop", Vec::new())],
+            active: Vec::new(),
+        };
+        let mut history = StackHistory::default();
+        sync(&mut state, vec![window(1, "firefox")], &mut history);
+        let deltas = sync(&mut state, Vec::new(), &mut history);
+        assert_eq!(
+            deltas,
+            vec![ModelDelta::SavedWindows {
+                index: 0,
+                windows: Vec::new(),
+            }]
+        );
+    }
+
+    #[test]
+    fn one_item_per_window_splits_the_active_stacks() {
+        let mut state = DockState::default();
+        let mut history = StackHistory::default();
+        let windows = vec![window(1, "gedit"), window(2, "gedit")];
+        history.update(&windows);
+        apply_window_list(
+            &mut state,
+            windows,
+            &history,
+            SortOrder::LaunchOrder,
+            GroupingMode::OnePerWindow,
+        );
+        assert_eq!(
+            keys(&state),
+            vec![window_key(&window(1, "")), window_key(&window(2, ""))]
+        );
+    }
+
+    #[test]
+    fn favorite_takes_over_the_active_stack() {
+        let mut state = DockState::default();
+        let mut history = StackHistory::default();
+        sync(
+            &mut state,
+            vec![window(1, "gedit"), window(2, "firefox")],
+            &mut history,
+        );
+
+        let deltas = apply_favorite(&mut state, app("firefox.desktop"), true, None);
+        assert_eq!(
+            deltas,
+            vec![ModelDelta::InsertSaved {
+                index: 0,
+                app: app("firefox.desktop"),
+                from_active: Some(1),
+            }]
+        );
+        assert_eq!(
+            state.saved,
+            vec![saved("firefox.desktop", vec![window(2, "firefox")])]
+        );
+        assert_eq!(keys(&state), vec!["gedit"]);
+    }
+
+    #[test]
+    fn pinning_a_running_app_leaves_one_item() {
+        let mut state = DockState::default();
+        let mut history = StackHistory::default();
+        let windows = vec![
+            window(1, "firefox"),
+            window(2, "gedit"),
+            window(3, "firefox"),
+        ];
+        sync(&mut state, windows.clone(), &mut history);
+
+        apply_favorite(&mut state, app("firefox.desktop"), true, None);
+        // the refresh after the favorite finds everything in place already
+        assert!(sync(&mut state, windows, &mut history).is_empty());
+        let items_with_firefox = state
+            .saved
+            .iter()
+            .map(|entry| &entry.windows)
+            .chain(state.active.iter().map(|stack| &stack.windows))
+            .filter(|windows| windows.iter().any(|w| w.app_id == "firefox"))
+            .count();
+        assert_eq!(items_with_firefox, 1);
+        assert_eq!(state.saved[0].windows.len(), 2);
+    }
+
+    #[test]
+    fn favorite_index_out_of_range_appends() {
+        let mut state = DockState {
+            saved: vec![saved("gedit.desktop", Vec::new())],
+            active: Vec::new(),
+        };
+        let deltas = apply_favorite(&mut state, app("firefox.desktop"), true, Some(5));
+        assert_eq!(
+            deltas,
+            vec![ModelDelta::InsertSaved {
+                index: 1,
+                app: app("firefox.desktop"),
+                from_active: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn repeated_favorite_saves_the_app_once() {
+        let mut state = DockState::default();
+        let mut history = StackHistory::default();
+        sync(&mut state, vec![window(1, "firefox")], &mut history);
+
+        // the D-Bus request and the click in the dock race, both are queued as favorites
+        assert_eq!(
+            apply_favorite(&mut state, app("firefox.desktop"), true, None).len(),
+            1
+        );
+        assert!(apply_favorite(&mut state, app("firefox.desktop"), true, Some(0)).is_empty());
+        assert_eq!(
+            state.saved,
+            vec![saved("firefox.desktop", vec![window(1, "firefox")])]
+        );
+        assert!(state.active.is_empty());
+
+        // a single unfavorite removes it for good
+        apply_favorite(&mut state, app("firefox.desktop"), false, None);
+        assert!(state.saved.is_empty());
+        assert!(apply_favorite(&mut state, app("firefox.desktop"), false, None).is_empty());
+        assert_eq!(keys(&state), vec!["firefox"]);
+    }
+
+    #[test]
+    fn favorite_without_id_does_nothing() {
+        let mut state = DockState {
+            saved: vec![saved("", Vec::new())],
+            active: Vec::new(),
+        };
+        assert!(apply_favorite(&mut state, app(""), true, None).is_empty());
+        assert!(apply_favorite(&mut state, app(""), false, None).is_empty());
+        assert_eq!(state.saved.len(), 1);
+    }
+
+    #[test]
+    fn other_packaging_counts_as_saved() {
+        let mut state = DockState {
+            saved: vec![saved("firefox.desktop", Vec::new())],
+            active: Vec::new(),
+        };
+        assert!(
+            apply_favorite(&mut state, app("org.mozilla.firefox.desktop"), true, None).is_empty()
+        );
+        assert_eq!(state.saved.len(), 1);
+
+        let deltas = apply_favorite(&mut state, app("org.mozilla.firefox.desktop"), false, None);
+        assert_eq!(
+            deltas,
+            vec![ModelDelta::RemoveSaved {
+                index: 0,
+                to_active: None,
+            }]
+        );
+        assert!(state.saved.is_empty());
+    }
+
+    #[test]
+    fn unfavorite_returns_the_windows_in_key_order() {
+        let mut state = DockState {
+            saved: vec![saved("gedit.desktop", vec![window(3, "gedit")])],
+            active: vec![
+                stack("firefox", vec![window(1, "firefox")]),
+                stack("vlc", vec![window(2, "vlc")]),
+            ],
+        };
+        let deltas = apply_favorite(&mut state, app("gedit.desktop"), false, None);
+        assert_eq!(
+            deltas,
+            vec![ModelDelta::RemoveSaved {
+                index: 0,
+                to_active: Some(1),
+            }]
+        );
+        assert_eq!(keys(&state), vec!["firefox", "gedit", "vlc"]);
+    }
+
+    #[test]
+    fn history_keeps_launch_order() {
+        let mut history = StackHistory::default();
+        history.update(&[window(1, "zed")]);
+        history.update(&[window(1, "zed"), window(2, "abc")]);
+        let mut stacks = vec![
+            stack("abc", vec![window(2, "abc")]),
+            stack("zed", vec![window(1, "zed")]),
+        ];
+        history.sort(&mut stacks, SortOrder::LaunchOrder);
+        assert_eq!(stacks[0].key, "zed");
+
+        // an app that was closed and opened again goes to the end
+        history.update(&[window(2, "abc")]);
+        history.update(&[window(2, "abc"), window(3, "zed")]);
+        history.sort(&mut stacks, SortOrder::LaunchOrder);
+        assert_eq!(stacks[0].key, "abc");
+    }
+
+    #[test]
+    fn history_puts_the_last_focused_app_first() {
+        let mut history = StackHistory::default();
+        let mut focused = window(1, "zed");
+        focused.focused = true;
+        history.update(&[focused.clone(), window(2, "abc")]);
+        let mut stacks = vec![
+            stack("abc", vec![window(2, "abc")]),
+            stack("zed", vec![focused]),
+        ];
+        history.sort(&mut stacks, SortOrder::MostRecentlyUsed);
+        assert_eq!(stacks[0].key, "zed");
+
+        let mut focused = window(2, "abc");
+        focused.focused = true;
+        history.update(&[window(1, "zed"), focused]);
+        history.sort(&mut stacks, SortOrder::MostRecentlyUsed);
+        assert_eq!(stacks[0].key, "abc");
+
+        // alphabetical keeps the order the stacks come in
+        history.sort(&mut stacks, SortOrder::Alphabetical);
+        assert_eq!(stacks[0].key, "abc");
+    }
+
+    #[test]
+    fn new_window_does_not_reorder_the_stack() {
+        let mut state = DockState::default();
+        let mut history = StackHistory::default();
+        // the daemon may hand out lower entities again, the window list comes sorted by them
+        let mut open = Vec::new();
+        let mut orders = Vec::new();
+        for entity in [5, 3, 1] {
+            open.push(window(entity, "gedit"));
+            open.sort_by_key(|w| w.entity);
+            history.update(&open);
+            // as `sync_models` does it
+            let mut windows = open.clone();
+            history.sort_windows(&mut windows);
+            apply_window_list(
+                &mut state,
+                windows,
+                &history,
+                SortOrder::LaunchOrder,
+                GroupingMode::GroupByApp,
+            );
+            let order: Vec<u32> = state.active[0].windows.iter().map(|w| w.entity.0).collect();
+            orders.push(order);
+        }
+        assert_eq!(orders, vec![vec![5], vec![5, 3], vec![5, 3, 1]]);
+    }
+
+    #[test]
+    fn history_sorts_windows_by_first_seen() {
+        let mut history = StackHistory::default();
+        history.update(&[window(5, "gedit")]);
+        history.update(&[window(2, "gedit"), window(5, "gedit")]);
+        let mut windows = vec![window(2, "gedit"), window(5, "gedit"), window(9, "gedit")];
+        history.sort_windows(&mut windows);
+        let entities: Vec<u32> = windows.iter().map(|w| w.entity.0).collect();
+        assert_eq!(entities, vec![5, 2, 9]);
+    }
+}
@@ -0,0 +1,165 @@
+// SPDX-License-Identifier: MPL-2.0-only
+
+//! Desktop entries written for windows without one, e.g. apps started from a terminal or
+//! AppImages, so they can be pinned. They live in a subdirectory of the user's applications,
+//! which gives them desktop ids starting with `cosmic-dock-generated-`.
+
+use crate::utils::Item;
+use gio::DesktopAppInfo;
+use gtk4::glib;
+use gtk4::prelude::*;
+use std::path::{Path, PathBuf};
+
+const DIR_NAME: &str = "cosmic-dock-generated";
+const GENERATED_KEY: &str = "X-Cosmic-Dock-Generated";
+
+fn dir() -> PathBuf {
+    let mut path = glib::user_data_dir();
+    path.push("applications");
+    path.push(DIR_NAME);
+    path
+}
+
+fn id_prefix() -> String {
+    format!("{}-", DIR_NAME)
+}
+
+/// The desktop id of a generated entry at `path`, `None` for other desktop files.
+pub fn desktop_id(path: &Path) -> Option<String> {
+    if path.parent()? != dir() {
+        return None;
+    }
+    Some(format!("{}{}", id_prefix(), path.file_name()?.to_str()?))
+}
+
+pub fn is_generated(id: &str) -> bool {
+    id.starts_with(&id_prefix())
+}
+
+/// The desktop id of `app_info`. GIO only knows the ids of the entries it indexed, which
+/// generated entries may not be yet.
+pub fn app_info_id(app_info: &DesktopAppInfo) -> Option<String> {
+    app_info
+        .id()
+        .map(|id| id.to_string())
+        .or_else(|| desktop_id(&app_info.filename()?))
+}
+
+/// Like `DesktopAppInfo::new`, also finding generated entries GIO didn't index yet.
+pub fn app_info(id: &str) -> Option<DesktopAppInfo> {
+    DesktopAppInfo::new(id).or_else(|| {
+        let file_name = id.strip_prefix(&id_prefix())?;
+        DesktopAppInfo::from_filename(dir().join(file_name))
+    })
+}
+
+// Exec arguments with reserved characters have to be quoted
+fn quote_exec_arg(arg: &str) -> String {
+    const RESERVED: &[char] = &[
+        ' ', '\t', '\n', '"', '\'', '\\', '>', '<', '~', '|', '&', ';', '$', '*', '?', '#', '(',
+        ')', '`',
+    ];
+    if !arg.contains(RESERVED) {
+        return arg.replace('%', "%%");
+    }
+    let mut quoted = String::from("\"");
+    for c in arg.chars() {
+        match c {
+            '"' | '`' | '$' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '%' => quoted.push_str("%%"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn slug(item: &Item, exe: &str) -> String {
+    let name = if item.app_id.is_empty() {
+        Path::new(exe)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default()
+    } else {
+        item.app_id.clone()
+    };
+    let slug: String = name
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '_' { c } else { '-' })
+        .collect();
+    let slug = slug.trim_matches('-');
+    if slug.is_empty() {
+        "app".into()
+    } else {
+        slug.into()
+    }
+}
+
+/// Writes a desktop entry for the window `item` and returns its desktop id. Entries that are
+/// already generated for the app are reused, real entries with the same id are never replaced.
+pub fn create(item: &Item) -> Result<String, String> {
+    let exe = item
+        .exe()
+        .ok_or_else(|| format!("the executable of {} is unknown", item.name))?;
+    let dir = dir();
+    std::fs::create_dir_all(&dir).map_err(|err| err.to_string())?;
+    let slug = slug(item, exe);
+    let (id, path) = (1..)
+        .map(|i| {
+            let file_name = match i {
+                1 => format!("{}.desktop", slug),
+                i => format!("{}-{}.desktop", slug, i),
+            };
+            (format!("{}{}", id_prefix(), file_name), dir.join(file_name))
+        })
+        .find(|(id, path)| {
+            // entries GIO resolves to another file belong to someone else
+            DesktopAppInfo::new(id)
+                .and_then(|app_info| app_info.filename())
+                .map(|filename| &filename == path)
+                .unwrap_or(true)
+                && (!path.exists() || is_generated_file(path))
+        })
+        .expect("Ran out of desktop file names");
+
+    let key_file = glib::KeyFile::new();
+    let group = "Desktop Entry";
+    key_file.set_string(group, "Type", "Application");
+    key_file.set_string(
+        group,
+        "Name",
+        if item.name.is_empty() { &slug } else { &item.name },
+    );
+    key_file.set_string(group, "Exec", &quote_exec_arg(exe));
+    if !item.app_id.is_empty() {
+        key_file.set_string(group, "StartupWMClass", &item.app_id);
+    }
+    if !item.icon.is_empty() {
+        key_file.set_string(group, "Icon", &item.icon);
+    }
+    key_file.set_boolean(group, GENERATED_KEY, true);
+    std::fs::write(&path, key_file.to_data().as_bytes()).map_err(|err| err.to_string())?;
+    Ok(id)
+}
+
+fn is_generated_file(path: &Path) -> bool {
+    let key_file = glib::KeyFile::new();
+    key_file
+        .load_from_file(path, glib::KeyFileFlags::NONE)
+        .and_then(|_| key_file.boolean("Desktop Entry", GENERATED_KEY))
+        .unwrap_or_default()
+}
+
+/// Removes the generated entry with desktop id `id`.
+pub fn delete(id: &str) -> Result<(), String> {
+    let path = id
+        .strip_prefix(&id_prefix())
+        .map(|file_name| dir().join(file_name))
+        .filter(|path| is_generated_file(path))
+        .ok_or_else(|| format!("{} is not a generated desktop entry", id))?;
+    std::fs::remove_file(path).map_err(|err| err.to_string())
+}
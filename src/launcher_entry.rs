@@ -0,0 +1,181 @@
+// SPDX-License-Identifier: MPL-2.0-only
+
+//! Badge counts, progress and urgency that apps broadcast with the
+//! `com.canonical.Unity.LauncherEntry` API.
+
+use crate::dock_object::DockObject;
+use crate::utils::Event;
+use futures_util::StreamExt;
+use gtk4::prelude::*;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use zbus::{fdo, Connection, MessageStream, MessageType};
+use zvariant::OwnedValue;
+
+const INTERFACE: &str = "com.canonical.Unity.LauncherEntry";
+// apps that never reset their count tend to send the stale value again, which doesn't bring
+// back a dismissed badge for this long
+const DISMISS_GRACE: Duration = Duration::from_secs(30);
+
+/// The launcher state of one app, merged from all of its updates.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LauncherEntry {
+    pub count: i64,
+    pub count_visible: bool,
+    pub progress: f64,
+    pub progress_visible: bool,
+    pub urgent: bool,
+    /// what was shown when the user dismissed it, see [`LauncherEntry::dismiss`]
+    dismissed: Option<(Option<i64>, Option<f64>, bool, Instant)>,
+}
+
+impl LauncherEntry {
+    /// Updates only carry the properties that changed.
+    pub fn merge(&mut self, properties: &HashMap<String, OwnedValue>) {
+        let get_bool = |key: &str| properties.get(key).and_then(|v| bool::try_from(v.clone()).ok());
+        if let Some(count) = properties.get("count").and_then(|v| i64::try_from(v.clone()).ok()) {
+            self.count = count;
+        }
+        if let Some(progress) = properties
+            .get("progress")
+            .and_then(|v| f64::try_from(v.clone()).ok())
+        {
+            self.progress = progress.clamp(0.0, 1.0);
+        }
+        if let Some(visible) = get_bool("count-visible") {
+            self.count_visible = visible;
+        }
+        if let Some(visible) = get_bool("progress-visible") {
+            self.progress_visible = visible;
+        }
+        if let Some(urgent) = get_bool("urgent") {
+            self.urgent = urgent;
+        }
+        if let Some((badge, progress, urgent, at)) = self.dismissed {
+            let sent = (self.sent_badge(), self.sent_progress(), self.urgent);
+            if sent != (badge, progress, urgent) || at.elapsed() > DISMISS_GRACE {
+                self.dismissed = None;
+            }
+        }
+    }
+
+    /// Hides the badge, progress and urgency until the app sends other values, or sends the
+    /// same values again after a grace period.
+    pub fn dismiss(&mut self) {
+        self.dismissed = Some((
+            self.sent_badge(),
+            self.sent_progress(),
+            self.urgent,
+            Instant::now(),
+        ));
+    }
+
+    fn sent_badge(&self) -> Option<i64> {
+        Some(self.count).filter(|count| self.count_visible && *count > 0)
+    }
+
+    fn sent_progress(&self) -> Option<f64> {
+        Some(self.progress).filter(|_| self.progress_visible)
+    }
+
+    /// The badge to show, if any.
+    pub fn badge(&self) -> Option<i64> {
+        self.sent_badge().filter(|_| self.dismissed.is_none())
+    }
+
+    /// The progress to show, if any.
+    pub fn progress(&self) -> Option<f64> {
+        self.sent_progress().filter(|_| self.dismissed.is_none())
+    }
+
+    pub fn is_urgent(&self) -> bool {
+        self.urgent && self.dismissed.is_none()
+    }
+}
+
+/// Shows `entry` on the dock object, or clears what was shown if it is `None`.
+pub fn apply_to(dock_object: &DockObject, entry: Option<&LauncherEntry>) {
+    let count = entry.and_then(|e| e.badge()).unwrap_or(0);
+    let progress = entry.and_then(|e| e.progress()).unwrap_or(-1.0);
+    let urgent = entry.map(|e| e.is_urgent()).unwrap_or_default();
+    if dock_object.property::<i64>("count") != count {
+        dock_object.set_property("count", count);
+    }
+    if dock_object.property::<f64>("progress") != progress {
+        dock_object.set_property("progress", progress);
+    }
+    if dock_object.property::<bool>("urgent") != urgent {
+        dock_object.set_property("urgent", urgent);
+    }
+}
+
+// "application://firefox.desktop" -> "firefox.desktop"
+fn desktop_id(app_uri: &str) -> Option<&str> {
+    app_uri
+        .strip_prefix("application://")
+        .or_else(|| app_uri.strip_prefix("app://"))
+        .filter(|id| !id.is_empty())
+}
+
+/// Forwards launcher entry updates as events until the connection closes.
+/// When an app leaves the bus its entry is cleared.
+pub async fn listen(conn: Connection, tx: mpsc::Sender<Event>) -> zbus::Result<()> {
+    let dbus = fdo::DBusProxy::new(&conn).await?;
+    dbus.add_match(&format!("type='signal',interface='{}',member='Update'", INTERFACE))
+        .await?;
+    dbus.add_match(
+        "type='signal',sender='org.freedesktop.DBus',interface='org.freedesktop.DBus',member='NameOwnerChanged'",
+    )
+    .await?;
+    let mut stream = MessageStream::from(&conn);
+    // unique bus name of each sender -> desktop id it updated
+    let mut senders: HashMap<String, String> = HashMap::new();
+
+    while let Some(m) = stream.next().await {
+        let m = match m {
+            Ok(m) if m.message_type() == MessageType::Signal => m,
+            _ => continue,
+        };
+        let interface = m.interface().map(|i| i.to_string());
+        let member = m.member().map(|m| m.to_string());
+        match (interface.as_deref(), member.as_deref()) {
+            (Some(INTERFACE), Some("Update")) => {
+                let (app_uri, properties) = match m.body::<(String, HashMap<String, OwnedValue>)>() {
+                    Ok(body) => body,
+                    Err(_) => continue,
+                };
+                let id = match desktop_id(&app_uri) {
+                    Some(id) => id.to_string(),
+                    None => continue,
+                };
+                if let Ok(Some(sender)) = m
+                    .header()
+                    .and_then(|h| h.sender().map(|s| s.map(|s| s.to_string())))
+                {
+                    senders.insert(sender, id.clone());
+                }
+                if tx.send(Event::LauncherEntry(id, Some(properties))).await.is_err() {
+                    break;
+                }
+            }
+            (Some("org.freedesktop.DBus"), Some("NameOwnerChanged")) => {
+                let (name, _, new_owner) = match m.body::<(String, String, String)>() {
+                    Ok(body) => body,
+                    Err(_) => continue,
+                };
+                if !new_owner.is_empty() {
+                    continue;
+                }
+                if let Some(id) = senders.remove(&name) {
+                    if tx.send(Event::LauncherEntry(id, None)).await.is_err() {
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
@@ -1,320 +1,1765 @@
 // SPDX-License-Identifier: MPL-2.0-only
 
+use apps_container::AppsContainer;
 use apps_window::CosmicDockAppListWindow;
+use config::AppsConfig;
 use dock_list::DockListType;
 use dock_object::DockObject;
-use gio::{ApplicationFlags, DesktopAppInfo};
+use futures_util::future::{self, Either};
+use futures_util::{FutureExt, StreamExt};
+use launcher_entry::LauncherEntry;
+use gio::ApplicationFlags;
 use gtk4::gdk::Display;
-use gtk4::{glib, prelude::*, CssProvider, StyleContext};
-use once_cell::sync::OnceCell;
-use std::collections::BTreeMap;
-use std::sync::{Arc, Mutex};
+use gtk4::{glib, prelude::*};
+use dock_state::{apply_favorite, same_window, AppEntry, StackHistory};
+use model_sync::{apply_deltas, read_dock_state, sync_models};
+use pin_service::PinTargets;
+use recent::RecentApps;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use once_cell::sync::Lazy;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Weak};
 use std::time::Duration;
-use tokio::sync::mpsc;
-use utils::{block_on, BoxedWindowList, Event, Item, DEST, PATH};
-use zbus::Connection;
+use toast::Severity;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::{mpsc, watch, Notify};
+use utils::{
+    config_path, daemon_capabilities, daemon_supports, decode_window_list, mark_method_missing,
+    notify_window_list, probe_daemon, queue_refresh, record_focus, refresh_dequeued, send_event,
+    session_connection, sort_windows, timeout_error, update_current_workspace, update_focus_order,
+    window_focus, window_lists_differ, window_maximize, window_minimize, window_move_to_workspace,
+    window_quit, window_toggle_minimize, BoxedWindowList, Event, Item, WindowOperation,
+    DAEMON_TIMEOUT, DEST, PATH,
+};
+use zbus::{fdo, Connection, MessageStream, MessageType};
 
+mod app_id;
 mod apps_container;
 mod apps_window;
+mod config;
+mod custom_launcher;
 mod dock_item;
 mod dock_list;
 mod dock_object;
 mod dock_popover;
+mod desktop_cache;
+mod dock_state;
+mod generated_entry;
+mod launcher_entry;
 mod localize;
+mod model_sync;
+mod overrides;
+mod recent;
+mod saved_writer;
+mod pin_service;
+mod settings_page;
+mod style;
+mod system_items;
+mod toast;
 mod utils;
+mod watchdog;
+mod window_object;
 
 const ID: &str = "com.system76.CosmicDockAppList";
-static TX: OnceCell<mpsc::Sender<Event>> = OnceCell::new();
+const MAX_POLL_BACKOFF: Duration = Duration::from_secs(5);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
 
-fn spawn_zbus(sender: mpsc::Sender<Event>, cached_results: Arc<Mutex<Vec<Item>>>) -> Connection {
-    let connection = block_on(Connection::session()).unwrap();
+/// Handle to the runtime that keeps the cached window list up to date.
+struct ZbusHandle {
+    runtime: tokio::runtime::Runtime,
+    task: tokio::task::JoinHandle<()>,
+    cancel: watch::Sender<bool>,
+}
 
-    let conn = connection.clone();
-    let cached_window_list = Arc::clone(&cached_results);
-    let _zbus_handle = std::thread::spawn(move || {
-        block_on(async move {
-            loop {
-                let m = conn
-                    .call_method(Some(DEST), PATH, Some(DEST), "WindowList", &())
-                    .await;
-                if let Ok(m) = m {
-                    if let Ok(mut reply) = m.body::<Vec<Item>>() {
-                        let mut cached_results = cached_window_list.as_ref().lock().unwrap();
-                        reply.sort_by(|a, b| a.name.cmp(&b.name));
-
-                        if cached_results.len() != reply.len()
-                            || !reply.iter().zip(cached_results.iter()).fold(
-                                0,
-                                |acc, z: (&Item, &Item)| {
-                                    let (a, b) = z;
-                                    if a.name == b.name {
-                                        acc + 1
-                                    } else {
-                                        acc
-                                    }
-                                },
-                            ) == cached_results.len()
-                        {
-                            cached_results.splice(.., reply);
-                            let _ = sender.send(Event::WindowList).await;
-                        }
+impl ZbusHandle {
+    /// Runs `task` on a runtime of its own until it ends or [`ZbusHandle::stop`] is called.
+    fn spawn(task: impl std::future::Future<Output = ()> + Send + 'static) -> Self {
+        let (cancel, mut cancelled) = watch::channel(false);
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("app-list-zbus")
+            .enable_time()
+            .build()
+            .expect("Failed to create the zbus runtime");
+        let task = runtime.spawn(async move {
+            // pending D-Bus calls are dropped as soon as the app list is torn down
+            let cancelled = async move {
+                let _ = cancelled.changed().await;
+            };
+            future::select(Box::pin(task), Box::pin(cancelled)).await;
+        });
+        Self {
+            runtime,
+            task,
+            cancel,
+        }
+    }
+
+    // the task may be stuck in a D-Bus call, so only wait for it up to `timeout`
+    fn stop(self, timeout: Duration) {
+        let _ = self.cancel.send(true);
+        if self
+            .runtime
+            .block_on(tokio::time::timeout(timeout, self.task))
+            .is_err()
+        {
+            log::warn!("zbus task did not stop in time, dropping it");
+        }
+        // also ends the worker thread of the runtime
+        self.runtime.shutdown_background();
+    }
+}
+
+/// What the zbus thread and the desktop entry lookup tell every instance of the app list.
+#[derive(Debug, Clone, Copy)]
+enum Notice {
+    WindowList,
+    RefreshFromCache,
+    DaemonStatus(bool),
+    DaemonRestarted,
+}
+
+/// Fans the notices out to the instances. The daemon status is kept for instances that attach
+/// later.
+#[derive(Debug, Clone)]
+struct Notifier {
+    notices: broadcast::Sender<Notice>,
+    daemon_available: Arc<AtomicBool>,
+}
+
+impl Notifier {
+    fn new() -> Self {
+        let (notices, _) = broadcast::channel(16);
+        Self {
+            notices,
+            daemon_available: Arc::new(AtomicBool::new(true)),
+        }
+    }
+
+    fn send(&self, notice: Notice) {
+        if let Notice::DaemonStatus(available) = notice {
+            self.daemon_available.store(available, Ordering::Release);
+        }
+        // fails if no instance is attached, which has nothing to update then
+        let _ = self.notices.send(notice);
+    }
+}
+
+/// The zbus thread, the cached window list, the desktop entry lookup and the pin interface,
+/// shared by every instance of the app list in the process, e.g. when the dock loads one per
+/// output. They are stopped when the last instance detaches.
+struct SharedBackend {
+    zbus_handle: Option<ZbusHandle>,
+    cached_results: Arc<Mutex<Vec<Item>>>,
+    repoll: Arc<Notify>,
+    notifier: Notifier,
+    connection: watch::Receiver<Option<Connection>>,
+    pin_targets: PinTargets,
+}
+
+// the backend of the attached instances, it can't be upgraded once the last one detached
+static SHARED_BACKEND: Lazy<Mutex<Weak<SharedBackend>>> = Lazy::new(|| Mutex::new(Weak::new()));
+
+impl SharedBackend {
+    /// The backend of the instances that are running, started if there are none.
+    fn attach(config: &AppsConfig) -> Arc<Self> {
+        let mut shared = SHARED_BACKEND.lock().unwrap();
+        if let Some(backend) = shared.upgrade() {
+            return backend;
+        }
+        let backend = Arc::new(Self::start(config));
+        *shared = Arc::downgrade(&backend);
+        backend
+    }
+
+    fn start(config: &AppsConfig) -> Self {
+        let notifier = Notifier::new();
+        let cached_results = Arc::new(Mutex::new(Vec::new()));
+        let repoll = Arc::new(Notify::new());
+        let resolved_notifier = notifier.clone();
+        desktop_cache::start(move || resolved_notifier.send(Notice::RefreshFromCache));
+        let (zbus_handle, connection) = spawn_zbus(
+            notifier.clone(),
+            Arc::clone(&cached_results),
+            Arc::clone(&repoll),
+            config,
+        );
+        // exported once, a second instance exporting it at the same path would be refused
+        let pin_targets = PinTargets::default();
+        let conn_rx = wait_for_connection(connection.clone());
+        glib::MainContext::default().spawn_local(glib::clone!(@strong pin_targets => async move {
+            if let Some(conn) = conn_rx.await {
+                if let Err(err) = pin_service::serve(&conn, pin_targets).await {
+                    log::error!(
+                        "Failed to export the pin interface as {}: {}",
+                        pin_service::PIN_NAME,
+                        err
+                    );
+                }
+            }
+        }));
+        Self {
+            zbus_handle: Some(zbus_handle),
+            cached_results,
+            repoll,
+            notifier,
+            connection,
+            pin_targets,
+        }
+    }
+
+    /// Passes the notices on to the event loop of an instance, starting with the state the
+    /// backend has by now.
+    fn forward_notices(&self, tx: mpsc::Sender<Event>) -> glib::SourceId {
+        let mut notices = self.notifier.notices.subscribe();
+        let daemon_available = Arc::clone(&self.notifier.daemon_available);
+        glib::MainContext::default().spawn_local(async move {
+            if !daemon_available.load(Ordering::Acquire) {
+                let _ = tx.send(Event::DaemonStatus(false)).await;
+            }
+            notify_window_list(&tx).await;
+            while !tx.is_closed() {
+                match notices.recv().await {
+                    Ok(Notice::WindowList) => notify_window_list(&tx).await,
+                    Ok(Notice::RefreshFromCache) => queue_refresh(&tx),
+                    Ok(Notice::DaemonStatus(available)) => {
+                        let _ = tx.send(Event::DaemonStatus(available)).await;
+                    }
+                    Ok(Notice::DaemonRestarted) => {
+                        let _ = tx.send(Event::DaemonRestarted).await;
+                    }
+                    // the missed notices are made up for by the current state
+                    Err(RecvError::Lagged(_)) => {
+                        let available = daemon_available.load(Ordering::Acquire);
+                        let _ = tx.send(Event::DaemonStatus(available)).await;
+                        notify_window_list(&tx).await;
                     }
-                    glib::timeout_future(Duration::from_millis(200)).await;
+                    Err(RecvError::Closed) => break,
                 }
             }
         })
-    });
+    }
+}
 
-    connection
+impl Drop for SharedBackend {
+    fn drop(&mut self) {
+        log::info!("Stopping the desktop entry lookup");
+        {
+            let _span = watchdog::span("stop desktop entry lookup");
+            desktop_cache::stop();
+        }
+        let conn = self.connection.borrow().clone();
+        if let (Some(zbus_handle), Some(conn)) = (&self.zbus_handle, conn) {
+            let _span = watchdog::span("withdraw pin interface");
+            let withdraw = pin_service::withdraw(&conn);
+            match zbus_handle
+                .runtime
+                .block_on(tokio::time::timeout(Duration::from_secs(1), withdraw))
+            {
+                Ok(Err(err)) => log::warn!("Failed to withdraw the pin interface: {}", err),
+                Err(_) => log::warn!("Withdrawing the pin interface did not finish in time"),
+                Ok(Ok(())) => {}
+            }
+        }
+        if let Some(zbus_handle) = self.zbus_handle.take() {
+            log::info!("Stopping the zbus thread");
+            let _span = watchdog::span("stop zbus thread");
+            zbus_handle.stop(Duration::from_secs(1));
+        }
+    }
 }
 
-pub fn localize() {
-    let localizer = crate::localize::localizer();
-    let requested_languages = i18n_embed::DesktopLanguageRequester::requested_languages();
+// the connection of the zbus thread once it is ready, `None` if it failed to connect
+async fn wait_for_connection(
+    mut connection: watch::Receiver<Option<Connection>>,
+) -> Option<Connection> {
+    loop {
+        if let Some(conn) = connection.borrow().clone() {
+            return Some(conn);
+        }
+        connection.changed().await.ok()?;
+    }
+}
+
+/// The event loop and the app list models of one instance of the app list, attached to the
+/// [`SharedBackend`]. Views of the instance show the backend's [`AppsContainer`] instead of
+/// building their own, so they all receive the same model syncs.
+#[derive(Default)]
+struct AppListBackend {
+    shared: Option<Arc<SharedBackend>>,
+    forward_handle: Option<glib::SourceId>,
+    event_handle: Option<glib::SourceId>,
+    tx: Option<mpsc::Sender<Event>>,
+    apps_container: Option<AppsContainer>,
+    config_monitor: Option<gio::FileMonitor>,
+    settings: Option<(gio::Settings, glib::SignalHandlerId)>,
+    overrides_monitor: Option<gio::FileMonitor>,
+    app_info_monitor: Option<(gio::AppInfoMonitor, glib::SignalHandlerId)>,
+}
+
+impl AppListBackend {
+    pub fn start() -> Self {
+        let (tx, rx) = mpsc::channel(100);
+        start_backend(tx, rx)
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.event_handle.is_some()
+    }
 
-    if let Err(error) = localizer.select(&requested_languages) {
-        eprintln!("Error while loading language for App List {}", error);
+    /// The app list kept up to date by the backend, `None` once it was torn down.
+    pub fn apps_container(&self) -> Option<&AppsContainer> {
+        self.apps_container.as_ref()
+    }
+
+    // only tears down the parts that were actually created
+    fn teardown(&mut self) {
+        let _span = watchdog::span("teardown");
+        // stops the event loop, which drops the receiver and closes the channel
+        for handle in [self.forward_handle.take(), self.event_handle.take()] {
+            let source = handle.and_then(|h| glib::MainContext::default().find_source_by_id(&h));
+            if let Some(source) = source {
+                source.destroy();
+            }
+        }
+        // the zbus thread keeps running for the other instances
+        if let Some(shared) = self.shared.take() {
+            if let Some(tx) = &self.tx {
+                shared.pin_targets.detach(tx);
+            }
+        }
+        drop(self.tx.take());
+        if let Some(config_monitor) = self.config_monitor.take() {
+            config_monitor.cancel();
+        }
+        if let Some((settings, handler)) = self.settings.take() {
+            settings.disconnect(handler);
+        }
+        if let Some(overrides_monitor) = self.overrides_monitor.take() {
+            overrides_monitor.cancel();
+        }
+        if let Some((monitor, handler)) = self.app_info_monitor.take() {
+            monitor.disconnect(handler);
+        }
+        // the volume monitor is shared, so its handlers have to be removed
+        if let Some(apps_container) = self.apps_container.take() {
+            log::info!("Writing the saved apps");
+            let _span = watchdog::span("flush saved apps");
+            apps_container.flush_saved();
+            apps_container.set_show_system(false);
+        }
     }
 }
 
-fn load_css() {
-    let provider = CssProvider::new();
-    provider.load_from_data(include_bytes!("style.css"));
+// runs on its own runtime so a slow session bus doesn't block the UI,
+// the connection is handed back through the returned receiver once it is ready
+fn spawn_zbus(
+    notifier: Notifier,
+    cached_results: Arc<Mutex<Vec<Item>>>,
+    repoll: Arc<Notify>,
+    config: &AppsConfig,
+) -> (ZbusHandle, watch::Receiver<Option<Connection>>) {
+    let poll_interval = config.poll_interval();
+    let (conn_tx, conn_rx) = watch::channel(None);
+    let watch = watch_window_list(notifier, cached_results, repoll, poll_interval, conn_tx);
+    (ZbusHandle::spawn(watch), conn_rx)
+}
+
+// a daemon that stopped replying must not stall the zbus thread, the next poll tries again
+async fn with_timeout<T>(
+    method: &str,
+    call: impl std::future::Future<Output = zbus::Result<T>>,
+) -> zbus::Result<T> {
+    watchdog::timed(method, tokio::time::timeout(DAEMON_TIMEOUT, call))
+        .await
+        .unwrap_or_else(|_| Err(timeout_error(method)))
+}
 
-    StyleContext::add_provider_for_display(
-        &Display::default().unwrap(),
-        &provider,
-        gtk4::STYLE_PROVIDER_PRIORITY_APPLICATION,
+// the windows and the unique name of the daemon that listed them, with the extended fields if
+// the daemon implements `WindowListExtended`, otherwise only with the legacy ones
+async fn fetch_window_list(conn: &Connection) -> zbus::Result<(Option<String>, Vec<Item>)> {
+    if daemon_supports("WindowListExtended") {
+        let call = conn.call_method(Some(DEST), PATH, Some(DEST), "WindowListExtended", &());
+        match with_timeout("WindowListExtended", call).await {
+            Ok(reply) => {
+                let owner = reply.sender().map(|sender| sender.to_string());
+                return Ok((owner, decode_window_list(&reply, true)?));
+            }
+            Err(zbus::Error::MethodError(name, _, _))
+                if name.as_str() == "org.freedesktop.DBus.Error.UnknownMethod" =>
+            {
+                mark_method_missing("WindowListExtended");
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    let call = conn.call_method(Some(DEST), PATH, Some(DEST), "WindowList", &());
+    let reply = with_timeout("WindowList", call).await?;
+    let owner = reply.sender().map(|sender| sender.to_string());
+    Ok((owner, decode_window_list(&reply, false)?))
+}
+
+// the entities of a restarted daemon mean nothing to the new one and may even be reused by it,
+// so the windows are forgotten until the new daemon lists them
+fn update_owner(
+    owner: &mut Option<String>,
+    new_owner: Option<String>,
+    cached_window_list: &Arc<Mutex<Vec<Item>>>,
+    notifier: &Notifier,
+) {
+    if *owner == new_owner {
+        return;
+    }
+    let restarted = owner.is_some();
+    *owner = new_owner;
+    if !restarted {
+        return;
+    }
+    log::info!("The window daemon restarted, forgetting its windows");
+    cached_window_list.lock().unwrap().clear();
+    update_focus_order(&[], None);
+    notifier.send(Notice::DaemonRestarted);
+    notifier.send(Notice::WindowList);
+}
+
+// the order the daemon activated the windows in, most recent first, `None` if it doesn't
+// report it
+async fn fetch_window_stack(conn: &Connection) -> Option<Vec<(u32, u32)>> {
+    if !daemon_supports("WindowStack") {
+        return None;
+    }
+    let call = conn.call_method(Some(DEST), PATH, Some(DEST), "WindowStack", &());
+    match with_timeout("WindowStack", call).await {
+        Ok(reply) => reply.body::<Vec<(u32, u32)>>().ok(),
+        Err(zbus::Error::MethodError(name, _, _))
+            if name.as_str() == "org.freedesktop.DBus.Error.UnknownMethod" =>
+        {
+            mark_method_missing("WindowStack");
+            None
+        }
+        Err(err) => {
+            log::warn!("Failed to fetch the window stack: {}", err);
+            None
+        }
+    }
+}
+
+async fn watch_window_list(
+    notifier: Notifier,
+    cached_window_list: Arc<Mutex<Vec<Item>>>,
+    repoll: Arc<Notify>,
+    poll_interval: Duration,
+    conn_tx: watch::Sender<Option<Connection>>,
+) {
+    let conn = match session_connection().await {
+        Ok(conn) => conn,
+        Err(err) => {
+            log::warn!("Failed to connect to the session bus: {}", err);
+            notifier.send(Notice::DaemonStatus(false));
+            return;
+        }
+    };
+    let _ = conn_tx.send(Some(conn.clone()));
+    if let Err(err) = with_timeout("Introspect", probe_daemon(&conn)).await {
+        log::warn!("Failed to introspect the daemon: {}", err);
+    }
+    let mut available = true;
+    let mut delay = poll_interval;
+    let mut try_subscribe = true;
+    let mut owner = None;
+    // runs until the last instance detaches and the task is cancelled
+    loop {
+        // prefer the WindowsChanged signal, older daemons and daemons that couldn't be
+        // introspected are polled
+        if try_subscribe && daemon_capabilities().supports_signal("WindowsChanged") {
+            try_subscribe = false;
+            let subscribed =
+                subscribe_window_list(&conn, &notifier, &cached_window_list, &mut owner).await;
+            if let Err(err) = subscribed {
+                log::info!("WindowsChanged signal unavailable, polling instead: {}", err);
+            }
+        }
+        match fetch_window_list(&conn).await {
+            Ok((reply_owner, reply)) => {
+                update_owner(&mut owner, reply_owner, &cached_window_list, &notifier);
+                if !available {
+                    available = true;
+                    delay = poll_interval;
+                    // it may be a different version of the daemon
+                    if let Err(err) = with_timeout("Introspect", probe_daemon(&conn)).await {
+                        log::warn!("Failed to introspect the daemon: {}", err);
+                    }
+                    // the daemon came back, it may support the signal now
+                    try_subscribe = true;
+                    notifier.send(Notice::DaemonStatus(true));
+                }
+                update_cached_window_list(&conn, &cached_window_list, reply, &notifier).await;
+            }
+            Err(err) => {
+                if available {
+                    available = false;
+                    log::warn!("Window list unavailable: {}", err);
+                    // cleared here rather than by the instances, which may only see the
+                    // notice after the daemon came back
+                    cached_window_list.lock().unwrap().clear();
+                    notifier.send(Notice::DaemonStatus(false));
+                }
+                delay = (delay * 2).min(MAX_POLL_BACKOFF);
+            }
+        }
+        // the result of an operation on a window is fetched right away
+        future::select(
+            Box::pin(tokio::time::sleep(delay)),
+            Box::pin(repoll.notified()),
+        )
+        .await;
+    }
+}
+
+async fn subscribe_window_list(
+    conn: &Connection,
+    notifier: &Notifier,
+    cached_window_list: &Arc<Mutex<Vec<Item>>>,
+    owner: &mut Option<String>,
+) -> zbus::Result<()> {
+    let rule = format!(
+        "type='signal',sender='{}',path='{}',interface='{}',member='WindowsChanged'",
+        DEST, PATH, DEST
     );
+    let dbus = fdo::DBusProxy::new(conn).await?;
+    dbus.add_match(&rule).await?;
+    // a restarted or upgraded daemon takes over the name
+    dbus.add_match(&format!(
+        "type='signal',sender='org.freedesktop.DBus',interface='org.freedesktop.DBus',member='NameOwnerChanged',arg0='{}'",
+        DEST
+    ))
+    .await?;
+    let mut stream = MessageStream::from(conn);
+
+    // the signal only fires on changes, so fetch the initial state once
+    let (reply_owner, reply) = fetch_window_list(conn).await?;
+    update_owner(owner, reply_owner, cached_window_list, notifier);
+    update_cached_window_list(conn, cached_window_list, reply, notifier).await;
+
+    while let Some(m) = stream.next().await {
+        let m = match m {
+            Ok(m) => m,
+            Err(_) => continue,
+        };
+        if m.message_type() == MessageType::Signal
+            && m.member().as_ref().map(|m| m.as_str()) == Some("NameOwnerChanged")
+        {
+            match m.body::<(String, String, String)>() {
+                Ok((name, _, new_owner)) if name == DEST => {
+                    let new_owner = Some(new_owner).filter(|new_owner| !new_owner.is_empty());
+                    update_owner(owner, new_owner.clone(), cached_window_list, notifier);
+                    // without an owner the daemon is gone, polling notices that and resubscribes later
+                    if new_owner.is_none() {
+                        return Err(zbus::Error::InvalidReply);
+                    }
+                    with_timeout("Introspect", probe_daemon(conn)).await?;
+                    if !daemon_capabilities().supports_signal("WindowsChanged") {
+                        return Err(zbus::Error::Unsupported);
+                    }
+                    let (reply_owner, reply) = fetch_window_list(conn).await?;
+                    update_owner(owner, reply_owner, cached_window_list, notifier);
+                    update_cached_window_list(conn, cached_window_list, reply, notifier).await;
+                }
+                _ => {}
+            }
+            continue;
+        }
+        if m.message_type() != MessageType::Signal
+            || m.interface().as_ref().map(|i| i.as_str()) != Some(DEST)
+            || m.member().as_ref().map(|m| m.as_str()) != Some("WindowsChanged")
+        {
+            continue;
+        }
+        // daemons with `WindowListExtended` send the extended format with the signal as well
+        let reply = decode_window_list(&m, true).or_else(|_| decode_window_list(&m, false));
+        if let Ok(reply) = reply {
+            update_cached_window_list(conn, cached_window_list, reply, notifier).await;
+        }
+    }
+    Err(zbus::Error::InvalidReply)
 }
 
-fn main() {
-    // Initialize logger
-    pretty_env_logger::init();
-    glib::set_application_name("Cosmic Dock App List");
+async fn update_cached_window_list(
+    conn: &Connection,
+    cached_window_list: &Arc<Mutex<Vec<Item>>>,
+    mut reply: Vec<Item>,
+    notifier: &Notifier,
+) {
+    reply.sort_by(|a, b| a.entity.cmp(&b.entity));
+    update_current_workspace(&reply);
+    let changed = {
+        let mut cached_results = cached_window_list.as_ref().lock().unwrap();
+        if window_lists_differ(&cached_results, &reply) {
+            cached_results.splice(.., reply);
+            true
+        } else {
+            false
+        }
+    };
+    if changed {
+        // focus changes also change the window list, so the order is fetched with it
+        let stack = fetch_window_stack(conn).await;
+        update_focus_order(&cached_window_list.lock().unwrap(), stack.as_deref());
+        notifier.send(Notice::WindowList);
+    }
+}
 
-    localize();
+/// Selects the desktop language, strings without a translation fall back to English.
+/// Only the first call has an effect.
+pub fn localize() {
+    static LOCALIZE: std::sync::Once = std::sync::Once::new();
+    LOCALIZE.call_once(|| {
+        let localizer = crate::localize::localizer();
+        let requested_languages = i18n_embed::DesktopLanguageRequester::requested_languages();
 
-    gio::resources_register_include!("compiled.gresource").unwrap();
-    let app = gtk4::Application::new(Some(ID), ApplicationFlags::default());
+        if let Err(error) = localizer.select(&requested_languages) {
+            log::warn!("Error while loading language for App List {}", error);
+        }
+    });
+}
 
-    app.connect_activate(|app| {
-        load_css();
-        let (tx, mut rx) = mpsc::channel(100);
+// syncs the models with the window list and restores the state that doesn't come from it
+fn refresh_models(
+    windows: &[Item],
+    apps_container: &AppsContainer,
+    stack_history: &mut StackHistory,
+    launcher_entries: &HashMap<String, LauncherEntry>,
+    recent: &RecentApps,
+) {
+    let _span = watchdog::span("refresh models");
+    sync_models(windows, apps_container, stack_history);
+    apps_container.update_recent(recent);
+    apps_container.update_window_model(windows);
+    apps_container.update_overlap(windows);
+    // rebuilt dock objects start without the launcher entry state
+    for (id, entry) in launcher_entries.iter() {
+        for dock_object in apps_container.dock_objects_by_id(id) {
+            launcher_entry::apply_to(&dock_object, Some(entry));
+        }
+    }
+}
 
-        let window = CosmicDockAppListWindow::new(app, tx.clone());
+/// Applies the settings that can change while the dock runs, the poll interval only applies
+/// on the next start. The models are refreshed when the change affects which apps are listed.
+fn apply_config(apps_container: &AppsContainer, config: &AppsConfig, tx: &mpsc::Sender<Event>) {
+    apps_container.set_click_action(config.click_action);
+    apps_container.set_show_system(config.show_system);
+    apps_container.set_icon_size_override(config.icon_size);
+    apps_container.set_display_mode(config.display_mode);
+    apps_container.set_hover_previews(config.hover_previews);
+    apps_container.set_animations_enabled(config.animations_enabled());
+    if config.show_saved != apps_container.shows_saved()
+        || config.sort_order != apps_container.sort_order()
+        || config.grouping_mode != apps_container.grouping_mode()
+        || config.show_active != apps_container.shows_active()
+        || config.all_outputs != apps_container.shows_all_outputs()
+        || config.recent_count != apps_container.recent_count()
+    {
+        apps_container.set_recent_count(config.recent_count);
+        apps_container.set_show_saved(config.show_saved);
+        apps_container.set_show_active(config.show_active);
+        apps_container.set_all_outputs(config.all_outputs);
+        apps_container.set_sort_order(config.sort_order);
+        apps_container.set_grouping_mode(config.grouping_mode);
+        queue_refresh(tx);
+    }
+}
 
-        let apps_container = apps_container::AppsContainer::new(tx.clone());
-        let cached_results = Arc::new(Mutex::new(Vec::new()));
-        // let zbus_conn = spawn_zbus(tx.clone(), Arc::clone(&cached_results));
-        TX.set(tx.clone()).unwrap();
-
-        let _ = glib::MainContext::default().spawn_local(async move {
-            while let Some(event) = rx.recv().await {
-                match event {
-                    Event::Activate(_) => {
-                        // let _activate_window = zbus_conn
-                        //     .call_method(Some(DEST), PATH, Some(DEST), "WindowFocus", &((e,)))
-                        //     .await
-                        //     .expect("Failed to focus selected window");
+// saves or removes the app, the models already match the window list so the refresh
+// only updates the recent apps and nothing moves a second time
+fn set_favorite(
+    apps_container: &AppsContainer,
+    tx: &mpsc::Sender<Event>,
+    id: &str,
+    favorite: bool,
+    index: Option<u32>,
+) {
+    match AppEntry::from_id(id) {
+        Some(app) => {
+            let mut state = read_dock_state(apps_container);
+            let index = index.map(|index| index as usize);
+            let deltas = apply_favorite(&mut state, app, favorite, index);
+            if !deltas.is_empty() {
+                apply_deltas(deltas, apps_container);
+                queue_refresh(tx);
+            }
+        }
+        None => log::warn!("Failed to resolve favorite {}", id),
+    }
+}
+
+// whether a model sync is queued, so a burst of refreshes is synced once
+#[derive(Default)]
+struct PendingSync(bool);
+
+impl PendingSync {
+    // true when the refresh has to queue a sync, false when one is already queued
+    fn queue(&mut self) -> bool {
+        !std::mem::replace(&mut self.0, true)
+    }
+
+    // true when the queued sync has to run before the event is handled
+    fn take_before(&mut self, event: &Event) -> bool {
+        if matches!(event, Event::RefreshFromCache | Event::WindowList) {
+            return false;
+        }
+        std::mem::take(&mut self.0)
+    }
+}
+
+// a successful operation fetches the window list right away, a failed one is reported back to
+// the event loop so the dock can undo what it showed before the daemon replied
+fn report_result(
+    tx: &mpsc::Sender<Event>,
+    repoll: &Notify,
+    op: WindowOperation,
+    entity: (u32, u32),
+    result: zbus::Result<()>,
+) {
+    match result {
+        Ok(()) => repoll.notify_one(),
+        Err(err) => {
+            log::warn!("Failed to apply {:?} to window {:?}: {}", op, entity, err);
+            send_event(
+                tx,
+                Event::OperationFailed {
+                    op,
+                    entity,
+                    message: err.to_string(),
+                },
+            );
+        }
+    }
+}
+
+/// Starts the zbus thread, the file monitors and the event loop that keeps the models up to date.
+fn start_backend(tx: mpsc::Sender<Event>, mut rx: mpsc::Receiver<Event>) -> AppListBackend {
+    // the dock may start the app list without going through main
+    localize();
+    let apps_container = AppsContainer::new(tx.clone());
+    let mut state = AppListBackend::default();
+    let config = AppsConfig::load();
+    apply_config(&apps_container, &config, &tx);
+    // settings that can change without rebuilding the dock, the config file is only
+    // used when the GSettings schema isn't installed
+    if let Some(settings) = config::settings() {
+        let config_tx = tx.clone();
+        let handler = settings.connect_changed(
+            None,
+            glib::clone!(@weak apps_container => move |settings, _| {
+                apply_config(&apps_container, &AppsConfig::from_settings(settings), &config_tx);
+            }),
+        );
+        state.settings = Some((settings, handler));
+    } else {
+        let config_file = gio::File::for_path(config_path());
+        match config_file.monitor_file(gio::FileMonitorFlags::NONE, None::<&gio::Cancellable>) {
+            Ok(monitor) => {
+                let config_tx = tx.clone();
+                monitor.connect_changed(glib::clone!(@weak apps_container => move |_, _, _, event| {
+                    if !matches!(
+                        event,
+                        gio::FileMonitorEvent::ChangesDoneHint | gio::FileMonitorEvent::Created
+                    ) {
+                        return;
                     }
-                    Event::Close(_) => {
-                        // let _activate_window = zbus_conn
-                        //     .call_method(Some(DEST), PATH, Some(DEST), "WindowQuit", &((e,)))
-                        //     .await
-                        //     .expect("Failed to close selected window");
+                    apply_config(&apps_container, &AppsConfig::load(), &config_tx);
+                }));
+                state.config_monitor = Some(monitor);
+            }
+            Err(err) => log::warn!("Failed to watch the config file: {}", err),
+        }
+    }
+    overrides::load();
+    let overrides_file = gio::File::for_path(overrides::overrides_path());
+    match overrides_file.monitor_file(gio::FileMonitorFlags::NONE, None::<&gio::Cancellable>) {
+        Ok(monitor) => {
+            monitor.connect_changed(glib::clone!(@weak apps_container => move |_, _, _, event| {
+                if !matches!(
+                    event,
+                    gio::FileMonitorEvent::ChangesDoneHint
+                        | gio::FileMonitorEvent::Created
+                        | gio::FileMonitorEvent::Deleted
+                ) {
+                    return;
+                }
+                // the dock items pick up the new icon and name when the app info is notified
+                for id in overrides::load() {
+                    for dock_object in apps_container.dock_objects_by_id(&id) {
+                        dock_object.notify("appinfo");
                     }
-                    Event::Favorite((name, should_favorite)) => {
-                        dbg!(&name);
-                        dbg!(should_favorite);
-                        let saved_app_model = apps_container.model(DockListType::Saved);
-                        let active_app_model = apps_container.model(DockListType::Active);
-                        if should_favorite {
-                            let mut cur: u32 = 0;
-                            let mut index: Option<u32> = None;
-                            while let Some(item) = active_app_model.item(cur) {
-                                if let Ok(cur_dock_object) = item.downcast::<DockObject>() {
-                                    if cur_dock_object.get_path() == Some(name.clone()) {
-                                        cur_dock_object.set_saved(true);
-                                        index = Some(cur);
-                                    }
-                                }
-                                cur += 1;
-                            }
-                            if let Some(index) = index {
-                                let object = active_app_model.item(index).unwrap();
-                                active_app_model.remove(index);
-                                saved_app_model.append(&object);
-                            }
-                        } else {
-                            let mut cur: u32 = 0;
-                            let mut index: Option<u32> = None;
-                            while let Some(item) = saved_app_model.item(cur) {
-                                if let Ok(cur_dock_object) = item.downcast::<DockObject>() {
-                                    if cur_dock_object.get_path() == Some(name.clone()) {
-                                        cur_dock_object.set_saved(false);
-                                        index = Some(cur);
-                                    }
+                }
+            }));
+            state.overrides_monitor = Some(monitor);
+        }
+        Err(err) => log::warn!("Failed to watch the app overrides: {}", err),
+    }
+    // another instance may have started the zbus thread already
+    let shared = SharedBackend::attach(&config);
+    let cached_results = Arc::clone(&shared.cached_results);
+    let repoll = Arc::clone(&shared.repoll);
+    let conn_rx = Box::pin(wait_for_connection(shared.connection.clone()));
+    state.forward_handle = Some(shared.forward_notices(tx.clone()));
+    shared.pin_targets.attach(tx.clone());
+    let pin_targets = shared.pin_targets.clone();
+    state.shared = Some(shared);
+    let pin_tx = tx.clone();
+    glib::MainContext::default().spawn_local(glib::clone!(@weak apps_container => async move {
+        let timeout = glib::timeout_future(CONNECT_TIMEOUT);
+        let zbus_conn = match future::select(conn_rx, timeout).await {
+            Either::Left((conn, _)) => conn,
+            Either::Right((_, conn_rx)) => {
+                // show the offline dock until the bus shows up
+                log::info!("Session bus not ready after {:?}", CONNECT_TIMEOUT);
+                apps_container.set_daemon_available(false);
+                let conn = conn_rx.await;
+                if conn.is_some() {
+                    apps_container.set_daemon_available(true);
+                }
+                conn
+            }
+        };
+        let zbus_conn = match zbus_conn {
+            Some(zbus_conn) => zbus_conn,
+            None => {
+                apps_container.set_daemon_available(false);
+                return;
+            }
+        };
+        let launcher_entries = launcher_entry::listen(zbus_conn.clone(), pin_tx.clone());
+        glib::MainContext::default().spawn_local(launcher_entries.map(|res| {
+            if let Err(err) = res {
+                log::warn!("Failed to listen for launcher entry updates: {}", err);
+            }
+        }));
+        apps_container.connect_local(
+            "pinned-changed",
+            false,
+            glib::clone!(@weak apps_container => @default-return None, move |_| {
+                let pinned = apps_container.saved_ids();
+                // pins made over D-Bus reach every instance, only the first one announces them
+                if !pin_targets.should_announce(&pinned) {
+                    return None;
+                }
+                let conn = zbus_conn.clone();
+                glib::MainContext::default().spawn_local(async move {
+                    if let Err(err) = pin_service::emit_pinned_changed(&conn, pinned).await {
+                        log::warn!("Failed to emit PinnedChanged: {}", err);
+                    }
+                });
+                None
+            }),
+        );
+    }));
+    // apps installed or removed while the dock runs
+    let app_info_monitor = gio::AppInfoMonitor::get();
+    let app_info_tx = tx.clone();
+    let handler = app_info_monitor.connect_changed(glib::clone!(@weak apps_container => move |_| {
+        desktop_cache::invalidate();
+        for type_ in [DockListType::Saved, DockListType::Active] {
+            let model = apps_container.model(type_);
+            let mut i = 0;
+            while let Some(item) = model.item(i) {
+                if let Ok(dock_object) = item.downcast::<DockObject>() {
+                    dock_object.refresh_app_info();
+                }
+                i += 1;
+            }
+        }
+        // running apps are matched to desktop files again when the active list is rebuilt
+        let active_model = apps_container.model(DockListType::Active);
+        active_model.splice(0, active_model.n_items(), &[] as &[glib::Object]);
+        queue_refresh(&app_info_tx);
+    }));
+    state.app_info_monitor = Some((app_info_monitor, handler));
+    state.tx = Some(tx.clone());
+    state.apps_container = Some(apps_container.clone());
+
+    let event_handle = glib::MainContext::default().spawn_local(async move {
+        let mut launcher_entries: HashMap<String, LauncherEntry> = HashMap::new();
+        let mut stack_history = StackHistory::default();
+        let mut pending_sync = PendingSync::default();
+        let mut recent = RecentApps::load();
+        // windows minimized by scrolling on the dock, and whether the window list showed them
+        // minimized yet
+        let mut minimized_by_dock: Vec<((u32, u32), bool)> = Vec::new();
+        // the window list the models were last refreshed from
+        let mut shown_windows: Vec<Item> = Vec::new();
+        while let Some(event) = rx.recv().await {
+            let _span = watchdog::span(format!("event {}", event.name()));
+            // every other event sees the models as of the refreshes queued before it
+            if pending_sync.take_before(&event) {
+                let cached_results = cached_results.as_ref().lock().unwrap();
+                refresh_models(
+                    &cached_results,
+                    &apps_container,
+                    &mut stack_history,
+                    &launcher_entries,
+                    &recent,
+                );
+                shown_windows = cached_results.to_vec();
+                // windows restored by the user since then are left alone
+                minimized_by_dock.retain_mut(|(entity, seen)| {
+                    let minimized = cached_results
+                        .iter()
+                        .find(|w| w.entity == *entity)
+                        .map(|w| w.minimized);
+                    match minimized {
+                        Some(true) => {
+                            *seen = true;
+                            true
+                        }
+                        Some(false) => !*seen,
+                        None => false,
+                    }
+                });
+            }
+            match event {
+                Event::ActivateIndex { app_id, index } => {
+                    let dock_object = apps_container.dock_object_for_app(&app_id);
+                    let windows = dock_object
+                        .as_ref()
+                        .map(|o| sort_windows(o.property::<BoxedWindowList>("active").0).0)
+                        .unwrap_or_default();
+                    // out of range indices focus the last window
+                    if let Some(window) = windows.get(index).or_else(|| windows.last()) {
+                        send_event(&tx, Event::Activate(window.entity));
+                    } else if let Some(id) = dock_object
+                        .and_then(|o| o.get_id())
+                        .or_else(|| {
+                            [app_id.clone(), format!("{}.desktop", app_id)]
+                                .into_iter()
+                                .find(|id| generated_entry::app_info(id).is_some())
+                        })
+                    {
+                        send_event(&tx, Event::Launch(id));
+                    } else {
+                        log::info!("No windows or desktop entry for {}", app_id);
+                    }
+                }
+                Event::Activate(entity) => {
+                    // the window may have closed since the dock last showed it, or belong to
+                    // another app after a daemon restart
+                    let shown = same_window(
+                        &shown_windows,
+                        &cached_results.as_ref().lock().unwrap(),
+                        entity,
+                    );
+                    if !shown {
+                        log::info!("Not focusing window {:?}, it no longer exists", entity);
+                        continue;
+                    }
+                    record_focus(entity);
+                    // waits for the shared connection if it isn't ready yet, later events queue up meanwhile
+                    let result = window_focus(entity).await;
+                    report_result(&tx, &repoll, WindowOperation::Focus, entity, result);
+                }
+                Event::Toggle(entity) => {
+                    let result = window_toggle_minimize(entity).await;
+                    report_result(&tx, &repoll, WindowOperation::Minimize, entity, result);
+                }
+                Event::Minimize(entity) => {
+                    let result = window_minimize(entity).await;
+                    report_result(&tx, &repoll, WindowOperation::Minimize, entity, result);
+                }
+                Event::Maximize(entity) => {
+                    let result = window_maximize(entity).await;
+                    report_result(&tx, &repoll, WindowOperation::Maximize, entity, result);
+                }
+                Event::MoveToWorkspace(entity, workspace) => {
+                    let result = window_move_to_workspace(entity, workspace).await;
+                    report_result(&tx, &repoll, WindowOperation::MoveToWorkspace, entity, result);
+                }
+                Event::Close(entity) => {
+                    apps_container.note_quit_from_dock(&[entity]);
+                    let result = window_quit(entity).await;
+                    report_result(&tx, &repoll, WindowOperation::Close, entity, result);
+                }
+                Event::CloseAll(entities) => {
+                    apps_container.note_quit_from_dock(&entities);
+                    // windows may already be gone, keep closing the rest and only report the first error
+                    let mut failed = None;
+                    for entity in entities {
+                        if let Err(err) = window_quit(entity).await {
+                            log::warn!("Failed to close window {:?}: {}", entity, err);
+                            failed.get_or_insert((entity, err));
+                        }
+                    }
+                    repoll.notify_one();
+                    if let Some((entity, err)) = failed {
+                        send_event(
+                            &tx,
+                            Event::OperationFailed {
+                                op: WindowOperation::Close,
+                                entity,
+                                message: err.to_string(),
+                            },
+                        );
+                    }
+                }
+                Event::OperationFailed { op, entity, message } => {
+                    // e.g. the row of a window that didn't close comes back
+                    if op == WindowOperation::Close {
+                        apps_container.restore_window(entity);
+                    }
+                    let message = match op {
+                        WindowOperation::Focus => fl!("focus-failed", error = message),
+                        WindowOperation::Close => fl!("close-failed", error = message),
+                        WindowOperation::Minimize => fl!("minimize-failed", error = message),
+                        WindowOperation::Maximize => fl!("maximize-failed", error = message),
+                        WindowOperation::MoveToWorkspace => fl!("move-failed", error = message),
+                    };
+                    apps_container.show_toast(Severity::Error, message);
+                }
+                Event::MinimizeAll(output) => {
+                    let windows: Vec<Item> = cached_results
+                        .as_ref()
+                        .lock()
+                        .unwrap()
+                        .iter()
+                        .filter(|w| !w.minimized)
+                        .filter(|w| output.is_none() || w.output() == output.as_deref())
+                        .cloned()
+                        .collect();
+                    for window in windows {
+                        match window_minimize(window.entity).await {
+                            Ok(()) => {
+                                if !minimized_by_dock.iter().any(|(e, _)| *e == window.entity) {
+                                    minimized_by_dock.push((window.entity, false));
                                 }
-                                cur += 1;
                             }
-                            if let Some(index) = index {
-                                let object = saved_app_model.item(index).unwrap();
-                                saved_app_model.remove(index);
-                                active_app_model.append(&object);
+                            Err(err) => {
+                                log::warn!("Failed to minimize window {:?}: {}", window.entity, err)
                             }
                         }
-                        let _ = tx.send(Event::RefreshFromCache).await;
                     }
-                    Event::RefreshFromCache => {
-                        // println!("refreshing model from cache");
+                }
+                Event::RestoreAll => {
+                    let minimized: Vec<(u32, u32)> = {
                         let cached_results = cached_results.as_ref().lock().unwrap();
-                        let stack_active = cached_results.iter().fold(
-                            BTreeMap::new(),
-                            |mut acc: BTreeMap<String, BoxedWindowList>, elem:&Item| {
-                                if let Some(v) = acc.get_mut(&elem.description) {
-                                    v.0.push(elem.clone());
-                                } else {
-                                    acc.insert(
-                                        elem.description.clone(),
-                                        BoxedWindowList(vec![elem.clone()]),
-                                    );
-                                }
-                                acc
+                        minimized_by_dock
+                            .drain(..)
+                            .map(|(entity, _)| entity)
+                            .filter(|entity| {
+                                cached_results.iter().any(|w| w.entity == *entity && w.minimized)
+                            })
+                            .collect()
+                    };
+                    for entity in minimized {
+                        if let Err(err) = window_toggle_minimize(entity).await {
+                            log::warn!("Failed to restore window {:?}: {}", entity, err);
+                        }
+                    }
+                }
+                Event::Favorite { id, .. } if !apps_container.shows_saved() => {
+                    // there is nothing to pin to in tasks-only mode, keep the app in the task list instead
+                    apps_container.toggle_keep_visible(&id);
+                    queue_refresh(&tx);
+                }
+                Event::Favorite {
+                    id,
+                    favorite,
+                    index,
+                } => {
+                    set_favorite(&apps_container, &tx, &id, favorite, index);
+                }
+                Event::PinWindow(window) => match generated_entry::create(&window) {
+                    Ok(id) => {
+                        // the window was cached as having no desktop entry
+                        desktop_cache::invalidate();
+                        send_event(
+                            &tx,
+                            Event::Favorite {
+                                id,
+                                favorite: true,
+                                index: None,
                             },
                         );
-                        let mut stack_active: Vec<BoxedWindowList> =
-                            stack_active.into_values().collect();
-
-                        // update active app stacks for saved apps into the saved app model
-                        // then put the rest in the active app model (which doesn't include saved apps)
-                        let saved_app_model = apps_container.model(DockListType::Saved);
-
-                        let mut saved_i: u32 = 0;
-                        while let Some(item) = saved_app_model.item(saved_i) {
-                            if let Ok(dock_obj) = item.downcast::<DockObject>() {
-                                if let Some(cur_app_info) =
-                                    dock_obj.property::<Option<DesktopAppInfo>>("appinfo")
-                                {
-                                    if let Some((i, _s)) = stack_active
-                                        .iter()
-                                        .enumerate()
-                                        .find(|(_i, s)| s.0[0].description == cur_app_info.name())
-                                    {
-                                        // println!(
-                                        //     "found active saved app {} at {}",
-                                        //     _s.0[0].name, i
-                                        // );
-                                        let active = stack_active.remove(i);
-                                        dock_obj.set_property("active", active.to_value());
-                                        saved_app_model.items_changed(
-                                            saved_i,
-                                            0,
-                                            0,
-                                        );
-                                    } else if cached_results
-                                        .iter()
-                                        .any(|s| s.description == cur_app_info.name())
-                                    {
-                                        dock_obj.set_property(
-                                            "active",
-                                            BoxedWindowList(Vec::new()).to_value(),
-                                        );
-                                        saved_app_model.items_changed(
-                                            saved_i,
-                                            0,
-                                            0,
-                                        );
-                                    }
-                                }
+                    }
+                    Err(err) => apps_container.show_toast(
+                        Severity::Error,
+                        fl!("pin-failed", app = window.name.clone(), error = err),
+                    ),
+                },
+                Event::DeleteGenerated(id) => {
+                    let app = generated_entry::app_info(&id)
+                        .map(|app_info| app_info.name().to_string())
+                        .unwrap_or_else(|| id.clone());
+                    set_favorite(&apps_container, &tx, &id, false, None);
+                    if let Err(err) = generated_entry::delete(&id) {
+                        apps_container.show_toast(
+                            Severity::Error,
+                            fl!("delete-entry-failed", app = app, error = err),
+                        );
+                    }
+                    desktop_cache::invalidate();
+                }
+                Event::AddCustomLauncher(launcher) => {
+                    let id = launcher.id.clone();
+                    custom_launcher::register(launcher);
+                    set_favorite(&apps_container, &tx, &id, true, None);
+                }
+                Event::Reorder { id, new_index } => {
+                    if !apps_container.reorder_saved(&id, new_index) {
+                        log::info!("Not reordering {}, it isn't pinned", id);
+                    }
+                }
+                Event::SavedIds(reply) => {
+                    let _ = reply.send(apps_container.saved_ids());
+                }
+                Event::Launch(id) => {
+                    for dock_object in apps_container.dock_objects_by_id(&id) {
+                        dock_object.set_launching(true);
+                    }
+                    // the gdk launch context takes care of startup notification
+                    let context = match Display::default() {
+                        Some(display) => display.app_launch_context(),
+                        None => {
+                            let err = "No display to launch on".to_string();
+                            log::warn!("Failed to launch {}: {}", id, err);
+                            send_event(&tx, Event::LaunchFailed(id, err));
+                            continue;
+                        }
+                    };
+                    let launcher = custom_launcher::get(&id);
+                    let result = match (&launcher, overrides::launch_info(&id)) {
+                        (Some(launcher), _) => launcher.launch(context.upcast_ref()),
+                        (None, Some(app_info)) => app_info
+                            .launch_uris_future(&[], Some(&context))
+                            .await
+                            .map_err(|err| err.to_string()),
+                        (None, None) => Err(format!("No desktop entry named {}", id)),
+                    };
+                    match result {
+                        Ok(()) => {
+                            // custom launchers are always saved, so they are never recent
+                            if launcher.is_none() {
+                                recent.record(&id);
                             }
-                            saved_i += 1;
+                            queue_refresh(&tx);
+                        }
+                        Err(err) => {
+                            log::warn!("Failed to launch {}: {}", id, err);
+                            send_event(&tx, Event::LaunchFailed(id, err));
                         }
-
-                        let active_app_model = apps_container.model(DockListType::Active);
-                        let model_len = active_app_model.n_items();
-                        let new_results: Vec<glib::Object> = stack_active
-                            .into_iter()
-                            .map(|v| DockObject::from_search_results(v).upcast())
-                            .collect();
-                        active_app_model.splice(0, model_len, &new_results[..]);
                     }
-                    Event::WindowList => {
-                        // sort to make comparison with cache easier
-                        let results = cached_results.as_ref().lock().unwrap();
-
-                        // build active app stacks for each app
-                        let stack_active = results.iter().fold(
-                            BTreeMap::new(),
-                            |mut acc: BTreeMap<String, BoxedWindowList>, elem| {
-                                if let Some(v) = acc.get_mut(&elem.description) {
-                                    v.0.push(elem.clone());
-                                } else {
-                                    acc.insert(
-                                        elem.description.clone(),
-                                        BoxedWindowList(vec![elem.clone()]),
-                                    );
-                                }
-                                acc
-                            },
+                }
+                Event::ClearRecent => {
+                    recent.clear();
+                    queue_refresh(&tx);
+                }
+                Event::LaunchFailed(id, err) => {
+                    for dock_object in apps_container.dock_objects_by_id(&id) {
+                        dock_object.set_launching(false);
+                        dock_object.emit_launch_failed();
+                    }
+                    let app = overrides::name(&id)
+                        .or_else(|| custom_launcher::get(&id).map(|launcher| launcher.name))
+                        .or_else(|| gio::DesktopAppInfo::new(&id).map(|app_info| app_info.name().to_string()))
+                        .unwrap_or(id);
+                    apps_container.show_toast(
+                        Severity::Error,
+                        fl!("launch-failed", app = app, error = err),
+                    );
+                }
+                Event::DaemonRestarted => {
+                    // the refresh sent after this shows no windows until the new daemon lists
+                    // them, launches in progress are resolved against the new list or time out
+                    minimized_by_dock.clear();
+                    shown_windows.clear();
+                    apps_container.forget_windows();
+                }
+                Event::DaemonStatus(available) => {
+                    // without the daemon only the saved apps are shown, and they can only be launched,
+                    // the zbus thread cleared the cached window list
+                    if !available {
+                        let cached_results = cached_results.as_ref().lock().unwrap();
+                        refresh_models(
+                            &cached_results,
+                            &apps_container,
+                            &mut stack_history,
+                            &launcher_entries,
+                            &recent,
                         );
-                        let mut stack_active: Vec<BoxedWindowList> =
-                            stack_active.into_values().collect();
-
-                        // update active app stacks for saved apps into the saved app model
-                        // then put the rest in the active app model (which doesn't include saved apps)
-                        let saved_app_model = apps_container.model(DockListType::Saved);
-
-                        let mut saved_i: u32 = 0;
-                        while let Some(item) = saved_app_model.item(saved_i) {
-                            if let Ok(dock_obj) = item.downcast::<DockObject>() {
-                                if let Some(cur_app_info) =
-                                    dock_obj.property::<Option<DesktopAppInfo>>("appinfo")
-                                {
-                                    if let Some((i, _s)) = stack_active
-                                        .iter()
-                                        .enumerate()
-                                        .find(|(_i, s)| s.0[0].description == cur_app_info.name())
-                                    {
-                                        // println!("found active saved app {} at {}", s.0[0].name, i);
-                                        let active = stack_active.remove(i);
-                                        dock_obj.set_property("active", active.to_value());
-                                        saved_app_model.items_changed(
-                                            saved_i,
-                                            0,
-                                            0,
-                                        );
-                                    } else if results
-                                        .iter()
-                                        .any(|s| s.description == cur_app_info.name())
-                                    {
-                                        dock_obj.set_property(
-                                            "active",
-                                            BoxedWindowList(Vec::new()).to_value(),
-                                        );
-                                        saved_app_model.items_changed(
-                                            saved_i,
-                                            0,
-                                            0,
-                                        );
-                                    }
-                                }
-                            }
-                            saved_i += 1;
+                        shown_windows = cached_results.to_vec();
+                    }
+                    if !available && apps_container.daemon_available() {
+                        apps_container.show_toast(Severity::Info, fl!("daemon-unavailable"));
+                    }
+                    apps_container.set_daemon_available(available);
+                }
+                Event::RefreshFromCache | Event::WindowList => {
+                    refresh_dequeued(&tx);
+                    if pending_sync.queue() {
+                        // refreshes arriving before the main loop is idle are handled by the same sync
+                        let tx = tx.clone();
+                        glib::idle_add_local_once(move || send_event(&tx, Event::SyncModels));
+                    }
+                }
+                // the pending sync already ran before this event was matched
+                Event::SyncModels => {}
+                Event::LauncherEntry(id, properties) => {
+                    let entry = match properties {
+                        Some(properties) => {
+                            let entry = launcher_entries.entry(id.clone()).or_default();
+                            entry.merge(&properties);
+                            Some(entry.clone())
                         }
-
-                        let active_app_model = apps_container.model(DockListType::Active);
-                        let model_len = active_app_model.n_items();
-                        let new_results: Vec<glib::Object> = stack_active
-                            .into_iter()
-                            .map(|v| DockObject::from_search_results(v).upcast())
-                            .collect();
-                        active_app_model.splice(0, model_len, &new_results[..]);
+                        None => {
+                            launcher_entries.remove(&id);
+                            None
+                        }
+                    };
+                    for dock_object in apps_container.dock_objects_by_id(&id) {
+                        launcher_entry::apply_to(&dock_object, entry.as_ref());
+                    }
+                }
+                Event::SaveFailed(err) => {
+                    apps_container.show_toast(Severity::Error, fl!("save-failed", error = err));
+                }
+                Event::ClearNotifications(id) => {
+                    // entries are keyed by the id the app sent, which may differ from the dock's
+                    let key = launcher_entries
+                        .keys()
+                        .find(|key| app_id::same_app(key, &id))
+                        .cloned()
+                        .unwrap_or_else(|| id.clone());
+                    // the entry is kept, so the dismissal outlasts a rebroadcast of the same values
+                    let entry = launcher_entries.entry(key).or_default();
+                    entry.dismiss();
+                    for dock_object in apps_container.dock_objects_by_id(&id) {
+                        launcher_entry::apply_to(&dock_object, Some(entry));
                     }
                 }
             }
-        });
+        }
+    });
+    state.event_handle = Some(event_handle);
+    state
+}
+
+/// Builds the application running the app list in its own window, used for development
+/// outside the dock.
+pub fn standalone_app() -> gtk4::Application {
+    let app = gtk4::Application::new(Some(ID), ApplicationFlags::default());
+
+    let state = Rc::new(RefCell::new(AppListBackend::default()));
+    app.connect_shutdown(glib::clone!(@strong state => move |_| {
+        state.borrow_mut().teardown();
+    }));
+
+    app.connect_activate(move |app| {
+        if state.borrow().is_running() {
+            // already running, just bring the window back
+            if let Some(window) = app.active_window() {
+                window.present();
+            }
+            return;
+        }
+        *state.borrow_mut() = AppListBackend::start();
+        // icons are looked up again when the style or icon theme changes
+        style::load(glib::clone!(@weak state => move || {
+            if let Some(apps_container) = state.borrow().apps_container() {
+                apps_container.refresh_items();
+            }
+        }));
+        // reduced motion applies to the existing items right away
+        style::watch_animations(glib::clone!(@weak state => move || {
+            if let Some(apps_container) = state.borrow().apps_container() {
+                apps_container.set_animations_enabled(AppsConfig::load().animations_enabled());
+            }
+        }));
+        let window = match state.borrow().apps_container() {
+            Some(apps_container) => CosmicDockAppListWindow::new(app, apps_container),
+            None => return,
+        };
+        app.set_accels_for_action("win.quit", &["<Primary>q"]);
+        // lets the compositor trigger the Super+number shortcuts when the dock isn't focused
+        let action_activate_saved =
+            gio::SimpleAction::new("activate-saved", Some(glib::VariantTy::UINT32));
+        action_activate_saved.connect_activate(glib::clone!(@weak window => move |_, index| {
+            if let Some(index) = index.and_then(|index| index.get::<u32>()) {
+                window.activate_saved(index);
+            }
+        }));
+        app.add_action(&action_activate_saved);
+
         window.show();
+        // only windows on the monitor showing the dock are listed
+        if let Some(surface) = window.surface() {
+            surface.connect_enter_monitor(glib::clone!(@weak state => move |_, monitor| {
+                let state = state.borrow();
+                if let (Some(apps_container), Some(tx)) = (&state.apps_container, state.tx.clone()) {
+                    apps_container.set_output_filter(monitor.connector().map(|c| c.to_string()));
+                    queue_refresh(&tx);
+                }
+            }));
+        }
     });
-    app.run();
+    app
+}
+
+fn main() {
+    // Initialize logger, warnings are shown unless RUST_LOG says otherwise
+    let filters = std::env::var("RUST_LOG").unwrap_or_else(|_| "warn".to_string());
+    pretty_env_logger::formatted_builder()
+        .parse_filters(&filters)
+        .init();
+    glib::set_application_name("Cosmic Dock App List");
+
+    localize();
+
+    gio::resources_register_include!("compiled.gresource").unwrap();
+    standalone_app().run();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{GroupingMode, SortOrder};
+    use crate::dock_state::{apply_window_list, DockState, SavedEntry};
+    use crate::utils::{ExtendedWireItem, WireItem};
+    use std::os::unix::net::UnixStream;
+    use std::time::Instant;
+    use zbus::{dbus_interface, ConnectionBuilder, Guid};
+    use zvariant::{OwnedValue, Value};
+
+    #[test]
+    fn teardown_of_a_backend_that_never_started() {
+        let mut backend = AppListBackend::default();
+        backend.teardown();
+        backend.teardown();
+        assert!(!backend.is_running());
+        assert!(backend.apps_container().is_none());
+    }
+
+    #[test]
+    fn burst_of_refreshes_is_synced_once() {
+        let mut pending_sync = PendingSync::default();
+        assert!(pending_sync.queue());
+        for _ in 0..10 {
+            assert!(!pending_sync.queue());
+            assert!(!pending_sync.take_before(&Event::WindowList));
+        }
+        assert!(pending_sync.take_before(&Event::SyncModels));
+        assert!(!pending_sync.take_before(&Event::SyncModels));
+        // a refresh after the sync ran needs another one
+        assert!(pending_sync.queue());
+    }
+
+    #[test]
+    fn user_actions_run_the_pending_sync_first() {
+        let mut pending_sync = PendingSync::default();
+        assert!(!pending_sync.take_before(&Event::Launch("firefox.desktop".to_string())));
+        pending_sync.queue();
+        assert!(pending_sync.take_before(&Event::Launch("firefox.desktop".to_string())));
+        // the sync queued before the action has nothing left to do
+        assert!(!pending_sync.take_before(&Event::SyncModels));
+    }
+
+    #[test]
+    fn zbus_thread_starts_and_stops_twice() {
+        for _ in 0..2 {
+            let (started_tx, started_rx) = std::sync::mpsc::channel();
+            // a D-Bus call that never returns
+            let handle = ZbusHandle::spawn(async move {
+                let _ = started_tx.send(());
+                future::pending::<()>().await;
+            });
+            started_rx.recv_timeout(Duration::from_secs(1)).unwrap();
+            let start = Instant::now();
+            handle.stop(Duration::from_secs(5));
+            assert!(start.elapsed() < Duration::from_secs(1));
+        }
+    }
+
+    // records that the future holding it was dropped
+    struct SetOnDrop(Arc<AtomicBool>);
+
+    impl Drop for SetOnDrop {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn stopping_the_zbus_thread_drops_its_task() {
+        let dropped = Arc::new(AtomicBool::new(false));
+        let guard = SetOnDrop(Arc::clone(&dropped));
+        let handle = ZbusHandle::spawn(async move {
+            let _guard = guard;
+            future::pending::<()>().await;
+        });
+        handle.stop(Duration::from_secs(1));
+        assert!(dropped.load(Ordering::SeqCst));
+    }
+
+    // a window daemon listing the windows the test sets, it records the windows it focuses
+    #[derive(Clone, Default)]
+    struct FakeDaemon {
+        windows: Arc<Mutex<Vec<Item>>>,
+        focused: Arc<Mutex<Vec<(u32, u32)>>>,
+    }
+
+    #[dbus_interface(name = "com.System76.PopShell")]
+    impl FakeDaemon {
+        fn window_list(&self) -> Vec<WireItem> {
+            let windows = self.windows.lock().unwrap();
+            windows
+                .iter()
+                .map(|w| WireItem {
+                    entity: w.entity,
+                    name: w.name.clone(),
+                    description: w.description.clone(),
+                    desktop_entry: w.desktop_entry.clone(),
+                })
+                .collect()
+        }
+
+        fn window_list_extended(&self) -> Vec<ExtendedWireItem> {
+            let windows = self.windows.lock().unwrap();
+            windows
+                .iter()
+                .map(|w| ExtendedWireItem {
+                    entity: w.entity,
+                    name: w.name.clone(),
+                    description: w.description.clone(),
+                    desktop_entry: w.desktop_entry.clone(),
+                    extras: HashMap::from([
+                        (
+                            "app_id".to_string(),
+                            OwnedValue::from(Value::from(w.app_id.clone())),
+                        ),
+                        (
+                            "focused".to_string(),
+                            OwnedValue::from(Value::from(w.focused)),
+                        ),
+                    ]),
+                })
+                .collect()
+        }
+
+        fn window_focus(&self, entity: (u32, u32)) {
+            self.focused.lock().unwrap().push(entity);
+        }
+    }
+
+    impl FakeDaemon {
+        fn set_windows(&self, windows: &[(u32, &str)]) {
+            *self.windows.lock().unwrap() = windows
+                .iter()
+                .map(|(entity, app_id)| Item {
+                    entity: (*entity, 0),
+                    name: format!("{} {}", app_id, entity),
+                    app_id: app_id.to_string(),
+                    ..Default::default()
+                })
+                .collect();
+        }
+    }
+
+    // the daemon's and the dock's end of a private connection
+    async fn connect(daemon: impl zbus::Interface) -> (Connection, Connection) {
+        let (daemon_socket, dock_socket) = UnixStream::pair().unwrap();
+        let guid = Guid::generate();
+        let daemon_conn = ConnectionBuilder::unix_stream(daemon_socket)
+            .server(&guid)
+            .p2p()
+            .build();
+        let dock_conn = ConnectionBuilder::unix_stream(dock_socket).p2p().build();
+        let (daemon_conn, dock_conn) = future::try_join(daemon_conn, dock_conn).await.unwrap();
+        daemon_conn.object_server().at(PATH, daemon).await.unwrap();
+        (daemon_conn, dock_conn)
+    }
+
+    // the zbus thread's side of the backend and the dock state of one instance
+    struct Dock {
+        conn: Connection,
+        daemon_name: String,
+        owner: Option<String>,
+        cached: Arc<Mutex<Vec<Item>>>,
+        notifier: Notifier,
+        state: DockState,
+        history: StackHistory,
+        shown: Vec<Item>,
+    }
+
+    impl Dock {
+        fn new(conn: Connection, daemon_name: &str) -> Self {
+            Self {
+                conn,
+                daemon_name: daemon_name.to_string(),
+                owner: None,
+                cached: Arc::new(Mutex::new(Vec::new())),
+                notifier: Notifier::new(),
+                state: DockState::default(),
+                history: StackHistory::default(),
+                shown: Vec::new(),
+            }
+        }
+
+        // a poll of the zbus thread and the refresh it queues
+        async fn sync(&mut self) {
+            self.poll().await;
+            self.refresh();
+        }
+
+        // a poll of the zbus thread, the main loop doesn't see it until the refresh
+        async fn poll(&mut self) {
+            let (reply_owner, reply) = fetch_window_list(&self.conn).await.unwrap();
+            // replies on a private connection have no sender, the name of the fake stands in
+            let reply_owner = reply_owner.or_else(|| Some(self.daemon_name.clone()));
+            update_owner(&mut self.owner, reply_owner, &self.cached, &self.notifier);
+            update_cached_window_list(&self.conn, &self.cached, reply, &self.notifier).await;
+        }
+
+        fn refresh(&mut self) {
+            let windows = self.cached.lock().unwrap().clone();
+            self.shown = windows.clone();
+            self.history.update(&windows);
+            apply_window_list(
+                &mut self.state,
+                windows,
+                &self.history,
+                SortOrder::LaunchOrder,
+                GroupingMode::GroupByApp,
+            );
+        }
+
+        // the event a click on the saved item `id` sends
+        fn click(&self, id: &str) -> Event {
+            let entry = self.state.saved.iter().find(|e| e.app.id == id).unwrap();
+            match entry.windows.first() {
+                Some(window) => Event::Activate(window.entity),
+                None => Event::Launch(id.to_string()),
+            }
+        }
+
+        // the main loop's handling of `event`, it only focuses windows the dock showed
+        async fn handle(&self, event: Event) {
+            if let Event::Activate(entity) = event {
+                let shown = same_window(&self.shown, &self.cached.lock().unwrap(), entity);
+                if shown {
+                    self.conn
+                        .call_method(Some(DEST), PATH, Some(DEST), "WindowFocus", &(entity,))
+                        .await
+                        .unwrap();
+                }
+            }
+        }
+
+        // ids and window counts of the saved items
+        fn saved(&self) -> Vec<(&str, usize)> {
+            let saved = self.state.saved.iter();
+            saved
+                .map(|e| (e.app.id.as_str(), e.windows.len()))
+                .collect()
+        }
+
+        // app ids and window counts of the active items
+        fn active(&self) -> Vec<(&str, usize)> {
+            let active = self.state.active.iter();
+            active.map(|s| (s.key.as_str(), s.windows.len())).collect()
+        }
+    }
+
+    fn pinned(id: &str) -> AppEntry {
+        AppEntry {
+            id: id.to_string(),
+            app_ids: vec![app_id::normalize(id)],
+            ..Default::default()
+        }
+    }
+
+    fn run(test: impl std::future::Future<Output = ()>) {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap()
+            .block_on(test);
+    }
+
+    #[test]
+    fn app_opens_two_windows_and_closes_one() {
+        run(async {
+            let daemon = FakeDaemon::default();
+            let (_daemon_conn, conn) = connect(daemon.clone()).await;
+            let mut dock = Dock::new(conn, ":1.1");
+
+            daemon.set_windows(&[(1, "gedit")]);
+            dock.sync().await;
+            assert_eq!(dock.active(), vec![("gedit", 1)]);
+
+            daemon.set_windows(&[(1, "gedit"), (2, "gedit"), (3, "vlc")]);
+            dock.sync().await;
+            assert_eq!(dock.active(), vec![("gedit", 2), ("vlc", 1)]);
+
+            daemon.set_windows(&[(2, "gedit"), (3, "vlc")]);
+            dock.sync().await;
+            assert_eq!(dock.active(), vec![("gedit", 1), ("vlc", 1)]);
+            assert_eq!(dock.state.active[0].windows[0].entity, (2, 0));
+        });
+    }
+
+    #[test]
+    fn app_pinned_while_running() {
+        run(async {
+            let daemon = FakeDaemon::default();
+            let (_daemon_conn, conn) = connect(daemon.clone()).await;
+            let mut dock = Dock::new(conn, ":1.1");
+
+            daemon.set_windows(&[(1, "firefox"), (2, "gedit"), (3, "firefox")]);
+            dock.sync().await;
+            apply_favorite(&mut dock.state, pinned("firefox.desktop"), true, None);
+            assert_eq!(dock.saved(), vec![("firefox.desktop", 2)]);
+            assert_eq!(dock.active(), vec![("gedit", 1)]);
+
+            // the next poll keeps the windows on the saved item
+            dock.sync().await;
+            assert_eq!(dock.saved(), vec![("firefox.desktop", 2)]);
+            assert_eq!(dock.active(), vec![("gedit", 1)]);
+
+            daemon.set_windows(&[(2, "gedit")]);
+            dock.sync().await;
+            assert_eq!(dock.saved(), vec![("firefox.desktop", 0)]);
+        });
+    }
+
+    #[test]
+    fn daemon_restarts() {
+        run(async {
+            let daemon = FakeDaemon::default();
+            let (daemon_conn, conn) = connect(daemon.clone()).await;
+            let mut dock = Dock::new(conn, ":1.1");
+            dock.state.saved.push(SavedEntry {
+                app: pinned("firefox.desktop"),
+                windows: Vec::new(),
+            });
+
+            daemon.set_windows(&[(1, "firefox"), (2, "gedit")]);
+            dock.sync().await;
+            assert_eq!(dock.saved(), vec![("firefox.desktop", 1)]);
+            assert_eq!(dock.active(), vec![("gedit", 1)]);
+
+            // the new daemon only knows the windows that are still open
+            drop(daemon_conn);
+            let daemon = FakeDaemon::default();
+            daemon.set_windows(&[(7, "gedit")]);
+            let (_daemon_conn, conn) = connect(daemon.clone()).await;
+            dock.conn = conn;
+            dock.daemon_name = ":1.2".to_string();
+            dock.sync().await;
+            assert_eq!(dock.saved(), vec![("firefox.desktop", 0)]);
+            assert_eq!(dock.active(), vec![("gedit", 1)]);
+            assert_eq!(dock.state.active[0].windows[0].entity, (7, 0));
+        });
+    }
+
+    // a daemon that stopped replying, it reports each call it receives
+    struct StalledDaemon {
+        calls: Mutex<std::sync::mpsc::Sender<()>>,
+    }
+
+    #[dbus_interface(name = "com.System76.PopShell")]
+    impl StalledDaemon {
+        async fn window_list(&self) -> Vec<WireItem> {
+            let _ = self.calls.lock().unwrap().send(());
+            future::pending().await
+        }
+
+        async fn window_list_extended(&self) -> Vec<ExtendedWireItem> {
+            let _ = self.calls.lock().unwrap().send(());
+            future::pending().await
+        }
+    }
+
+    fn stalled_daemon() -> (StalledDaemon, std::sync::mpsc::Receiver<()>) {
+        let (calls, received) = std::sync::mpsc::channel();
+        let daemon = StalledDaemon {
+            calls: Mutex::new(calls),
+        };
+        (daemon, received)
+    }
+
+    #[test]
+    fn calls_to_a_stalled_daemon_time_out() {
+        run(async {
+            let (daemon, _received) = stalled_daemon();
+            let (_daemon_conn, conn) = connect(daemon).await;
+            let start = Instant::now();
+            let result = fetch_window_list(&conn).await;
+            assert!(matches!(result, Err(zbus::Error::Failure(_))));
+            assert!(start.elapsed() >= DAEMON_TIMEOUT);
+            assert!(start.elapsed() < DAEMON_TIMEOUT * 2);
+        });
+    }
+
+    #[test]
+    fn unload_does_not_wait_for_a_stalled_daemon() {
+        let (daemon, received) = stalled_daemon();
+        let (daemon_conn, conn) = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(connect(daemon));
+        let handle = ZbusHandle::spawn(async move {
+            loop {
+                let _ = fetch_window_list(&conn).await;
+            }
+        });
+        received.recv_timeout(Duration::from_secs(1)).unwrap();
+
+        let start = Instant::now();
+        handle.stop(Duration::from_secs(1));
+        assert!(start.elapsed() < Duration::from_millis(500));
+        drop(daemon_conn);
+    }
+
+    #[test]
+    fn restarted_daemon_reusing_entities() {
+        run(async {
+            let daemon = FakeDaemon::default();
+            let (daemon_conn, conn) = connect(daemon.clone()).await;
+            let mut dock = Dock::new(conn, ":1.1");
+            dock.state.saved.push(SavedEntry {
+                app: pinned("firefox.desktop"),
+                windows: Vec::new(),
+            });
+            daemon.set_windows(&[(1, "firefox")]);
+            dock.sync().await;
+            assert_eq!(dock.state.saved[0].windows[0].entity, (1, 0));
+            // a click queued before the restart
+            let stale_click = dock.click("firefox.desktop");
+
+            // the new daemon hands the entity of the firefox window to another app
+            drop(daemon_conn);
+            let daemon = FakeDaemon::default();
+            daemon.set_windows(&[(1, "gedit")]);
+            let (_daemon_conn, conn) = connect(daemon.clone()).await;
+            dock.conn = conn;
+            dock.daemon_name = ":1.2".to_string();
+            let mut notices = dock.notifier.notices.subscribe();
+            dock.poll().await;
+            dock.handle(stale_click).await;
+            dock.refresh();
+
+            // the instances forget the old windows before they see the new list
+            assert!(matches!(notices.try_recv(), Ok(Notice::DaemonRestarted)));
+            assert!(matches!(notices.try_recv(), Ok(Notice::WindowList)));
+            assert_eq!(dock.saved(), vec![("firefox.desktop", 0)]);
+            assert_eq!(dock.active(), vec![("gedit", 1)]);
+
+            // activating firefox can't reach the gedit window
+            let click = dock.click("firefox.desktop");
+            assert!(matches!(click, Event::Launch(_)));
+            dock.handle(click).await;
+            assert!(!daemon.focused.lock().unwrap().contains(&(1, 0)));
+        });
+    }
 }
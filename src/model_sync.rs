@@ -0,0 +1,392 @@
+// SPDX-License-Identifier: MPL-2.0-only
+
+use crate::apps_container::AppsContainer;
+use crate::custom_launcher;
+use crate::dock_list::DockListType;
+use crate::dock_item::REVEAL_DURATION_MS;
+use crate::dock_object::DockObject;
+use crate::generated_entry;
+use crate::dock_state::{
+    apply_window_list, stack_key, window_key, ActiveStack, AppEntry, DockState, ModelDelta,
+    SavedEntry, StackHistory,
+};
+use crate::config::GroupingMode;
+use crate::utils::{BoxedWindowList, Item};
+use crate::watchdog;
+use gio::DesktopAppInfo;
+use gtk4::prelude::*;
+use gtk4::{gio, glib};
+use std::time::Duration;
+
+/// How long the item of a closed app offers to keep the app in the dock.
+const KEEP_PROMPT_SECS: u64 = 5;
+
+// stacks without windows are keyed by desktop id
+fn active_key(dock_obj: &DockObject) -> String {
+    let key: fn(&Item) -> String = if dock_obj.is_single_window() {
+        window_key
+    } else {
+        stack_key
+    };
+    dock_obj
+        .property::<BoxedWindowList>("active")
+        .0
+        .get(0)
+        .map(key)
+        .or_else(|| dock_obj.get_id())
+        .unwrap_or_default()
+}
+
+/// Reads the current contents of both models.
+pub fn read_dock_state(apps_container: &AppsContainer) -> DockState {
+    let mut state = DockState::default();
+    let saved_app_model = apps_container.model(DockListType::Saved);
+    let mut i: u32 = 0;
+    while let Some(item) = saved_app_model.item(i) {
+        if let Ok(dock_obj) = item.downcast::<DockObject>() {
+            // objects without an app info keep their place so indices stay aligned with the model
+            let app = match dock_obj.custom_launcher() {
+                Some(launcher) => AppEntry::from_custom_launcher(&launcher),
+                None => dock_obj
+                    .property::<Option<DesktopAppInfo>>("appinfo")
+                    .map(|app_info| AppEntry::from_app_info(&app_info))
+                    .unwrap_or_default(),
+            };
+            state.saved.push(SavedEntry {
+                app,
+                windows: dock_obj.property::<BoxedWindowList>("active").0,
+            });
+        }
+        i += 1;
+    }
+
+    let active_app_model = apps_container.model(DockListType::Active);
+    let mut i: u32 = 0;
+    while let Some(item) = active_app_model.item(i) {
+        if let Ok(dock_obj) = item.downcast::<DockObject>() {
+            // objects that are animating out or only stay for their prompt keep their place
+            if dock_obj.property::<bool>("removing") || dock_obj.property::<bool>("keep-prompt") {
+                state.active.push(ActiveStack {
+                    key: String::new(),
+                    windows: Vec::new(),
+                });
+            } else {
+                state.active.push(ActiveStack {
+                    key: active_key(&dock_obj),
+                    windows: dock_obj.property::<BoxedWindowList>("active").0,
+                });
+            }
+        }
+        i += 1;
+    }
+    state
+}
+
+/// Applies the changes computed by [`crate::dock_state`] to the models.
+pub fn apply_deltas(deltas: Vec<ModelDelta>, apps_container: &AppsContainer) {
+    let _span = watchdog::span("apply deltas");
+    let saved_app_model = apps_container.model(DockListType::Saved);
+    let active_app_model = apps_container.model(DockListType::Active);
+    for delta in deltas {
+        match delta {
+            ModelDelta::SavedWindows { index, windows } => {
+                let index = index as u32;
+                let dock_obj = match saved_app_model
+                    .item(index)
+                    .and_then(|item| item.downcast::<DockObject>().ok())
+                {
+                    Some(dock_obj) => dock_obj,
+                    None => continue,
+                };
+                let active = BoxedWindowList(windows);
+                if !active.0.is_empty() && dock_obj.property::<bool>("launching") {
+                    dock_obj.set_launching(false);
+                }
+                let focused = active.focused().is_some();
+                if dock_obj.property::<bool>("focused") != focused {
+                    dock_obj.set_property("focused", focused);
+                }
+                dock_obj.set_property("active", active.to_value());
+                saved_app_model.items_changed(index, 0, 0);
+            }
+            ModelDelta::InsertSaved {
+                index,
+                app,
+                from_active,
+            } => {
+                // keep the running object so its state survives being saved
+                let running = from_active.and_then(|i| {
+                    let object = active_app_model.item(i as u32)?;
+                    active_app_model.remove(i as u32);
+                    object.downcast::<DockObject>().ok()
+                });
+                let new_object = || match custom_launcher::get(&app.id) {
+                    Some(launcher) => Some(DockObject::from_custom_launcher(launcher)),
+                    None => DockObject::from_app_info_path(&app.path),
+                };
+                if let Some(dock_obj) = running.or_else(new_object) {
+                    // the windows may have resolved to another entry, the saved one has to
+                    // match the id or the next sync would list the windows a second time
+                    if dock_obj.get_id().as_deref() != Some(app.id.as_str()) {
+                        if let Some(app_info) = generated_entry::app_info(&app.id) {
+                            dock_obj.set_property("appinfo", Some(app_info));
+                        }
+                    }
+                    dock_obj.set_saved(true);
+                    saved_app_model.insert(index as u32, &dock_obj);
+                }
+            }
+            ModelDelta::RemoveSaved { index, to_active } => {
+                if let Some(object) = saved_app_model.item(index as u32) {
+                    saved_app_model.remove(index as u32);
+                    if let Ok(dock_obj) = object.downcast::<DockObject>() {
+                        dock_obj.set_saved(false);
+                        if let Some(position) = to_active {
+                            let position = (position as u32).min(active_app_model.n_items());
+                            active_app_model.insert(position, &dock_obj);
+                        }
+                    }
+                }
+            }
+            ModelDelta::ReplaceActive(stacks) => {
+                let one_per_window = apps_container.grouping_mode() == GroupingMode::OnePerWindow;
+                let mut new_results: Vec<DockObject> = stacks
+                    .into_iter()
+                    .map(|stack| match stack.windows.as_slice() {
+                        [window] if one_per_window => DockObject::from_window(window.clone()),
+                        _ => DockObject::from_search_results(BoxedWindowList(stack.windows)),
+                    })
+                    .collect();
+                if !apps_container.shows_saved() {
+                    for id in apps_container.keep_visible_ids() {
+                        if new_results.iter().any(|o| o.get_id().as_deref() == Some(&id)) {
+                            continue;
+                        }
+                        if let Some(app_info) = generated_entry::app_info(&id) {
+                            let dock_object = DockObject::new(app_info);
+                            dock_object.set_saved(false);
+                            new_results.push(dock_object);
+                        }
+                    }
+                }
+                update_active_model(
+                    active_app_model,
+                    new_results,
+                    apps_container.animations_enabled(),
+                    &|dock_obj| apps_container.offer_keep_in_dock(dock_obj),
+                );
+            }
+        }
+    }
+}
+
+fn set_windows(dock_obj: &DockObject, active: BoxedWindowList) {
+    let focused = active.focused().is_some();
+    if dock_obj.property::<bool>("focused") != focused {
+        dock_obj.set_property("focused", focused);
+    }
+    if dock_obj.property::<BoxedWindowList>("active") != active {
+        dock_obj.set_property("active", active.to_value());
+    }
+}
+
+// lets the item shrink before it is taken out of the model, without animations it is removed
+// right away
+fn remove_animated(model: &gio::ListStore, dock_obj: &DockObject, animate: bool) {
+    // the windows are gone, which also closes an open window list
+    set_windows(dock_obj, BoxedWindowList::default());
+    if !animate {
+        if let Some(i) = dock_obj.position_in(model) {
+            model.remove(i);
+        }
+        return;
+    }
+    dock_obj.set_property("removing", true);
+    glib::timeout_add_local_once(
+        Duration::from_millis(REVEAL_DURATION_MS.into()),
+        glib::clone!(@weak model, @weak dock_obj => move || {
+            if let Some(i) = dock_obj.position_in(&model) {
+                model.remove(i);
+            }
+        }),
+    );
+}
+
+// keeps the item of a closed app while it offers to keep the app in the dock, it is removed once
+// the prompt is answered or timed out
+fn defer_removal(model: &gio::ListStore, dock_obj: &DockObject, animate: bool) {
+    set_windows(dock_obj, BoxedWindowList::default());
+    dock_obj.set_property("keep-prompt", true);
+    let timeout = glib::timeout_add_local_once(
+        Duration::from_secs(KEEP_PROMPT_SECS),
+        glib::clone!(@weak model, @weak dock_obj => move || {
+            dock_obj.forget_keep_prompt_timeout();
+            end_keep_prompt(&model, &dock_obj, animate);
+        }),
+    );
+    dock_obj.set_keep_prompt_timeout(Some(timeout));
+}
+
+/// Removes the item kept for its keep in dock prompt from `model`.
+pub fn end_keep_prompt(model: &gio::ListStore, dock_obj: &DockObject, animate: bool) {
+    dock_obj.set_keep_prompt_timeout(None);
+    if !dock_obj.property::<bool>("keep-prompt") {
+        return;
+    }
+    dock_obj.set_property("keep-prompt", false);
+    remove_animated(model, dock_obj, animate);
+}
+
+// the same app if the desktop ids match, or for apps without one if the stack still holds the first window
+fn same_stack(old: &DockObject, new: &DockObject) -> bool {
+    // items of single windows only match the item of the same window
+    if old.is_single_window() || new.is_single_window() {
+        return old.is_single_window() == new.is_single_window()
+            && active_key(old) == active_key(new);
+    }
+    match (old.get_id(), new.get_id()) {
+        (Some(old_id), Some(new_id)) => old_id == new_id,
+        (None, None) => {
+            let old_windows = old.property::<BoxedWindowList>("active").0;
+            new.property::<BoxedWindowList>("active")
+                .0
+                .get(0)
+                .map(|first| old_windows.iter().any(|w| w.entity == first.entity))
+                .unwrap_or_default()
+                || active_key(old) == active_key(new)
+        }
+        // a desktop entry was found for the windows since the last sync
+        (None, Some(_)) => {
+            let new_windows = new.property::<BoxedWindowList>("active").0;
+            old.property::<BoxedWindowList>("active")
+                .0
+                .iter()
+                .any(|w| new_windows.iter().any(|n| n.entity == w.entity))
+        }
+        _ => false,
+    }
+}
+
+/// Updates the active model to hold `new_results` in order, only inserting and removing
+/// the objects that changed so unchanged icons keep their widgets and popovers. Objects of
+/// closed apps stay a while if `offer_keep` returns true for them.
+fn update_active_model(
+    model: &gio::ListStore,
+    new_results: Vec<DockObject>,
+    animate: bool,
+    offer_keep: &dyn Fn(&DockObject) -> bool,
+) {
+    let is_live = |dock_obj: &DockObject| {
+        !dock_obj.property::<bool>("removing") && !dock_obj.property::<bool>("keep-prompt")
+    };
+    let object_at = |i: u32| model.item(i).and_then(|o| o.downcast::<DockObject>().ok());
+    let app_listed = |dock_obj: &DockObject| {
+        dock_obj
+            .get_id()
+            .map(|id| new_results.iter().any(|new| new.get_id() == Some(id.clone())))
+            .unwrap_or_default()
+    };
+
+    let mut i: u32 = 0;
+    while let Some(dock_obj) = object_at(i) {
+        // the app was started again while its closed item offered to keep it
+        if dock_obj.property::<bool>("keep-prompt") && app_listed(&dock_obj) {
+            end_keep_prompt(model, &dock_obj, animate);
+            if !animate {
+                continue;
+            }
+        } else if is_live(&dock_obj) && !new_results.iter().any(|new| same_stack(&dock_obj, new)) {
+            // other windows of the app may still be listed by their own items
+            if !app_listed(&dock_obj) && offer_keep(&dock_obj) {
+                defer_removal(model, &dock_obj, animate);
+            } else {
+                remove_animated(model, &dock_obj, animate);
+                if !animate {
+                    continue;
+                }
+            }
+        }
+        i += 1;
+    }
+
+    let mut position: u32 = 0;
+    for new_obj in new_results {
+        // objects on their way out stay where they are
+        while object_at(position).map(|o| !is_live(&o)).unwrap_or_default() {
+            position += 1;
+        }
+        let existing = (position..model.n_items()).find(|&i| {
+            object_at(i)
+                .map(|o| is_live(&o) && same_stack(&o, &new_obj))
+                .unwrap_or_default()
+        });
+        match existing.and_then(|i| Some((i, object_at(i)?))) {
+            Some((i, dock_obj)) => {
+                if i != position {
+                    model.remove(i);
+                    model.insert(position, &dock_obj);
+                }
+                if dock_obj.get_id().is_none() {
+                    if let Some(appinfo) = new_obj.property::<Option<DesktopAppInfo>>("appinfo") {
+                        dock_obj.set_property("appinfo", Some(appinfo));
+                    }
+                }
+                set_windows(&dock_obj, new_obj.property::<BoxedWindowList>("active"));
+            }
+            None => model.insert(position, &new_obj),
+        }
+        position += 1;
+    }
+}
+
+/// Rebuilds the active app stacks from the cached window list.
+/// Stacks belonging to saved apps are stored in the saved model,
+/// the rest are diffed into the active model.
+pub fn sync_models(cached: &[Item], apps_container: &AppsContainer, history: &mut StackHistory) {
+    let _span = watchdog::span("sync models");
+    let mut state = {
+        let _span = watchdog::span("read dock state");
+        read_dock_state(apps_container)
+    };
+    // in tasks-only mode pinned apps are listed with the other running apps
+    if !apps_container.shows_saved() {
+        state.saved.clear();
+    }
+    history.update(cached);
+    // stacks keep the order of their windows
+    let mut windows = cached.to_vec();
+    history.sort_windows(&mut windows);
+    let mut deltas = {
+        let _span = watchdog::span("apply window list");
+        apply_window_list(
+            &mut state,
+            windows,
+            history,
+            apps_container.sort_order(),
+            apps_container.grouping_mode(),
+        )
+    };
+    // in launcher-only mode windows of apps that aren't saved are not shown at all
+    if !apps_container.shows_active() {
+        for delta in deltas.iter_mut() {
+            if let ModelDelta::ReplaceActive(stacks) = delta {
+                stacks.clear();
+            }
+        }
+    }
+    // saved apps keep all of their windows, running apps are only listed on their own output
+    if let Some(output) = apps_container.output_filter() {
+        for delta in deltas.iter_mut() {
+            if let ModelDelta::ReplaceActive(stacks) = delta {
+                for stack in stacks.iter_mut() {
+                    stack
+                        .windows
+                        .retain(|w| w.output().map(|o| o == output).unwrap_or(true));
+                }
+                stacks.retain(|stack| !stack.windows.is_empty());
+            }
+        }
+    }
+    apply_deltas(deltas, apps_container);
+    apps_container.close_stale_popover();
+}
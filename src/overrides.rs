@@ -0,0 +1,111 @@
+// SPDX-License-Identifier: MPL-2.0-only
+
+//! Per-app replacements for the icon, name and command of a desktop entry, read from
+//! `overrides.toml` next to the config file:
+//!
+//! ```toml
+//! ["steam.desktop"]
+//! icon = "steam-symbolic"
+//! exec = "flatpak run com.valvesoftware.Steam"
+//! ```
+
+use gio::{AppInfo, DesktopAppInfo, Icon};
+use gtk4::prelude::*;
+use gtk4::glib;
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct AppOverride {
+    /// icon name or path
+    pub icon: Option<String>,
+    /// command line used instead of the Exec key
+    pub exec: Option<String>,
+    pub name: Option<String>,
+}
+
+static OVERRIDES: Lazy<RwLock<HashMap<String, AppOverride>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+pub fn overrides_path() -> PathBuf {
+    let mut path = glib::user_config_dir();
+    path.push(crate::ID);
+    std::fs::create_dir_all(&path).expect("Could not create directory.");
+    path.push("overrides.toml");
+    path
+}
+
+/// Reads the overrides file, a missing file removes every override.
+/// Returns the desktop ids whose overrides changed.
+pub fn load() -> Vec<String> {
+    let mut overrides: HashMap<String, AppOverride> = match std::fs::read_to_string(overrides_path()) {
+        Ok(data) => match toml::from_str(&data) {
+            Ok(overrides) => overrides,
+            Err(err) => {
+                log::warn!("Failed to parse the app overrides: {}", err);
+                HashMap::new()
+            }
+        },
+        Err(_) => HashMap::new(),
+    };
+    overrides.retain(|id, _| {
+        let known = DesktopAppInfo::new(id).is_some();
+        if !known {
+            log::warn!("Ignoring override for unknown desktop id {}", id);
+        }
+        known
+    });
+
+    let mut current = OVERRIDES.write().unwrap();
+    let mut changed: Vec<String> = current
+        .iter()
+        .filter(|(id, o)| overrides.get(*id) != Some(o))
+        .map(|(id, _)| id.clone())
+        .collect();
+    changed.extend(
+        overrides
+            .keys()
+            .filter(|id| !current.contains_key(*id))
+            .cloned(),
+    );
+    *current = overrides;
+    changed
+}
+
+pub fn get(id: &str) -> Option<AppOverride> {
+    OVERRIDES.read().unwrap().get(id).cloned()
+}
+
+pub fn icon(id: &str) -> Option<Icon> {
+    get(id)?
+        .icon
+        .and_then(|icon| Icon::for_string(&icon).ok())
+}
+
+pub fn name(id: &str) -> Option<String> {
+    get(id)?.name
+}
+
+/// The app info to launch for `id`, built from the overridden command line if there is one.
+pub fn launch_info(id: &str) -> Option<AppInfo> {
+    let app_info = crate::generated_entry::app_info(id)?;
+    let exec = match get(id).and_then(|o| o.exec) {
+        Some(exec) => exec,
+        None => return Some(app_info.upcast()),
+    };
+    match AppInfo::create_from_commandline(
+        &exec,
+        Some(&app_info.name()),
+        gio::AppInfoCreateFlags::SUPPORTS_STARTUP_NOTIFICATION,
+    ) {
+        Ok(app_info) => Some(app_info),
+        Err(err) => {
+            log::warn!("Failed to use the command override for {}: {}", id, err);
+            Some(app_info.upcast())
+        }
+    }
+}
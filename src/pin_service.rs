@@ -0,0 +1,223 @@
+// SPDX-License-Identifier: MPL-2.0-only
+
+use crate::app_id;
+use crate::custom_launcher::{self, CustomLauncher, LaunchTarget};
+use crate::utils::Event;
+use gio::DesktopAppInfo;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{mpsc, oneshot};
+use zbus::{dbus_interface, Connection, SignalContext};
+
+pub const PIN_PATH: &str = "/com/System76/CosmicDockAppList";
+// the application owns `crate::ID` on the session bus, so the interface gets its own name
+pub const PIN_NAME: &str = "com.system76.CosmicDockAppList.Pins";
+
+/// The event loops of the app list instances in the process. The pin interface is exported
+/// once for all of them: changes are sent to every instance, queries are answered by the one
+/// that attached first.
+#[derive(Debug, Clone, Default)]
+pub struct PinTargets {
+    targets: Arc<Mutex<Vec<mpsc::Sender<Event>>>>,
+    // every instance applies a change sent to all of them, it is announced once
+    announced: Arc<Mutex<Option<Vec<String>>>>,
+}
+
+impl PinTargets {
+    pub fn attach(&self, tx: mpsc::Sender<Event>) {
+        self.targets.lock().unwrap().push(tx);
+    }
+
+    pub fn detach(&self, tx: &mpsc::Sender<Event>) {
+        self.targets
+            .lock()
+            .unwrap()
+            .retain(|target| !target.same_channel(tx));
+    }
+
+    /// Whether `pinned` differs from the saved apps last announced, which it then becomes.
+    pub fn should_announce(&self, pinned: &[String]) -> bool {
+        let mut announced = self.announced.lock().unwrap();
+        if announced.as_deref() == Some(pinned) {
+            return false;
+        }
+        *announced = Some(pinned.to_vec());
+        true
+    }
+
+    fn primary(&self) -> Option<mpsc::Sender<Event>> {
+        self.all().into_iter().next()
+    }
+
+    // instances whose event loop stopped without detaching are skipped
+    fn all(&self) -> Vec<mpsc::Sender<Event>> {
+        let mut targets = self.targets.lock().unwrap();
+        targets.retain(|target| !target.is_closed());
+        targets.clone()
+    }
+
+    // true if at least one instance received the event
+    async fn send_all(&self, event: impl Fn() -> Event) -> bool {
+        let mut sent = false;
+        for target in self.all() {
+            sent |= target.send(event()).await.is_ok();
+        }
+        sent
+    }
+}
+
+/// Lets other programs pin and unpin apps in the dock.
+pub struct PinService {
+    targets: PinTargets,
+}
+
+impl PinService {
+    // the reply is only sent once every event queued before it has been handled
+    async fn saved_ids(&self) -> Vec<String> {
+        let primary = match self.targets.primary() {
+            Some(primary) => primary,
+            None => return Vec::new(),
+        };
+        let (reply_tx, reply_rx) = oneshot::channel();
+        if primary.send(Event::SavedIds(reply_tx)).await.is_err() {
+            return Vec::new();
+        }
+        reply_rx.await.unwrap_or_default()
+    }
+
+    async fn favorite(&self, desktop_id: &str, should_favorite: bool) -> bool {
+        if DesktopAppInfo::new(desktop_id).is_none() && custom_launcher::get(desktop_id).is_none() {
+            log::warn!("Refusing to pin unknown desktop id {}", desktop_id);
+            return false;
+        }
+        // another packaging of a saved app counts as saved
+        let was_saved = self
+            .saved_ids()
+            .await
+            .iter()
+            .any(|id| app_id::same_app(id, desktop_id));
+        if was_saved == should_favorite {
+            return false;
+        }
+        let sent = self
+            .targets
+            .send_all(|| Event::Favorite {
+                id: desktop_id.to_string(),
+                favorite: should_favorite,
+                index: None,
+            })
+            .await;
+        if !sent {
+            return false;
+        }
+        let is_saved = self
+            .saved_ids()
+            .await
+            .iter()
+            .any(|id| app_id::same_app(id, desktop_id));
+        is_saved == should_favorite
+    }
+}
+
+#[dbus_interface(name = "com.system76.CosmicDockAppList")]
+impl PinService {
+    /// Adds the app to the saved apps, returns false if it is unknown or already pinned.
+    async fn pin(&self, desktop_id: String) -> bool {
+        self.favorite(&desktop_id, true).await
+    }
+
+    /// Removes the app from the saved apps, returns false if it wasn't pinned.
+    async fn unpin(&self, desktop_id: String) -> bool {
+        self.favorite(&desktop_id, false).await
+    }
+
+    /// Focuses the window at `index` of the app, counted in the order the dock lists them,
+    /// or launches the app if it has no windows. `app_id` is a desktop id or window app id.
+    async fn activate_window(&self, app_id: String, index: u32) -> bool {
+        // activating in every instance would focus or launch the app once per instance
+        match self.targets.primary() {
+            Some(primary) => primary
+                .send(Event::ActivateIndex {
+                    app_id,
+                    index: index as usize,
+                })
+                .await
+                .is_ok(),
+            None => false,
+        }
+    }
+
+    /// Saves a launcher that opens `url`, or runs `exec` if `url` is empty, and returns its id,
+    /// which `Unpin` takes. Windows whose app id is `match_string` or whose title contains it
+    /// are shown on the launcher. Returns an empty string if neither `url` nor `exec` is given.
+    async fn add_custom_launcher(
+        &self,
+        name: String,
+        icon: String,
+        url: String,
+        exec: String,
+        match_string: String,
+    ) -> String {
+        let target = match (url.is_empty(), exec.is_empty()) {
+            (false, _) => LaunchTarget::Url(url),
+            (true, false) => LaunchTarget::Exec(exec),
+            (true, true) => return String::new(),
+        };
+        let launcher = CustomLauncher::new(&name, &icon, target, &match_string);
+        let id = launcher.id.clone();
+        let sent = self
+            .targets
+            .send_all(|| Event::AddCustomLauncher(launcher.clone()))
+            .await;
+        if !sent {
+            return String::new();
+        }
+        let saved = self.saved_ids().await.iter().any(|saved_id| *saved_id == id);
+        if saved {
+            id
+        } else {
+            String::new()
+        }
+    }
+
+    /// Clears the badge, progress and urgency the app with `desktop_id` sent over the launcher
+    /// entry API, until it sends new values.
+    async fn clear_notifications(&self, desktop_id: String) -> bool {
+        self.targets
+            .send_all(|| Event::ClearNotifications(desktop_id.clone()))
+            .await
+    }
+
+    /// Desktop ids of the saved apps, in dock order.
+    async fn list_pinned(&self) -> Vec<String> {
+        self.saved_ids().await
+    }
+
+    #[dbus_interface(signal)]
+    pub async fn pinned_changed(ctxt: &SignalContext<'_>, pinned: Vec<String>) -> zbus::Result<()>;
+}
+
+/// Exports the pin interface on `conn` and requests [`PIN_NAME`] for it. Nothing stays
+/// exported if either step fails.
+pub async fn serve(conn: &Connection, targets: PinTargets) -> zbus::Result<()> {
+    if !conn.object_server().at(PIN_PATH, PinService { targets }).await? {
+        return Err(zbus::Error::Failure(format!("{} is already exported", PIN_PATH)));
+    }
+    if let Err(err) = conn.request_name(PIN_NAME).await {
+        let _ = conn.object_server().remove::<PinService, _>(PIN_PATH).await;
+        return Err(err);
+    }
+    Ok(())
+}
+
+/// Removes the pin interface from `conn` and releases [`PIN_NAME`].
+pub async fn withdraw(conn: &Connection) -> zbus::Result<()> {
+    conn.object_server().remove::<PinService, _>(PIN_PATH).await?;
+    conn.release_name(PIN_NAME).await?;
+    Ok(())
+}
+
+/// Notifies listeners that the saved apps changed.
+pub async fn emit_pinned_changed(conn: &Connection, pinned: Vec<String>) -> zbus::Result<()> {
+    let ctxt = SignalContext::new(conn, PIN_PATH)?;
+    PinService::pinned_changed(&ctxt, pinned).await
+}
@@ -0,0 +1,77 @@
+// SPDX-License-Identifier: MPL-2.0-only
+
+//! The launch history behind the recent apps section.
+
+use crate::utils::recent_path;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::File;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The number of launches that are remembered, more than any dock shows.
+const MAX_ENTRIES: usize = 32;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Launch {
+    pub id: String,
+    /// seconds since the unix epoch
+    pub time: u64,
+}
+
+/// Desktop ids of launched apps, most recent first and without duplicates.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct RecentApps(Vec<Launch>);
+
+impl RecentApps {
+    pub fn load() -> Self {
+        File::open(recent_path())
+            .ok()
+            .and_then(|file| serde_json::from_reader(file).ok())
+            .unwrap_or_default()
+    }
+
+    fn store(&self) {
+        match File::create(recent_path()) {
+            Ok(file) => {
+                if let Err(err) = serde_json::to_writer_pretty(file, self) {
+                    log::warn!("Failed to save the launch history: {}", err);
+                }
+            }
+            Err(err) => log::warn!("Failed to save the launch history: {}", err),
+        }
+    }
+
+    /// Moves `id` to the front of the history.
+    pub fn record(&mut self, id: &str) {
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        self.0.retain(|launch| launch.id != id);
+        self.0.insert(
+            0,
+            Launch {
+                id: id.to_string(),
+                time,
+            },
+        );
+        self.0.truncate(MAX_ENTRIES);
+        self.store();
+    }
+
+    pub fn clear(&mut self) {
+        self.0.clear();
+        self.store();
+    }
+
+    /// The last `n` launched apps that aren't in `exclude`.
+    pub fn latest(&self, n: usize, exclude: &HashSet<String>) -> Vec<String> {
+        self.0
+            .iter()
+            .filter(|launch| !exclude.contains(&launch.id))
+            .take(n)
+            .map(|launch| launch.id.clone())
+            .collect()
+    }
+}
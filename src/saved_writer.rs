@@ -0,0 +1,196 @@
+// SPDX-License-Identifier: MPL-2.0-only
+
+//! Writes the saved apps file on a worker thread. Each write goes to a temporary file that is
+//! renamed over the old one, so a crash leaves either the old or the new list behind. A
+//! temporary file left by a crash is never read and is replaced by the next write.
+
+use crate::custom_launcher::StoredApp;
+use crate::utils::Event;
+use crate::watchdog;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread::{self, JoinHandle};
+use tokio::sync::mpsc::Sender;
+
+#[derive(Debug)]
+pub struct SavedWriter {
+    jobs: Option<mpsc::Sender<Vec<StoredApp>>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl SavedWriter {
+    /// Starts the worker writing to `path`, failed writes are reported as `Event::SaveFailed`.
+    pub fn new(path: PathBuf, tx: Sender<Event>) -> Self {
+        let (jobs, rx) = mpsc::channel::<Vec<StoredApp>>();
+        let worker = thread::spawn(move || {
+            while let Ok(mut apps) = rx.recv() {
+                // only the newest list is worth writing
+                while let Ok(newer) = rx.try_recv() {
+                    apps = newer;
+                }
+                let _span = watchdog::span("write saved apps");
+                let result = serde_json::to_vec_pretty(&apps)
+                    .map_err(|err| err.to_string())
+                    .and_then(|data| write_atomic(&path, &data).map_err(|err| err.to_string()));
+                if let Err(err) = result {
+                    log::error!("Failed to save the saved apps: {}", err);
+                    // the event loop may be gone or busy waiting for this thread on shutdown
+                    let _ = tx.try_send(Event::SaveFailed(err));
+                }
+            }
+        });
+        Self {
+            jobs: Some(jobs),
+            worker: Some(worker),
+        }
+    }
+
+    pub fn write(&self, apps: Vec<StoredApp>) {
+        if let Some(jobs) = self.jobs.as_ref() {
+            let _ = jobs.send(apps);
+        }
+    }
+
+    /// Waits until the queued lists are written.
+    pub fn finish(&mut self) {
+        drop(self.jobs.take());
+        if let Some(worker) = self.worker.take() {
+            let _span = watchdog::span("join saved apps writer");
+            if worker.join().is_err() {
+                log::error!("The saved apps writer panicked");
+            }
+        }
+    }
+}
+
+impl Drop for SavedWriter {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+/// Reads the saved apps file at `path`, `None` if it can't be opened. A file that can't be
+/// parsed loads as an empty list, so it isn't replaced by the default apps.
+pub fn load(path: &Path) -> Option<Vec<StoredApp>> {
+    let file = File::open(path).ok()?;
+    match serde_json::from_reader(io::BufReader::new(file)) {
+        Ok(apps) => Some(apps),
+        Err(err) => {
+            log::warn!("Failed to parse the saved apps: {}", err);
+            Some(Vec::new())
+        }
+    }
+}
+
+/// `path` with ".tmp" appended, next to it so the rename doesn't cross file systems.
+pub fn temp_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".tmp");
+    path.with_file_name(name)
+}
+
+fn write_atomic(path: &Path, data: &[u8]) -> io::Result<()> {
+    let temp = temp_path(path);
+    let mut file = File::create(&temp)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    std::fs::rename(&temp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a directory of its own for each test, as they run in parallel
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "cosmic-dock-saved-writer-{}-{}",
+            std::process::id(),
+            name
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn apps(ids: &[&str]) -> Vec<StoredApp> {
+        ids.iter().map(|id| StoredApp::Id(id.to_string())).collect()
+    }
+
+    fn ids(apps: &[StoredApp]) -> Vec<&str> {
+        apps.iter()
+            .map(|app| match app {
+                StoredApp::Id(id) => id.as_str(),
+                StoredApp::Custom(launcher) => launcher.id.as_str(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn queued_lists_end_with_the_newest() {
+        let dir = test_dir("coalesce");
+        let path = dir.join("saved.json");
+        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        let mut writer = SavedWriter::new(path.clone(), tx);
+        writer.write(apps(&["firefox.desktop"]));
+        writer.write(apps(&["firefox.desktop", "gedit.desktop"]));
+        writer.write(apps(&["gedit.desktop", "firefox.desktop"]));
+        writer.finish();
+
+        let loaded = load(&path).unwrap();
+        assert_eq!(ids(&loaded), vec!["gedit.desktop", "firefox.desktop"]);
+        assert!(!temp_path(&path).exists());
+        assert!(rx.try_recv().is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn stale_temp_file_is_ignored_and_replaced() {
+        let dir = test_dir("stale");
+        let path = dir.join("saved.json");
+        std::fs::write(&path, r#"["firefox.desktop"]"#).unwrap();
+        // a write that crashed before the rename
+        std::fs::write(temp_path(&path), r#"["gedit.desktop", "fire"#).unwrap();
+
+        assert_eq!(ids(&load(&path).unwrap()), vec!["firefox.desktop"]);
+
+        let (tx, _rx) = tokio::sync::mpsc::channel(1);
+        let mut writer = SavedWriter::new(path.clone(), tx);
+        writer.write(apps(&["firefox.desktop", "gedit.desktop"]));
+        writer.finish();
+        assert_eq!(
+            ids(&load(&path).unwrap()),
+            vec!["firefox.desktop", "gedit.desktop"]
+        );
+        assert!(!temp_path(&path).exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn missing_and_broken_files() {
+        let dir = test_dir("broken");
+        let path = dir.join("saved.json");
+        std::fs::write(temp_path(&path), r#"["firefox.desktop"]"#).unwrap();
+        // only the temporary file is there, so nothing is loaded and the defaults are used
+        assert!(load(&path).is_none());
+
+        std::fs::write(&path, "{").unwrap();
+        assert!(load(&path).unwrap().is_empty());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn failed_writes_are_reported() {
+        let dir = test_dir("failed");
+        // the parent of the file doesn't exist
+        let path = dir.join("missing").join("saved.json");
+        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        let mut writer = SavedWriter::new(path, tx);
+        writer.write(apps(&["firefox.desktop"]));
+        writer.finish();
+        assert!(matches!(rx.try_recv(), Ok(Event::SaveFailed(_))));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,161 @@
+// SPDX-License-Identifier: MPL-2.0-only
+
+//! The preferences of the app list as a widget the settings app can embed. The standalone
+//! window shows it from its menu. Changes are saved to the config, which the running app list
+//! watches and applies through the setters of `AppsContainer`.
+
+use crate::config::{AppsConfig, ClickAction, GroupingMode, SortOrder};
+use crate::fl;
+use cascade::cascade;
+use gtk4::prelude::*;
+use gtk4::{Align, Box, DropDown, Label, Orientation, Switch};
+
+const ICON_SIZES: &[u32] = &[0, 16, 24, 32, 48, 64, 96];
+const CLICK_ACTIONS: &[ClickAction] = &[
+    ClickAction::ShowPopover,
+    ClickAction::FocusLast,
+    ClickAction::CycleWindows,
+    ClickAction::MinimizeToggle,
+];
+const SORT_ORDERS: &[SortOrder] = &[
+    SortOrder::LaunchOrder,
+    SortOrder::MostRecentlyUsed,
+    SortOrder::Alphabetical,
+];
+const GROUPING_MODES: &[GroupingMode] = &[GroupingMode::GroupByApp, GroupingMode::OnePerWindow];
+
+/// A page with a row per setting, showing the config as it is when the page is created.
+pub fn new() -> gtk4::Widget {
+    let config = AppsConfig::load();
+    let page = cascade! {
+        Box::new(Orientation::Vertical, 12);
+        ..set_margin_start(18);
+        ..set_margin_end(18);
+        ..set_margin_top(18);
+        ..set_margin_bottom(18);
+        ..add_css_class("dock_settings");
+    };
+
+    let mut icon_sizes = ICON_SIZES.to_vec();
+    if !icon_sizes.contains(&config.icon_size) {
+        icon_sizes.push(config.icon_size);
+    }
+    let icon_size_names: Vec<String> = icon_sizes
+        .iter()
+        .map(|size| match size {
+            0 => fl!("icon-size-automatic"),
+            size => fl!("icon-size-pixels", size = *size),
+        })
+        .collect();
+    let icon_size = choice(&icon_size_names, &icon_sizes, config.icon_size, |config, size| {
+        config.icon_size = size;
+    });
+    page.append(&row(&fl!("icon-size"), &icon_size));
+
+    let click_action_names = [
+        fl!("click-show-popover"),
+        fl!("click-focus-last"),
+        fl!("click-cycle-windows"),
+        fl!("click-minimize-toggle"),
+    ];
+    let click_action = choice(
+        &click_action_names,
+        CLICK_ACTIONS,
+        config.click_action,
+        |config, click_action| config.click_action = click_action,
+    );
+    page.append(&row(&fl!("click-action"), &click_action));
+
+    let show_saved = toggle(config.show_saved, |config, show_saved| {
+        config.show_saved = show_saved;
+    });
+    page.append(&row(&fl!("show-saved"), &show_saved));
+
+    let show_active = toggle(config.show_active, |config, show_active| {
+        config.show_active = show_active;
+    });
+    page.append(&row(&fl!("show-active"), &show_active));
+
+    let sort_order_names = [
+        fl!("sort-launch-order"),
+        fl!("sort-most-recently-used"),
+        fl!("sort-alphabetical"),
+    ];
+    let sort_order = choice(
+        &sort_order_names,
+        SORT_ORDERS,
+        config.sort_order,
+        |config, sort_order| config.sort_order = sort_order,
+    );
+    page.append(&row(&fl!("sort-order"), &sort_order));
+
+    let grouping_mode_names = [fl!("group-by-app"), fl!("one-per-window")];
+    let grouping_mode = choice(
+        &grouping_mode_names,
+        GROUPING_MODES,
+        config.grouping_mode,
+        |config, grouping_mode| config.grouping_mode = grouping_mode,
+    );
+    page.append(&row(&fl!("grouping-mode"), &grouping_mode));
+
+    let animations = toggle(config.animations, |config, animations| {
+        config.animations = animations;
+    });
+    page.append(&row(&fl!("animations"), &animations));
+
+    page.upcast()
+}
+
+fn row(title: &str, control: &impl IsA<gtk4::Widget>) -> Box {
+    let label = cascade! {
+        Label::new(Some(title));
+        ..set_halign(Align::Start);
+        ..set_hexpand(true);
+        ..set_mnemonic_widget(Some(control));
+    };
+    control.set_valign(Align::Center);
+    cascade! {
+        Box::new(Orientation::Horizontal, 12);
+        ..append(&label);
+        ..append(control);
+    }
+}
+
+// other settings may have changed since the page was created, so the config is read again
+fn update_config<F: FnOnce(&mut AppsConfig)>(update: F) {
+    let mut config = AppsConfig::load();
+    update(&mut config);
+    if let Err(err) = config.save() {
+        log::warn!("Failed to save the app list settings: {}", err);
+    }
+}
+
+fn choice<T, F>(names: &[String], values: &[T], current: T, set: F) -> DropDown
+where
+    T: Copy + PartialEq + 'static,
+    F: Fn(&mut AppsConfig, T) + 'static,
+{
+    let names: Vec<&str> = names.iter().map(|name| name.as_str()).collect();
+    let drop_down = DropDown::from_strings(&names);
+    if let Some(position) = values.iter().position(|value| *value == current) {
+        drop_down.set_selected(position as u32);
+    }
+    let values = values.to_vec();
+    drop_down.connect_selected_notify(move |drop_down| {
+        if let Some(value) = values.get(drop_down.selected() as usize) {
+            update_config(|config| set(config, *value));
+        }
+    });
+    drop_down
+}
+
+fn toggle<F: Fn(&mut AppsConfig, bool) + 'static>(active: bool, set: F) -> Switch {
+    let switch = cascade! {
+        Switch::new();
+        ..set_active(active);
+    };
+    switch.connect_active_notify(move |switch| {
+        update_config(|config| set(config, switch.is_active()));
+    });
+    switch
+}
@@ -0,0 +1,209 @@
+// SPDX-License-Identifier: MPL-2.0-only
+
+//! The style sheet of the dock. Colors follow the dark or light style of the desktop, which is
+//! read from the settings portal and falls back to the GTK settings. Whether the desktop allows
+//! animations is read the same way.
+
+use crate::utils::session_connection;
+use futures_util::StreamExt;
+use gtk4::gdk::Display;
+use gtk4::prelude::*;
+use gtk4::{glib, CssProvider, IconTheme, StyleContext};
+use std::cell::Cell;
+use std::rc::Rc;
+use zbus::{fdo, MessageStream, MessageType};
+use zvariant::{OwnedValue, Value};
+
+const SHARED: &[u8] = include_bytes!("style.css");
+const DARK: &[u8] = include_bytes!("style-dark.css");
+const LIGHT: &[u8] = include_bytes!("style-light.css");
+
+const PORTAL_DEST: &str = "org.freedesktop.portal.Desktop";
+const PORTAL_PATH: &str = "/org/freedesktop/portal/desktop";
+const PORTAL_SETTINGS: &str = "org.freedesktop.portal.Settings";
+const APPEARANCE: &str = "org.freedesktop.appearance";
+const COLOR_SCHEME: &str = "color-scheme";
+const INTERFACE: &str = "org.gnome.desktop.interface";
+const ENABLE_ANIMATIONS: &str = "enable-animations";
+
+thread_local! {
+    // the portal setting, which takes precedence over the GTK setting once it was read
+    static PORTAL_ANIMATIONS: Cell<Option<bool>> = Cell::new(None);
+}
+
+/// Adds the style sheet to the default display and reloads it when the desktop switches between
+/// dark and light. `on_change` is called after a style or icon theme change so icons can be
+/// looked up again. Only the first call has an effect.
+pub fn load<F: Fn() + 'static>(on_change: F) {
+    static LOADED: std::sync::Once = std::sync::Once::new();
+    let mut on_change = Some(on_change);
+    LOADED.call_once(|| {
+        if let (Some(display), Some(on_change)) = (Display::default(), on_change.take()) {
+            watch(&display, Rc::new(on_change));
+        }
+    });
+}
+
+fn watch(display: &Display, on_change: Rc<dyn Fn()>) {
+    // the whole sheet is reloaded at once so the variant rules cascade with the shared ones
+    let provider = CssProvider::new();
+    StyleContext::add_provider_for_display(
+        display,
+        &provider,
+        gtk4::STYLE_PROVIDER_PRIORITY_APPLICATION,
+    );
+    // 0 is no preference, 1 prefers dark and 2 prefers light
+    let color_scheme = Rc::new(Cell::new(0));
+    let dark = Rc::new(Cell::new(None));
+    let update = Rc::new(glib::clone!(@strong provider, @strong color_scheme, @strong dark, @strong on_change => move || {
+        let prefers_dark = prefers_dark(color_scheme.get());
+        if dark.replace(Some(prefers_dark)) == Some(prefers_dark) {
+            return;
+        }
+        let variant = if prefers_dark { DARK } else { LIGHT };
+        provider.load_from_data(&[SHARED, variant].concat());
+        on_change();
+    }));
+    update();
+
+    if let Some(settings) = gtk4::Settings::default() {
+        for property in ["gtk-application-prefer-dark-theme", "gtk-theme-name"] {
+            settings.connect_notify_local(
+                Some(property),
+                glib::clone!(@strong update => move |_, _| update()),
+            );
+        }
+    }
+    IconTheme::for_display(display).connect_changed(move |_| on_change());
+
+    glib::MainContext::default().spawn_local(async move {
+        let on_value = |value: &Value| {
+            if let Some(value) = color_scheme_value(value) {
+                color_scheme.set(value);
+                update();
+            }
+        };
+        if let Err(err) = watch_portal_setting(APPEARANCE, COLOR_SCHEME, &on_value).await {
+            log::warn!("Failed to read the color scheme from the settings portal: {}", err);
+        }
+    });
+}
+
+/// Whether the desktop allows animations, users that prefer reduced motion turn them off.
+pub fn desktop_animations() -> bool {
+    PORTAL_ANIMATIONS.with(|animations| animations.get()).unwrap_or_else(|| {
+        gtk4::Settings::default()
+            .map(|settings| settings.is_gtk_enable_animations())
+            .unwrap_or(true)
+    })
+}
+
+/// Calls `on_change` when [`desktop_animations`] may have changed. Only the first call has an
+/// effect.
+pub fn watch_animations<F: Fn() + 'static>(on_change: F) {
+    static WATCHING: std::sync::Once = std::sync::Once::new();
+    let mut on_change = Some(on_change);
+    WATCHING.call_once(|| {
+        let on_change = match on_change.take() {
+            Some(on_change) => Rc::new(on_change),
+            None => return,
+        };
+        if let Some(settings) = gtk4::Settings::default() {
+            settings.connect_notify_local(
+                Some("gtk-enable-animations"),
+                glib::clone!(@strong on_change => move |_, _| on_change()),
+            );
+        }
+        glib::MainContext::default().spawn_local(async move {
+            let on_value = |value: &Value| {
+                if let Some(enabled) = bool_value(value) {
+                    PORTAL_ANIMATIONS.with(|animations| animations.set(Some(enabled)));
+                    on_change();
+                }
+            };
+            if let Err(err) = watch_portal_setting(INTERFACE, ENABLE_ANIMATIONS, &on_value).await {
+                log::warn!("Failed to read the animation setting from the settings portal: {}", err);
+            }
+        });
+    });
+}
+
+fn prefers_dark(color_scheme: u32) -> bool {
+    match color_scheme {
+        1 => true,
+        2 => false,
+        _ => gtk4::Settings::default()
+            .map(|settings| {
+                settings.is_gtk_application_prefer_dark_theme()
+                    || settings
+                        .gtk_theme_name()
+                        .map(|name| name.to_lowercase().ends_with("dark"))
+                        .unwrap_or_default()
+            })
+            .unwrap_or(true),
+    }
+}
+
+fn color_scheme_value(value: &Value) -> Option<u32> {
+    match value {
+        Value::U32(color_scheme) => Some(*color_scheme),
+        // Read wraps the setting in a second variant
+        Value::Value(value) => color_scheme_value(value),
+        _ => None,
+    }
+}
+
+fn bool_value(value: &Value) -> Option<bool> {
+    match value {
+        Value::Bool(value) => Some(*value),
+        Value::Value(value) => bool_value(value),
+        _ => None,
+    }
+}
+
+// calls `on_value` with the current value of the setting and again whenever it changes
+async fn watch_portal_setting(
+    namespace: &str,
+    key: &str,
+    on_value: &dyn Fn(&Value),
+) -> zbus::Result<()> {
+    let conn = session_connection().await?;
+    let reply = conn
+        .call_method(
+            Some(PORTAL_DEST),
+            PORTAL_PATH,
+            Some(PORTAL_SETTINGS),
+            "Read",
+            &(namespace, key),
+        )
+        .await?;
+    on_value(&reply.body::<OwnedValue>()?);
+
+    let dbus = fdo::DBusProxy::new(&conn).await?;
+    dbus.add_match(&format!(
+        "type='signal',interface='{}',member='SettingChanged'",
+        PORTAL_SETTINGS
+    ))
+    .await?;
+    let mut stream = MessageStream::from(&conn);
+    while let Some(m) = stream.next().await {
+        let m = match m {
+            Ok(m) if m.message_type() == MessageType::Signal => m,
+            _ => continue,
+        };
+        let interface = m.interface().map(|i| i.to_string());
+        let member = m.member().map(|m| m.to_string());
+        if interface.as_deref() != Some(PORTAL_SETTINGS) || member.as_deref() != Some("SettingChanged") {
+            continue;
+        }
+        let (changed_namespace, changed_key, value) =
+            match m.body::<(String, String, OwnedValue)>() {
+                Ok(body) => body,
+                Err(_) => continue,
+            };
+        if changed_namespace == namespace && changed_key == key {
+            on_value(&value);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,194 @@
+// SPDX-License-Identifier: MPL-2.0-only
+
+//! The optional system section at the end of the dock: removable volumes and the trash.
+
+use crate::dock_object::{DockObject, Location};
+use crate::fl;
+use gtk4::prelude::*;
+use gtk4::{gdk, gio, glib};
+
+pub const TRASH_URI: &str = "trash:///";
+
+/// The monitors keeping the system section up to date.
+pub struct SystemItems {
+    volume_monitor: gio::VolumeMonitor,
+    handlers: Vec<glib::SignalHandlerId>,
+    trash_monitor: Option<gio::FileMonitor>,
+}
+
+impl SystemItems {
+    /// Fills `model` with the ejectable mounts followed by the trash and keeps it up to date.
+    pub fn watch(model: &gio::ListStore) -> Self {
+        let volume_monitor = gio::VolumeMonitor::get();
+        for mount in volume_monitor.mounts() {
+            add_mount(model, &mount);
+        }
+        let trash = DockObject::from_location(trash_location());
+        model.append(&trash);
+
+        let handlers = vec![
+            volume_monitor.connect_mount_added(glib::clone!(@weak model => move |_, mount| {
+                add_mount(&model, mount);
+            })),
+            volume_monitor.connect_mount_removed(glib::clone!(@weak model => move |_, mount| {
+                remove_mount(&model, mount);
+            })),
+        ];
+
+        let trash_file = gio::File::for_uri(TRASH_URI);
+        let trash_monitor =
+            match trash_file.monitor_directory(gio::FileMonitorFlags::NONE, None::<&gio::Cancellable>) {
+                Ok(monitor) => {
+                    monitor.connect_changed(glib::clone!(@weak trash => move |_, _, _, _| {
+                        trash.set_property("location", crate::dock_object::BoxedLocation(Some(trash_location())));
+                    }));
+                    Some(monitor)
+                }
+                Err(err) => {
+                    log::warn!("Failed to watch the trash: {}", err);
+                    None
+                }
+            };
+
+        Self {
+            volume_monitor,
+            handlers,
+            trash_monitor,
+        }
+    }
+
+    pub fn stop(self) {
+        for handler in self.handlers {
+            self.volume_monitor.disconnect(handler);
+        }
+        if let Some(trash_monitor) = self.trash_monitor {
+            trash_monitor.cancel();
+        }
+    }
+}
+
+fn trash_items() -> u32 {
+    gio::File::for_uri(TRASH_URI)
+        .query_info(
+            "trash::item-count",
+            gio::FileQueryInfoFlags::NONE,
+            None::<&gio::Cancellable>,
+        )
+        .map(|info| info.attribute_uint32("trash::item-count"))
+        .unwrap_or(0)
+}
+
+// the icon shows whether there is anything in the trash
+fn trash_location() -> Location {
+    let icon_name = if trash_items() > 0 {
+        "user-trash-full"
+    } else {
+        "user-trash"
+    };
+    Location {
+        uri: TRASH_URI.to_string(),
+        name: fl!("trash"),
+        icon: gio::ThemedIcon::new(icon_name).upcast(),
+        mount: None,
+    }
+}
+
+fn mount_uri(mount: &gio::Mount) -> String {
+    mount.root().uri().to_string()
+}
+
+// only removable drives are shown, not every partition
+fn add_mount(model: &gio::ListStore, mount: &gio::Mount) {
+    if !mount.can_eject() || mount.is_shadowed() {
+        return;
+    }
+    let location = Location {
+        uri: mount_uri(mount),
+        name: mount.name().to_string(),
+        icon: mount.icon(),
+        mount: Some(mount.clone()),
+    };
+    // mounts go before the trash
+    let position = model.n_items().saturating_sub(1);
+    model.insert(position, &DockObject::from_location(location));
+}
+
+fn remove_mount(model: &gio::ListStore, mount: &gio::Mount) {
+    let uri = mount_uri(mount);
+    let mut i: u32 = 0;
+    while let Some(item) = model.item(i) {
+        let matches = item
+            .downcast_ref::<DockObject>()
+            .and_then(|o| o.location())
+            .map(|location| location.uri == uri)
+            .unwrap_or_default();
+        if matches {
+            model.remove(i);
+            return;
+        }
+        i += 1;
+    }
+}
+
+/// Opens the location in the default file manager.
+pub fn open(location: &Location) {
+    let context = gdk::Display::default().map(|display| display.app_launch_context());
+    if let Err(err) = gio::AppInfo::launch_default_for_uri(&location.uri, context.as_ref()) {
+        log::warn!("Failed to open {}: {}", location.uri, err);
+    }
+}
+
+pub fn can_empty_trash() -> bool {
+    trash_items() > 0
+}
+
+/// Deletes everything in the trash.
+pub fn empty_trash() {
+    glib::MainContext::default().spawn_local(async move {
+        let trash = gio::File::for_uri(TRASH_URI);
+        let enumerator = match trash
+            .enumerate_children_future(
+                "standard::name",
+                gio::FileQueryInfoFlags::NOFOLLOW_SYMLINKS,
+                glib::PRIORITY_DEFAULT,
+            )
+            .await
+        {
+            Ok(enumerator) => enumerator,
+            Err(err) => {
+                log::warn!("Failed to empty the trash: {}", err);
+                return;
+            }
+        };
+        loop {
+            let infos = match enumerator.next_files_future(64, glib::PRIORITY_DEFAULT).await {
+                Ok(infos) if !infos.is_empty() => infos,
+                Ok(_) => break,
+                Err(err) => {
+                    log::warn!("Failed to empty the trash: {}", err);
+                    break;
+                }
+            };
+            for info in infos {
+                let child = trash.child(info.name());
+                if let Err(err) = child.delete_future(glib::PRIORITY_DEFAULT).await {
+                    log::warn!("Failed to delete {}: {}", child.uri(), err);
+                }
+            }
+        }
+    });
+}
+
+pub fn eject(mount: &gio::Mount) {
+    let name = mount.name();
+    mount.eject_with_operation(
+        gio::MountUnmountFlags::NONE,
+        None::<&gio::MountOperation>,
+        None::<&gio::Cancellable>,
+        move |result| {
+            if let Err(err) = result {
+                log::warn!("Failed to eject {}: {}", name, err);
+            }
+        },
+    );
+}
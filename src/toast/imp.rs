@@ -0,0 +1,39 @@
+// SPDX-License-Identifier: MPL-2.0-only
+
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+
+use gtk4::glib;
+use gtk4::subclass::prelude::*;
+use gtk4::{Label, Popover};
+use once_cell::sync::OnceCell;
+
+use super::Severity;
+
+#[derive(Debug, Default)]
+pub struct Toast {
+    pub label: OnceCell<Label>,
+    pub queue: RefCell<VecDeque<(Severity, String)>>,
+    /// severity of the message that is shown, `None` while nothing is
+    pub current: Cell<Option<Severity>>,
+    pub timeout: RefCell<Option<glib::SourceId>>,
+}
+
+#[glib::object_subclass]
+impl ObjectSubclass for Toast {
+    const NAME: &'static str = "DockToast";
+    type Type = super::Toast;
+    type ParentType = Popover;
+}
+
+impl ObjectImpl for Toast {
+    fn dispose(&self, _obj: &Self::Type) {
+        if let Some(source_id) = self.timeout.take() {
+            source_id.remove();
+        }
+    }
+}
+
+impl WidgetImpl for Toast {}
+
+impl PopoverImpl for Toast {}
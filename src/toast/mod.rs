@@ -0,0 +1,137 @@
+// SPDX-License-Identifier: MPL-2.0-only
+
+//! Short messages shown next to the dock, used for errors the user would otherwise not notice.
+
+use cascade::cascade;
+use cosmic_panel_config::config::Anchor;
+use gtk4::prelude::*;
+use gtk4::subclass::prelude::*;
+use gtk4::{glib, Box, Button, Label, Orientation, PositionType};
+use std::time::Duration;
+
+use crate::fl;
+
+mod imp;
+
+const TOAST_TIMEOUT_SECS: u64 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Info,
+    Error,
+}
+
+impl Severity {
+    fn css_class(self) -> &'static str {
+        match self {
+            Self::Info => "info",
+            Self::Error => "error",
+        }
+    }
+}
+
+glib::wrapper! {
+    pub struct Toast(ObjectSubclass<imp::Toast>)
+        @extends gtk4::Popover, gtk4::Widget,
+    @implements gtk4::Accessible, gtk4::Buildable, gtk4::ConstraintTarget, gtk4::Native, gtk4::ShortcutManager;
+}
+
+impl Default for Toast {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Toast {
+    pub fn new() -> Self {
+        let self_: Self = glib::Object::new(&[]).expect("Failed to create Toast");
+        let imp = imp::Toast::from_instance(&self_);
+
+        let label = cascade! {
+            Label::new(None);
+            ..set_wrap(true);
+            ..set_max_width_chars(40);
+            ..set_xalign(0.0);
+            ..add_css_class("dock_toast_label");
+        };
+        // nothing in the toast takes the keyboard focus away from the focused window
+        let close_button = cascade! {
+            Button::from_icon_name("window-close-symbolic");
+            ..set_focusable(false);
+            ..set_focus_on_click(false);
+            ..set_tooltip_text(Some(&fl!("dismiss")));
+            ..add_css_class("flat");
+        };
+        close_button.connect_clicked(glib::clone!(@weak self_ => move |_| {
+            self_.dismiss();
+        }));
+        let content = cascade! {
+            Box::new(Orientation::Horizontal, 8);
+            ..append(&label);
+            ..append(&close_button);
+        };
+        cascade! {
+            &self_;
+            ..set_child(Some(&content));
+            ..set_autohide(false);
+            ..set_can_focus(false);
+            ..set_has_arrow(false);
+            ..add_css_class("dock_toast");
+        };
+        imp.label.set(label).unwrap();
+        self_
+    }
+
+    /// Shows `message`, after the messages before it were dismissed or timed out.
+    pub fn show_message(&self, severity: Severity, message: String) {
+        let imp = imp::Toast::from_instance(self);
+        imp.queue.borrow_mut().push_back((severity, message));
+        if imp.current.get().is_none() {
+            self.show_next();
+        }
+    }
+
+    /// Hides the message that is shown and moves on to the next one.
+    pub fn dismiss(&self) {
+        let imp = imp::Toast::from_instance(self);
+        if let Some(source_id) = imp.timeout.take() {
+            source_id.remove();
+        }
+        self.popdown();
+        self.show_next();
+    }
+
+    fn show_next(&self) {
+        let imp = imp::Toast::from_instance(self);
+        if let Some(severity) = imp.current.take() {
+            self.remove_css_class(severity.css_class());
+        }
+        let (severity, message) = match imp.queue.borrow_mut().pop_front() {
+            Some(next) => next,
+            None => return,
+        };
+        imp.label.get().unwrap().set_text(&message);
+        self.add_css_class(severity.css_class());
+        imp.current.set(Some(severity));
+        let source_id = glib::timeout_add_local_once(
+            Duration::from_secs(TOAST_TIMEOUT_SECS),
+            glib::clone!(@weak self as self_ => move || {
+                let imp = imp::Toast::from_instance(&self_);
+                imp.timeout.replace(None);
+                self_.dismiss();
+            }),
+        );
+        imp.timeout.replace(Some(source_id));
+        self.popup();
+    }
+
+    /// Shows the toast on the side of the dock facing the screen.
+    pub fn set_anchor(&self, anchor: Anchor) {
+        self.set_position(match anchor {
+            Anchor::Left => PositionType::Right,
+            Anchor::Right => PositionType::Left,
+            Anchor::Top => PositionType::Bottom,
+            Anchor::Bottom => PositionType::Top,
+        });
+    }
+}
@@ -2,35 +2,683 @@
 
 use std::path::PathBuf;
 
+use crate::custom_launcher::CustomLauncher;
+use crate::dock_state::AppEntry;
+use crate::watchdog;
+use gio::DesktopAppInfo;
 use gtk4::glib;
+use gtk4::prelude::*;
+use once_cell::sync::OnceCell;
 use serde::{Deserialize, Serialize};
-use std::future::Future;
+use once_cell::sync::Lazy;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use futures_util::future::{self, Either};
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TrySendError;
+use zbus::{Connection, Message};
 use zvariant_derive::Type;
 
 pub const DEST: &str = "com.System76.PopShell";
 pub const PATH: &str = "/com/System76/PopShell";
 
+/// A request to the daemon about a single window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowOperation {
+    Focus,
+    Close,
+    Minimize,
+    Maximize,
+    MoveToWorkspace,
+}
+
 #[derive(Debug)]
 pub enum Event {
     WindowList,
     Activate((u32, u32)),
     Close((u32, u32)),
-    Favorite((String, bool)),
+    Toggle((u32, u32)),
+    Minimize((u32, u32)),
+    Maximize((u32, u32)),
+    /// Moves the window to the 1-based workspace
+    MoveToWorkspace((u32, u32), u32),
+    CloseAll(Vec<(u32, u32)>),
+    /// The daemon failed to apply `op` to the window, changes shown before it replied are undone
+    OperationFailed {
+        op: WindowOperation,
+        entity: (u32, u32),
+        message: String,
+    },
+    /// Minimizes the windows on the output, or on every output if `None`
+    MinimizeAll(Option<String>),
+    /// Restores the windows minimized by `MinimizeAll` that are still minimized
+    RestoreAll,
+    DaemonStatus(bool),
+    /// Another daemon took over, the windows of the old one are forgotten
+    DaemonRestarted,
+    /// Saves or removes the app with desktop id `id`, inserting it at `index` if given
+    Favorite {
+        id: String,
+        favorite: bool,
+        index: Option<u32>,
+    },
     RefreshFromCache,
+    /// Focuses the window at `index` of the app in popover order, launching the app if it has
+    /// no windows. `app_id` is a desktop id, with or without ".desktop", or a window app id.
+    ActivateIndex { app_id: String, index: usize },
+    /// Writes a desktop entry for the window, which has none, and saves it
+    PinWindow(Item),
+    /// Removes the app with the generated desktop id and deletes its desktop entry
+    DeleteGenerated(String),
+    /// Saves a launcher for a URL or command, e.g. a web app
+    AddCustomLauncher(CustomLauncher),
+    /// Moves the saved app with desktop id `id` to `new_index` among the saved apps
+    Reorder { id: String, new_index: u32 },
+    /// Forgets the launch history shown in the recent apps section
+    ClearRecent,
+    /// Runs the sync requested by the preceding `WindowList` and `RefreshFromCache` events
+    SyncModels,
+    Launch(String),
+    LaunchFailed(String, String),
+    /// Replies with the desktop ids of the saved apps once the preceding events are handled
+    SavedIds(tokio::sync::oneshot::Sender<Vec<String>>),
+    /// Launcher entry properties an app sent for its desktop id, `None` once the app left the bus
+    LauncherEntry(String, Option<HashMap<String, zvariant::OwnedValue>>),
+    /// Clears the badge, progress and urgency the app with desktop id `id` sent, e.g. a count
+    /// the app never resets
+    ClearNotifications(String),
+    /// Writing the saved apps failed, they stay in the dock and are written with the next change
+    SaveFailed(String),
+}
+
+impl Event {
+    /// The name of the variant, which names the event in the watchdog log.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::WindowList => "WindowList",
+            Event::Activate(_) => "Activate",
+            Event::Close(_) => "Close",
+            Event::Toggle(_) => "Toggle",
+            Event::Minimize(_) => "Minimize",
+            Event::Maximize(_) => "Maximize",
+            Event::MoveToWorkspace(..) => "MoveToWorkspace",
+            Event::CloseAll(_) => "CloseAll",
+            Event::OperationFailed { .. } => "OperationFailed",
+            Event::MinimizeAll(_) => "MinimizeAll",
+            Event::RestoreAll => "RestoreAll",
+            Event::DaemonStatus(_) => "DaemonStatus",
+            Event::DaemonRestarted => "DaemonRestarted",
+            Event::Favorite { .. } => "Favorite",
+            Event::RefreshFromCache => "RefreshFromCache",
+            Event::ActivateIndex { .. } => "ActivateIndex",
+            Event::PinWindow(_) => "PinWindow",
+            Event::DeleteGenerated(_) => "DeleteGenerated",
+            Event::AddCustomLauncher(_) => "AddCustomLauncher",
+            Event::Reorder { .. } => "Reorder",
+            Event::ClearRecent => "ClearRecent",
+            Event::SyncModels => "SyncModels",
+            Event::Launch(_) => "Launch",
+            Event::LaunchFailed(..) => "LaunchFailed",
+            Event::SavedIds(_) => "SavedIds",
+            Event::LauncherEntry(..) => "LauncherEntry",
+            Event::ClearNotifications(_) => "ClearNotifications",
+            Event::SaveFailed(_) => "SaveFailed",
+        }
+    }
 }
 
+/// A window as the daemon's `WindowList` method and `WindowsChanged` signal send it, `a((uu)sss)`.
 #[derive(Debug, Deserialize, Serialize, Type, Clone, PartialEq, Eq)]
+pub struct WireItem {
+    pub entity: (u32, u32),
+    pub name: String,
+    pub description: String,
+    pub desktop_entry: String,
+}
+
+/// A window as `WindowListExtended` sends it, `a((uu)sssa{sv})`. The extras carry the fields
+/// of [`Item`] beyond the legacy ones, keyed by field name, daemons leave out what they don't know.
+#[derive(Debug, Deserialize, Serialize, Type, Clone, PartialEq)]
+pub struct ExtendedWireItem {
+    pub entity: (u32, u32),
+    pub name: String,
+    pub description: String,
+    pub desktop_entry: String,
+    pub extras: HashMap<String, zvariant::OwnedValue>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct Item {
     pub(crate) entity: (u32, u32),
     pub(crate) name: String,
     pub(crate) description: String,
     pub(crate) desktop_entry: String,
+    /// wayland app_id or X11 WM_CLASS of the window
+    pub(crate) app_id: String,
+    /// the window has set the urgency hint
+    pub(crate) urgent: bool,
+    /// the window has keyboard focus
+    pub(crate) focused: bool,
+    /// 1-based index of the window's workspace, 0 if unknown
+    pub(crate) workspace: u32,
+    /// icon name or path the compositor knows for the window, empty if none
+    pub(crate) icon: String,
+    /// connector name of the output showing the window, e.g. "DP-1", empty if unknown
+    pub(crate) output: String,
+    /// position and size of the window on its output in logical pixels, a size of 0 if unknown
+    pub(crate) x: i32,
+    pub(crate) y: i32,
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) minimized: bool,
+    /// path of the window's executable, empty if unknown
+    pub(crate) exe: String,
 }
 
-#[derive(Clone, Debug, Default, glib::Boxed)]
+impl Item {
+    pub fn workspace(&self) -> Option<u32> {
+        match self.workspace {
+            0 => None,
+            workspace => Some(workspace),
+        }
+    }
+
+    pub fn output(&self) -> Option<&str> {
+        Some(self.output.as_str()).filter(|output| !output.is_empty())
+    }
+
+    pub fn exe(&self) -> Option<&str> {
+        Some(self.exe.as_str()).filter(|exe| !exe.is_empty())
+    }
+
+    /// Checks whether the window covers part of the given area of its output.
+    pub fn intersects(&self, x: i32, y: i32, width: i32, height: i32) -> bool {
+        if self.minimized || self.width == 0 || self.height == 0 {
+            return false;
+        }
+        self.x < x + width
+            && x < self.x + self.width as i32
+            && self.y < y + height
+            && y < self.y + self.height as i32
+    }
+}
+
+impl From<WireItem> for Item {
+    fn from(wire: WireItem) -> Self {
+        Item {
+            entity: wire.entity,
+            name: wire.name,
+            description: wire.description,
+            desktop_entry: wire.desktop_entry,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<ExtendedWireItem> for Item {
+    fn from(wire: ExtendedWireItem) -> Self {
+        let extras = &wire.extras;
+        let string = |key: &str| {
+            extras
+                .get(key)
+                .and_then(|v| String::try_from(v.clone()).ok())
+                .unwrap_or_default()
+        };
+        let flag = |key: &str| {
+            extras
+                .get(key)
+                .and_then(|v| bool::try_from(v.clone()).ok())
+                .unwrap_or_default()
+        };
+        let unsigned = |key: &str| {
+            extras
+                .get(key)
+                .and_then(|v| u32::try_from(v.clone()).ok())
+                .unwrap_or_default()
+        };
+        let signed = |key: &str| {
+            extras
+                .get(key)
+                .and_then(|v| i32::try_from(v.clone()).ok())
+                .unwrap_or_default()
+        };
+        Item {
+            app_id: string("app_id"),
+            urgent: flag("urgent"),
+            focused: flag("focused"),
+            workspace: unsigned("workspace"),
+            icon: string("icon"),
+            output: string("output"),
+            x: signed("x"),
+            y: signed("y"),
+            width: unsigned("width"),
+            height: unsigned("height"),
+            minimized: flag("minimized"),
+            exe: string("exe"),
+            entity: wire.entity,
+            name: wire.name,
+            description: wire.description,
+            desktop_entry: wire.desktop_entry,
+        }
+    }
+}
+
+/// Decodes a window list sent by the daemon, in the extended format if `extended`.
+pub fn decode_window_list(message: &Message, extended: bool) -> zbus::Result<Vec<Item>> {
+    Ok(if extended {
+        let items = message.body::<Vec<ExtendedWireItem>>()?;
+        items.into_iter().map(Item::from).collect()
+    } else {
+        let items = message.body::<Vec<WireItem>>()?;
+        items.into_iter().map(Item::from).collect()
+    })
+}
+
+#[derive(Clone, Debug, Default, PartialEq, glib::Boxed)]
 #[boxed_type(name = "BoxedWindowList")]
 pub struct BoxedWindowList(pub Vec<Item>);
 
+impl BoxedWindowList {
+    /// The first window demanding attention, if any.
+    pub fn urgent(&self) -> Option<&Item> {
+        self.0.iter().find(|item| item.urgent)
+    }
+
+    /// The window that has keyboard focus, if it belongs to this list.
+    pub fn focused(&self) -> Option<&Item> {
+        self.0.iter().find(|item| item.focused)
+    }
+
+    /// The window that was focused most recently, `None` if none of them is known to have been
+    /// focused.
+    pub fn most_recent(&self) -> Option<&Item> {
+        let order = FOCUS_ORDER.lock().unwrap();
+        self.0
+            .iter()
+            .filter_map(|item| Some((*order.last_focused.get(&item.entity)?, item)))
+            .max_by_key(|(last_focused, _)| *last_focused)
+            .map(|(_, item)| item)
+    }
+
+    /// True if there are windows and every one of them is minimized.
+    pub fn all_minimized(&self) -> bool {
+        !self.0.is_empty() && self.0.iter().all(|item| item.minimized)
+    }
+}
+
+// how recently each window was focused, higher is more recent
+#[derive(Debug, Default)]
+struct FocusOrder {
+    counter: u64,
+    last_focused: HashMap<(u32, u32), u64>,
+}
+
+static FOCUS_ORDER: Lazy<Mutex<FocusOrder>> = Lazy::new(|| Mutex::new(FocusOrder::default()));
+
+/// Records that the dock focused `entity`, which is all the dock knows about daemons that don't
+/// report the order windows were activated in.
+pub fn record_focus(entity: (u32, u32)) {
+    record_focus_locked(&mut FOCUS_ORDER.lock().unwrap(), entity);
+}
+
+/// Updates the focus order for a new window list. `stack` is the order the daemon activated
+/// the windows in, most recent first, without it the window that has focus is the most recent.
+pub fn update_focus_order(windows: &[Item], stack: Option<&[(u32, u32)]>) {
+    let mut order = FOCUS_ORDER.lock().unwrap();
+    order
+        .last_focused
+        .retain(|entity, _| windows.iter().any(|w| w.entity == *entity));
+    match stack {
+        Some(stack) => {
+            let base = order.counter;
+            for (i, entity) in stack.iter().rev().enumerate() {
+                order.last_focused.insert(*entity, base + i as u64 + 1);
+            }
+            order.counter = base + stack.len() as u64;
+        }
+        None => {
+            if let Some(focused) = windows.iter().find(|w| w.focused) {
+                if order.last_focused.get(&focused.entity) != Some(&order.counter) {
+                    record_focus_locked(&mut order, focused.entity);
+                }
+            }
+        }
+    }
+}
+
+fn record_focus_locked(order: &mut FocusOrder, entity: (u32, u32)) {
+    order.counter += 1;
+    let counter = order.counter;
+    order.last_focused.insert(entity, counter);
+}
+
+/// Checks whether two window lists sorted by entity describe different windows, titles, urgency, focus,
+/// workspaces, outputs, geometry or minimized state.
+pub fn window_lists_differ(a: &[Item], b: &[Item]) -> bool {
+    a.len() != b.len()
+        || a
+            .iter()
+            .zip(b.iter())
+            .any(|(a, b)| {
+                a.entity != b.entity
+                    || a.name != b.name
+                    || a.urgent != b.urgent
+                    || a.focused != b.focused
+                    || a.workspace != b.workspace
+                    || a.output != b.output
+                    || (a.x, a.y, a.width, a.height) != (b.x, b.y, b.width, b.height)
+                    || a.minimized != b.minimized
+            })
+}
+
+/// Sends an event from the GTK main loop without waiting for room in the queue.
+/// While the queue is full the event is sent from a task instead, so user actions are never dropped.
+pub fn send_event(tx: &mpsc::Sender<Event>, event: Event) {
+    match tx.try_send(event) {
+        Ok(()) => {}
+        Err(TrySendError::Full(event)) => {
+            log::warn!("Event queue is full, sending {:?} once there is room", event);
+            let tx = tx.clone();
+            glib::MainContext::default().spawn_local(async move {
+                let _ = tx.send(event).await;
+            });
+        }
+        Err(TrySendError::Closed(event)) => {
+            log::error!("Dropping {:?}, the event loop has stopped", event);
+        }
+    }
+}
+
+// the queues of the app list instances that hold a refresh, so bursts of refreshes take up a
+// single slot in each queue
+static REFRESH_QUEUED: Lazy<Mutex<Vec<mpsc::Sender<Event>>>> =
+    Lazy::new(|| Mutex::new(Vec::new()));
+
+// returns false if `tx` already has a refresh queued
+fn mark_refresh_queued(tx: &mpsc::Sender<Event>) -> bool {
+    let mut queued = REFRESH_QUEUED.lock().unwrap();
+    if queued.iter().any(|queued| queued.same_channel(tx)) {
+        return false;
+    }
+    queued.push(tx.clone());
+    true
+}
+
+/// Asks the event loop to sync the models from the cached window list, unless that is already queued.
+pub fn queue_refresh(tx: &mpsc::Sender<Event>) {
+    if mark_refresh_queued(tx) {
+        send_event(tx, Event::RefreshFromCache);
+    }
+}
+
+/// Tells the event loop that the window list changed, unless a refresh is already queued.
+/// It waits for room in the queue.
+pub async fn notify_window_list(tx: &mpsc::Sender<Event>) {
+    if mark_refresh_queued(tx) && tx.send(Event::WindowList).await.is_err() {
+        refresh_dequeued(tx);
+    }
+}
+
+/// Called by the event loop of `tx` when it takes a refresh out of the queue.
+pub fn refresh_dequeued(tx: &mpsc::Sender<Event>) {
+    REFRESH_QUEUED
+        .lock()
+        .unwrap()
+        .retain(|queued| !queued.same_channel(tx));
+}
+
+static CURRENT_WORKSPACE: AtomicU32 = AtomicU32::new(0);
+
+/// Remembers the workspace of the focused window as the current one.
+pub fn update_current_workspace(windows: &[Item]) {
+    if let Some(focused) = windows.iter().find(|w| w.focused) {
+        CURRENT_WORKSPACE.store(focused.workspace, Ordering::Relaxed);
+    }
+}
+
+/// The workspace of the most recently focused window, if known.
+pub fn current_workspace() -> Option<u32> {
+    match CURRENT_WORKSPACE.load(Ordering::Relaxed) {
+        0 => None,
+        workspace => Some(workspace),
+    }
+}
+
+/// Groups the windows by workspace if they are on more than one, in the order the popover
+/// lists them. Returns whether they were grouped.
+pub fn sort_windows(mut windows: Vec<Item>) -> (Vec<Item>, bool) {
+    let grouped = windows
+        .iter()
+        .any(|w| w.workspace() != windows[0].workspace());
+    if grouped {
+        // current workspace first, then by number, unknown workspaces last
+        let current = current_workspace();
+        windows.sort_by_key(|w| match w.workspace() {
+            Some(ws) if Some(ws) == current => (0, ws),
+            Some(ws) => (1, ws),
+            None => (2, 0),
+        });
+    }
+    (windows, grouped)
+}
+
+/// Looks up `icon` for `widget` at `size` logical pixels. The paintable is rendered at the
+/// widget's scale factor, so it stays sharp on hi-dpi outputs.
+pub fn icon_paintable<W: IsA<gtk4::Widget>>(icon: &gio::Icon, size: i32, widget: &W) -> gtk4::IconPaintable {
+    gtk4::IconTheme::for_display(&widget.display()).lookup_by_gicon(
+        icon,
+        size,
+        widget.scale_factor(),
+        gtk4::TextDirection::None,
+        gtk4::IconLookupFlags::empty(),
+    )
+}
+
+/// Checks whether the window `item` belongs to `app_info`.
+pub fn item_matches_app_info(item: &Item, app_info: &DesktopAppInfo) -> bool {
+    AppEntry::from_app_info(app_info).matches(item)
+}
+
+/// How long to wait for the daemon to reply before giving up on a call.
+pub const DAEMON_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The error for calls the daemon didn't reply to in time.
+pub fn timeout_error(method: &str) -> zbus::Error {
+    zbus::Error::Failure(format!("{} timed out, the daemon is not responding", method))
+}
+
+// calls from the main loop give up after DAEMON_TIMEOUT, so a stuck daemon can't block the
+// events queued behind the call
+async fn call_daemon<B>(method: &str, body: &B) -> zbus::Result<Arc<Message>>
+where
+    B: serde::ser::Serialize + zvariant::DynamicType,
+{
+    let conn = session_connection().await?;
+    let call = conn.call_method(Some(DEST), PATH, Some(DEST), method, body);
+    let call = future::select(Box::pin(call), glib::timeout_future(DAEMON_TIMEOUT));
+    match watchdog::timed(method, call).await {
+        Either::Left((result, _)) => result,
+        Either::Right(_) => Err(timeout_error(method)),
+    }
+}
+
+/// Asks the daemon to focus the window `entity`.
+pub async fn window_focus(entity: (u32, u32)) -> zbus::Result<()> {
+    call_daemon("WindowFocus", &(entity,)).await?;
+    Ok(())
+}
+
+/// The methods and signals the running daemon implements.
+#[derive(Debug, Clone, Default)]
+pub struct DaemonCapabilities {
+    /// false until the daemon was introspected, everything is assumed to be supported meanwhile
+    probed: bool,
+    methods: HashSet<String>,
+    signals: HashSet<String>,
+    /// methods that failed as unknown although they were assumed to be supported
+    missing: HashSet<String>,
+}
+
+// names of the `element`s in introspection XML, e.g. all methods
+fn element_names(xml: &str, element: &str) -> HashSet<String> {
+    let prefix = format!("<{} name=\"", element);
+    xml.split(prefix.as_str())
+        .skip(1)
+        .filter_map(|rest| rest.split('"').next())
+        .map(String::from)
+        .collect()
+}
+
+impl DaemonCapabilities {
+    fn from_introspection(xml: &str) -> Self {
+        let interface = format!("<interface name=\"{}\"", DEST);
+        let section = xml
+            .find(&interface)
+            .map(|start| {
+                let rest = &xml[start..];
+                &rest[..rest.find("</interface>").unwrap_or(rest.len())]
+            })
+            .unwrap_or_default();
+        Self {
+            probed: true,
+            methods: element_names(section, "method"),
+            signals: element_names(section, "signal"),
+            missing: HashSet::new(),
+        }
+    }
+
+    pub fn supports_method(&self, method: &str) -> bool {
+        !self.missing.contains(method) && (!self.probed || self.methods.contains(method))
+    }
+
+    /// Unlike methods, which fail when they are missing, a missing signal is never noticed, so
+    /// signals of a daemon that wasn't introspected count as unsupported.
+    pub fn supports_signal(&self, signal: &str) -> bool {
+        self.probed && self.signals.contains(signal)
+    }
+}
+
+static CAPABILITIES: Lazy<Mutex<DaemonCapabilities>> =
+    Lazy::new(|| Mutex::new(DaemonCapabilities::default()));
+
+/// Introspects the daemon, called on connecting and whenever another daemon takes over the bus name.
+pub async fn probe_daemon(conn: &Connection) -> zbus::Result<()> {
+    let call = conn.call_method(
+        Some(DEST),
+        PATH,
+        Some("org.freedesktop.DBus.Introspectable"),
+        "Introspect",
+        &(),
+    );
+    let m = watchdog::timed("Introspect", call).await?;
+    let xml = m.body::<String>()?;
+    *CAPABILITIES.lock().unwrap() = DaemonCapabilities::from_introspection(&xml);
+    Ok(())
+}
+
+pub fn daemon_capabilities() -> DaemonCapabilities {
+    CAPABILITIES.lock().unwrap().clone()
+}
+
+/// Whether the daemon implements `method`.
+pub fn daemon_supports(method: &str) -> bool {
+    CAPABILITIES.lock().unwrap().supports_method(method)
+}
+
+/// Stops calling `method` after the daemon replied that it doesn't know it.
+pub fn mark_method_missing(method: &str) {
+    CAPABILITIES.lock().unwrap().missing.insert(method.to_string());
+}
+
+// calls a method newer daemons have, without calling it again once it turned out to be missing
+async fn call_optional_method<B>(method: &'static str, body: &B) -> zbus::Result<Arc<Message>>
+where
+    B: serde::ser::Serialize + zvariant::DynamicType,
+{
+    if !daemon_supports(method) {
+        return Err(zbus::Error::Unsupported);
+    }
+    match call_daemon(method, body).await {
+        Err(zbus::Error::MethodError(name, description, reply))
+            if name.as_str() == "org.freedesktop.DBus.Error.UnknownMethod" =>
+        {
+            mark_method_missing(method);
+            Err(zbus::Error::MethodError(name, description, reply))
+        }
+        result => result,
+    }
+}
+
+/// Asks the daemon to minimize the window `entity`, or restore it if it is minimized.
+pub async fn window_toggle_minimize(entity: (u32, u32)) -> zbus::Result<()> {
+    call_optional_method("WindowToggleMinimize", &(entity,)).await?;
+    Ok(())
+}
+
+pub async fn window_minimize(entity: (u32, u32)) -> zbus::Result<()> {
+    call_optional_method("WindowMinimize", &(entity,)).await?;
+    Ok(())
+}
+
+pub async fn window_maximize(entity: (u32, u32)) -> zbus::Result<()> {
+    call_optional_method("WindowMaximize", &(entity,)).await?;
+    Ok(())
+}
+
+/// Asks the daemon to move the window `entity` to the 1-based `workspace`.
+pub async fn window_move_to_workspace(entity: (u32, u32), workspace: u32) -> zbus::Result<()> {
+    call_optional_method("WindowMoveToWorkspace", &(entity, workspace)).await?;
+    Ok(())
+}
+
+/// Asks the daemon to close the window `entity`.
+pub async fn window_quit(entity: (u32, u32)) -> zbus::Result<()> {
+    call_daemon("WindowQuit", &(entity,)).await?;
+    Ok(())
+}
+
+/// Requests a thumbnail of the window `entity` as (width, height, RGBA pixels).
+pub async fn window_capture(entity: (u32, u32)) -> zbus::Result<(u32, u32, Vec<u8>)> {
+    let m = call_optional_method("WindowCapture", &(entity,)).await?;
+    m.body::<(u32, u32, Vec<u8>)>()
+}
+
+/// A capture of the window as a texture, `None` if the daemon sent an empty image.
+pub async fn window_preview(entity: (u32, u32)) -> zbus::Result<Option<gtk4::gdk::Texture>> {
+    let (width, height, pixels) = window_capture(entity).await?;
+    if pixels.is_empty() {
+        return Ok(None);
+    }
+    let texture = gtk4::gdk::MemoryTexture::new(
+        width as i32,
+        height as i32,
+        gtk4::gdk::MemoryFormat::R8g8b8a8,
+        &glib::Bytes::from_owned(pixels),
+        width as usize * 4,
+    );
+    Ok(Some(texture.upcast()))
+}
+
+/// The session bus connection shared by the whole app list.
+pub async fn session_connection() -> zbus::Result<Connection> {
+    static CONNECTION: OnceCell<Connection> = OnceCell::new();
+    if let Some(conn) = CONNECTION.get() {
+        return Ok(conn.clone());
+    }
+    let conn = Connection::session().await?;
+    Ok(CONNECTION.get_or_init(|| conn).clone())
+}
+
+pub fn config_path() -> PathBuf {
+    let mut path = glib::user_config_dir();
+    path.push(crate::ID);
+    std::fs::create_dir_all(&path).expect("Could not create directory.");
+    path.push("config.json");
+    path
+}
+
 pub fn data_path() -> PathBuf {
     let mut path = glib::user_data_dir();
     path.push(crate::ID);
@@ -39,17 +687,164 @@ pub fn data_path() -> PathBuf {
     path
 }
 
-pub fn thread_context() -> glib::MainContext {
-    glib::MainContext::thread_default().unwrap_or_else(|| {
-        let ctx = glib::MainContext::new();
-        ctx
-    })
+pub fn recent_path() -> PathBuf {
+    let mut path = glib::user_data_dir();
+    path.push(crate::ID);
+    std::fs::create_dir_all(&path).expect("Could not create directory.");
+    path.push("recent.json");
+    path
 }
 
-pub fn block_on<F>(future: F) -> F::Output
-where
-    F: Future,
-{
-    let ctx = thread_context();
-    ctx.with_thread_default(|| ctx.block_on(future)).unwrap()
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(entity: u32, name: &str) -> Item {
+        Item {
+            entity: (entity, 0),
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn window_list_changes() {
+        let base = vec![window(1, "Inbox"), window(2, "Terminal")];
+        let focused = {
+            let mut list = base.clone();
+            list[1].focused = true;
+            list
+        };
+        let moved = {
+            let mut list = base.clone();
+            list[0].x = 100;
+            list
+        };
+        let exe_changed = {
+            let mut list = base.clone();
+            list[0].exe = "/usr/bin/thunderbird".to_string();
+            list
+        };
+        let cases: &[(&str, Vec<Item>, bool)] = &[
+            ("same", base.clone(), false),
+            (
+                "reorder",
+                vec![window(2, "Terminal"), window(1, "Inbox")],
+                true,
+            ),
+            (
+                "retitle",
+                vec![window(1, "Drafts"), window(2, "Terminal")],
+                true,
+            ),
+            (
+                "replace",
+                vec![window(1, "Inbox"), window(3, "Terminal")],
+                true,
+            ),
+            ("close", vec![window(1, "Inbox")], true),
+            ("focus", focused, true),
+            ("move", moved, true),
+            // fields that don't show in the dock don't cause a refresh
+            ("exe", exe_changed, false),
+        ];
+        for (case, list, differ) in cases {
+            assert_eq!(window_lists_differ(&base, list), *differ, "{}", case);
+            assert_eq!(
+                window_lists_differ(list, &base),
+                *differ,
+                "{} reversed",
+                case
+            );
+        }
+    }
+
+    // the events in the queue, refreshes are taken out the way the event loop does it
+    fn drain(tx: &mpsc::Sender<Event>, rx: &mut mpsc::Receiver<Event>) -> Vec<&'static str> {
+        let mut names = Vec::new();
+        while let Ok(event) = rx.try_recv() {
+            if matches!(event, Event::WindowList | Event::RefreshFromCache) {
+                refresh_dequeued(tx);
+            }
+            names.push(event.name());
+        }
+        names
+    }
+
+    #[test]
+    fn burst_of_refreshes_is_queued_once() {
+        let (tx, mut rx) = mpsc::channel(100);
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            for _ in 0..10 {
+                notify_window_list(&tx).await;
+                queue_refresh(&tx);
+            }
+        });
+        assert_eq!(drain(&tx, &mut rx), vec!["WindowList"]);
+        // a change after the sync started needs another one
+        queue_refresh(&tx);
+        queue_refresh(&tx);
+        assert_eq!(drain(&tx, &mut rx), vec!["RefreshFromCache"]);
+    }
+
+    #[test]
+    fn instances_queue_their_own_refresh() {
+        let (tx_a, mut rx_a) = mpsc::channel(100);
+        let (tx_b, mut rx_b) = mpsc::channel(100);
+        queue_refresh(&tx_a);
+        queue_refresh(&tx_b);
+        queue_refresh(&tx_a);
+        assert_eq!(drain(&tx_a, &mut rx_a), vec!["RefreshFromCache"]);
+        assert_eq!(drain(&tx_b, &mut rx_b), vec!["RefreshFromCache"]);
+    }
+
+    #[test]
+    fn user_actions_keep_their_place_between_refreshes() {
+        let (tx, mut rx) = mpsc::channel(100);
+        queue_refresh(&tx);
+        send_event(&tx, Event::Launch("firefox.desktop".to_string()));
+        queue_refresh(&tx);
+        send_event(
+            &tx,
+            Event::Favorite {
+                id: "firefox.desktop".to_string(),
+                favorite: true,
+                index: None,
+            },
+        );
+        assert_eq!(
+            drain(&tx, &mut rx),
+            vec!["RefreshFromCache", "Launch", "Favorite"]
+        );
+    }
+
+    #[test]
+    fn window_list_flood_keeps_user_actions() {
+        // without the coalescing the flood would fill the queue and block on the full queue
+        let (tx, mut rx) = mpsc::channel(8);
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let mut events = Vec::new();
+        runtime.block_on(async {
+            for i in 0..10_000 {
+                notify_window_list(&tx).await;
+                if i % 100 == 0 {
+                    send_event(&tx, Event::Launch(i.to_string()));
+                }
+                // the event loop gets to run now and then
+                if i % 10 == 0 {
+                    events.extend(drain(&tx, &mut rx));
+                }
+            }
+        });
+        events.extend(drain(&tx, &mut rx));
+
+        let refreshes = events.iter().filter(|name| **name == "WindowList").count();
+        assert!(refreshes <= 1_000, "{} refreshes", refreshes);
+        assert_eq!(events.iter().filter(|name| **name == "Launch").count(), 100);
+    }
 }
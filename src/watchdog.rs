@@ -0,0 +1,88 @@
+// SPDX-License-Identifier: MPL-2.0-only
+
+//! Timing of the steps that may stall the dock: D-Bus calls, model syncs and thread joins.
+//! Steps taking longer than [`SLOW_THRESHOLD`] are logged as warnings, the others at debug
+//! level, so `RUST_LOG=cosmic_dock_app_list::watchdog=debug` shows every timing.
+//!
+//! Spans opened while another span of the same thread is open are logged with its name, e.g.
+//! `event WindowList > refresh models > sync models`, so the output names the event that was
+//! being processed when a step was slow.
+
+use std::cell::{Cell, RefCell};
+use std::future::Future;
+use std::marker::PhantomData;
+use std::time::{Duration, Instant};
+
+pub const SLOW_THRESHOLD: Duration = Duration::from_millis(250);
+
+thread_local! {
+    static OPEN_SPANS: RefCell<Vec<(u64, String)>> = RefCell::new(Vec::new());
+    static NEXT_ID: Cell<u64> = Cell::new(0);
+}
+
+/// A step that is timed until it is dropped.
+#[must_use]
+#[derive(Debug)]
+pub struct Span {
+    id: u64,
+    path: String,
+    start: Instant,
+    // the span is registered with its thread
+    _not_send: PhantomData<*const ()>,
+}
+
+/// Opens a span named `name` nested in the spans open on this thread.
+pub fn span(name: impl Into<String>) -> Span {
+    let id = NEXT_ID.with(|next| {
+        let id = next.get();
+        next.set(id.wrapping_add(1));
+        id
+    });
+    let path = nested_path(&name.into());
+    OPEN_SPANS.with(|spans| spans.borrow_mut().push((id, path.clone())));
+    Span {
+        id,
+        path,
+        start: Instant::now(),
+        _not_send: PhantomData,
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        report(&self.path, self.start.elapsed());
+        // futures on the main context may close spans out of order
+        OPEN_SPANS.with(|spans| {
+            let mut spans = spans.borrow_mut();
+            if let Some(position) = spans.iter().rposition(|(id, _)| *id == self.id) {
+                spans.remove(position);
+            }
+        });
+    }
+}
+
+/// Awaits `future` and logs how long it took. The name is nested in the spans open when the
+/// future is created, it isn't registered as a span itself because the future may move between
+/// threads.
+pub async fn timed<F: Future>(name: &str, future: F) -> F::Output {
+    let path = nested_path(name);
+    let start = Instant::now();
+    let output = future.await;
+    report(&path, start.elapsed());
+    output
+}
+
+fn nested_path(name: &str) -> String {
+    OPEN_SPANS.with(|spans| match spans.borrow().last() {
+        Some((_, parent)) => format!("{} > {}", parent, name),
+        None => name.to_string(),
+    })
+}
+
+fn report(path: &str, elapsed: Duration) {
+    if elapsed >= SLOW_THRESHOLD {
+        log::warn!("{} took {} ms", path, elapsed.as_millis());
+    } else {
+        log::debug!("{} took {} ms", path, elapsed.as_millis());
+    }
+}
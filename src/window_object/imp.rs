@@ -0,0 +1,75 @@
+// SPDX-License-Identifier: MPL-2.0-only
+
+use std::cell::RefCell;
+
+use crate::utils::{Event, Item};
+use glib::{ParamFlags, ParamSpec, Value};
+use gtk4::gdk::glib::ParamSpecBoolean;
+use gtk4::gdk::glib::ParamSpecString;
+use gtk4::glib;
+use gtk4::prelude::*;
+use gtk4::subclass::prelude::*;
+use once_cell::sync::Lazy;
+use once_cell::sync::OnceCell;
+use tokio::sync::mpsc::Sender;
+
+// Object holding the state
+#[derive(Default)]
+pub struct WindowObject {
+    pub(super) item: RefCell<Option<Item>>,
+    pub(super) tx: OnceCell<Sender<Event>>,
+}
+
+// The central trait for subclassing a GObject
+#[glib::object_subclass]
+impl ObjectSubclass for WindowObject {
+    const NAME: &'static str = "WindowObject";
+    type Type = super::WindowObject;
+    type ParentType = glib::Object;
+}
+
+// Trait shared by all GObjects
+impl ObjectImpl for WindowObject {
+    fn properties() -> &'static [ParamSpec] {
+        static PROPERTIES: Lazy<Vec<ParamSpec>> = Lazy::new(|| {
+            vec![
+                ParamSpecString::new("title", "title", "title of the window", None, ParamFlags::READABLE),
+                ParamSpecString::new(
+                    "app-id",
+                    "app-id",
+                    "wayland app_id or X11 WM_CLASS of the window",
+                    None,
+                    ParamFlags::READABLE,
+                ),
+                ParamSpecBoolean::new("focused", "focused", "the window has keyboard focus", false, ParamFlags::READABLE),
+                ParamSpecBoolean::new(
+                    "urgent",
+                    "urgent",
+                    "the window has set the urgency hint",
+                    false,
+                    ParamFlags::READABLE,
+                ),
+                ParamSpecBoolean::new(
+                    "minimized",
+                    "minimized",
+                    "the window is minimized",
+                    false,
+                    ParamFlags::READABLE,
+                ),
+            ]
+        });
+        PROPERTIES.as_ref()
+    }
+
+    fn property(&self, _obj: &Self::Type, _id: usize, pspec: &ParamSpec) -> Value {
+        let item = self.item.borrow();
+        match pspec.name() {
+            "title" => item.as_ref().map(|item| item.name.clone()).to_value(),
+            "app-id" => item.as_ref().map(|item| item.app_id.clone()).to_value(),
+            "focused" => item.as_ref().map(|item| item.focused).unwrap_or_default().to_value(),
+            "urgent" => item.as_ref().map(|item| item.urgent).unwrap_or_default().to_value(),
+            "minimized" => item.as_ref().map(|item| item.minimized).unwrap_or_default().to_value(),
+            _ => unimplemented!(),
+        }
+    }
+}
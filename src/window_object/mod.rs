@@ -0,0 +1,117 @@
+// SPDX-License-Identifier: MPL-2.0-only
+
+use crate::utils::{send_event, Event, Item};
+use gtk4::gdk::glib::Object;
+use gtk4::gdk::subclass::prelude::ObjectSubclassExt;
+use gtk4::prelude::*;
+use gtk4::{gio, glib};
+use tokio::sync::mpsc::Sender;
+
+mod imp;
+
+glib::wrapper! {
+    /// A window listed by the daemon, for hosts that show windows outside of the dock.
+    pub struct WindowObject(ObjectSubclass<imp::WindowObject>);
+}
+
+impl WindowObject {
+    pub fn new(item: Item, tx: Sender<Event>) -> Self {
+        let self_: Self = Object::new(&[]).expect("Failed to create `WindowObject`.");
+        let imp = imp::WindowObject::from_instance(&self_);
+        imp.item.replace(Some(item));
+        imp.tx.set(tx).unwrap();
+        self_
+    }
+
+    pub fn item(&self) -> Option<Item> {
+        let imp = imp::WindowObject::from_instance(self);
+        imp.item.borrow().clone()
+    }
+
+    pub fn entity(&self) -> Option<(u32, u32)> {
+        let imp = imp::WindowObject::from_instance(self);
+        imp.item.borrow().as_ref().map(|item| item.entity)
+    }
+
+    /// Replaces the window state, notifying the properties that changed.
+    pub fn set_item(&self, item: Item) {
+        let imp = imp::WindowObject::from_instance(self);
+        let old = imp.item.replace(Some(item.clone()));
+        let changed = |f: fn(&Item) -> bool| old.as_ref().map(|old| !f(old)).unwrap_or(true);
+        if changed(|old| old.name == item.name) {
+            self.notify("title");
+        }
+        if changed(|old| old.app_id == item.app_id) {
+            self.notify("app-id");
+        }
+        if changed(|old| old.focused == item.focused) {
+            self.notify("focused");
+        }
+        if changed(|old| old.urgent == item.urgent) {
+            self.notify("urgent");
+        }
+        if changed(|old| old.minimized == item.minimized) {
+            self.notify("minimized");
+        }
+    }
+
+    /// Focuses the window.
+    pub fn activate(&self) {
+        if let Some(entity) = self.entity() {
+            self.send(Event::Activate(entity));
+        }
+    }
+
+    /// Asks the window to close.
+    pub fn close(&self) {
+        if let Some(entity) = self.entity() {
+            self.send(Event::Close(entity));
+        }
+    }
+
+    fn send(&self, event: Event) {
+        let imp = imp::WindowObject::from_instance(self);
+        send_event(imp.tx.get().unwrap(), event);
+    }
+}
+
+/// Updates `model` to hold one [`WindowObject`] per window, in the order of `windows`.
+pub fn update_window_model(model: &gio::ListStore, windows: &[Item], tx: &Sender<Event>) {
+    // windows that are gone are removed, the rest are updated in place
+    let mut i: u32 = 0;
+    while let Some(item) = model.item(i) {
+        let window_object = item.downcast::<WindowObject>().unwrap();
+        match windows.iter().find(|w| Some(w.entity) == window_object.entity()) {
+            Some(window) => {
+                window_object.set_item(window.clone());
+                i += 1;
+            }
+            None => model.remove(i),
+        }
+    }
+    for (index, window) in windows.iter().enumerate() {
+        let index = index as u32;
+        let current = model
+            .item(index)
+            .and_then(|item| item.downcast::<WindowObject>().ok())
+            .and_then(|window_object| window_object.entity());
+        if current == Some(window.entity) {
+            continue;
+        }
+        let existing = (index + 1..model.n_items()).find(|&j| {
+            model
+                .item(j)
+                .and_then(|item| item.downcast::<WindowObject>().ok())
+                .and_then(|window_object| window_object.entity())
+                == Some(window.entity)
+        });
+        match existing {
+            Some(j) => {
+                let window_object = model.item(j).unwrap();
+                model.remove(j);
+                model.insert(index, &window_object);
+            }
+            None => model.insert(index, &WindowObject::new(window.clone(), tx.clone())),
+        }
+    }
+}